@@ -1,4 +1,6 @@
-use crate::{ControllerOptions, GatewayOptions, ManagerOptions, TracingOptions};
+use crate::{
+    AuthOptions, ControllerOptions, GatewayOptions, ManagerOptions, OperatorOptions, TracingOptions,
+};
 use clap::{ColorChoice, Parser};
 use std::path::PathBuf;
 
@@ -30,6 +32,9 @@ pub enum Command {
     Operator {
         #[command(flatten)]
         controller_options: ControllerOptions,
+
+        #[command(flatten)]
+        operator_options: OperatorOptions,
     },
 
     /// Run only the API server without the operator
@@ -40,6 +45,9 @@ pub enum Command {
 
         #[command(flatten)]
         gateway_options: GatewayOptions,
+
+        #[command(flatten)]
+        auth_options: AuthOptions,
     },
 
     /// Run the manager API server
@@ -50,6 +58,9 @@ pub enum Command {
 
         #[command(flatten)]
         manager_options: ManagerOptions,
+
+        #[command(flatten)]
+        auth_options: AuthOptions,
     },
 
     /// Export CRD or schema definitions
@@ -0,0 +1,77 @@
+use super::{LeaderElection, OperatorOptions};
+use crate::{Error, Result};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+use std::time::SystemTime;
+use tokio::net::TcpListener;
+
+// Store the operator start time
+static OPERATOR_START_TIME: LazyLock<SystemTime> = LazyLock::new(SystemTime::now);
+
+/// Represents the health status of the operator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperatorStatus {
+    /// Indicates if the operator is operational.
+    pub ok: bool,
+
+    /// The version of the application.
+    pub version: String,
+
+    /// The uptime of the operator in seconds.
+    pub uptime: u64,
+
+    /// The identity this replica takes part in the leader election with.
+    pub identity: String,
+
+    /// Indicates if this replica is the leader and is currently reconciling resources.
+    pub leader: bool,
+}
+
+/// Handler for the `/health/status` endpoint.
+async fn status(State(election): State<LeaderElection>) -> Response {
+    let status = OperatorStatus {
+        ok: true,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime: OPERATOR_START_TIME.elapsed().unwrap_or_default().as_secs(),
+        identity: election.identity().to_string(),
+        leader: election.is_leader(),
+    };
+    (StatusCode::OK, Json(status)).into_response()
+}
+
+/// Handler for the `/health/ping` endpoint.
+async fn ping() -> Response {
+    (StatusCode::NO_CONTENT, ()).into_response()
+}
+
+/// Handler for the `/health/leader` endpoint. Answers with `503 Service Unavailable` on the
+/// replicas standing by, so it can be used to tell which replica is the leader.
+async fn leader(State(election): State<LeaderElection>) -> Response {
+    match election.is_leader() {
+        true => (StatusCode::NO_CONTENT, ()).into_response(),
+        false => (StatusCode::SERVICE_UNAVAILABLE, ()).into_response(),
+    }
+}
+
+/// Serve the health endpoints of the operator until the process stops.
+#[tracing::instrument(name = "HealthServer", skip_all)]
+pub async fn serve_health(options: &OperatorOptions, election: LeaderElection) -> Result<()> {
+    let _ = *OPERATOR_START_TIME;
+    let address = SocketAddr::new(options.health_host, options.health_port);
+    let router = Router::new()
+        .route("/health/status", get(status))
+        .route("/health/ping", get(ping))
+        .route("/health/leader", get(leader))
+        .with_state(election);
+
+    // --- Set up the TCP listener and bind to the address.
+    let listener = TcpListener::bind(&address).await.map_err(Error::from)?;
+    tracing::info!("Health server listening on http://{}", address);
+    axum::serve(listener, router).await.map_err(Error::from)
+}
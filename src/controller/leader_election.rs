@@ -0,0 +1,340 @@
+use super::{Controller, OperatorOptions};
+use crate::{Error, Result};
+use chrono::{DateTime, Utc};
+use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::MicroTime;
+use kube::api::{ObjectMeta, PostParams};
+use kube::Api;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+/// Interval between two attempts to acquire or renew the lease.
+const LEADER_ELECTION_RETRY_PERIOD: Duration = Duration::from_secs(2);
+
+/// Elects a single leader among the replicas of the operator using a `coordination.k8s.io/v1`
+/// `Lease`. Only the leader runs the reconcilers, the other replicas stand by until the lease
+/// expires or is released, and one of them takes it over.
+#[derive(Debug, Clone)]
+pub struct LeaderElection {
+    api: Api<Lease>,
+    name: String,
+    identity: String,
+    lease_duration: Duration,
+    renew_deadline: Duration,
+    is_leader: watch::Sender<bool>,
+}
+
+/// The last record of the lease read by this replica, along with the local time at which it
+/// was first seen. The expiry of a lease held by another replica is measured from this time
+/// rather than from its `renewTime`, so that the election does not depend on the clocks of
+/// the replicas being synchronized.
+#[derive(Debug, Default)]
+struct ObservedLease(Option<(LeaseSpec, Instant)>);
+
+impl ObservedLease {
+    /// Record the given spec of the lease, and return the local time at which it last changed.
+    fn observe(&mut self, spec: &LeaseSpec, now: Instant) -> Instant {
+        match &self.0 {
+            Some((observed, observed_at)) if observed == spec => *observed_at,
+            _ => {
+                self.0 = Some((spec.clone(), now));
+                now
+            }
+        }
+    }
+}
+
+/// Check if the lease can be acquired by the given identity, meaning it is either already held
+/// by it, held by nobody, or expired because its holder did not renew it for the duration of
+/// the lease since the record last changed.
+fn can_acquire(spec: &LeaseSpec, identity: &str, observed_at: Instant, now: Instant) -> bool {
+    match spec.holder_identity.as_deref() {
+        None | Some("") => true,
+        Some(holder) if holder == identity => true,
+        Some(_) => {
+            let duration = spec.lease_duration_seconds.unwrap_or_default();
+            let duration = Duration::from_secs(u64::try_from(duration).unwrap_or_default());
+            observed_at + duration < now
+        }
+    }
+}
+
+impl LeaderElection {
+    /// Create the election from the options of the operator, which must use a renew deadline
+    /// lower than the lease duration so that the leader steps down before its lease expires.
+    pub fn new(controller: &Controller, options: &OperatorOptions) -> Result<Self> {
+        if options.renew_deadline >= options.lease_duration {
+            return Err(Error::generic(format!(
+                "The renew deadline ({}s) must be lower than the lease duration ({}s)",
+                options.renew_deadline, options.lease_duration
+            ))
+            .with_name("E_INVALID_RENEW_DEADLINE"));
+        }
+        let identity = std::env::var("POD_NAME")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_else(|_| Uuid::new_v4().to_string());
+        let (is_leader, _) = watch::channel(false);
+        Ok(Self {
+            api: Api::namespaced(controller.get_client(), &controller.get_namespace()),
+            name: options.lease_name.clone(),
+            identity,
+            lease_duration: Duration::from_secs(options.lease_duration),
+            renew_deadline: Duration::from_secs(options.renew_deadline),
+            is_leader,
+        })
+    }
+
+    /// Returns the identity this replica holds the lease with.
+    pub fn identity(&self) -> &str {
+        &self.identity
+    }
+
+    /// Check if this replica currently holds the lease.
+    pub fn is_leader(&self) -> bool {
+        *self.is_leader.borrow()
+    }
+
+    /// Subscribe to the changes of leadership of this replica.
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.is_leader.subscribe()
+    }
+
+    /// Update the leadership of this replica, logging the transitions.
+    pub fn set_leader(&self, is_leader: bool) {
+        let is_modified = self.is_leader.send_if_modified(|current| {
+            let is_modified = *current != is_leader;
+            *current = is_leader;
+            is_modified
+        });
+        if is_modified && is_leader {
+            tracing::info!("Acquired the lease '{}' as '{}'", self.name, self.identity);
+        } else if is_modified {
+            tracing::warn!("Lost the lease '{}' as '{}'", self.name, self.identity);
+        }
+    }
+
+    /// Build the spec of the lease held by this replica.
+    fn lease_spec(&self, acquire_time: DateTime<Utc>, transitions: i32) -> LeaseSpec {
+        LeaseSpec {
+            holder_identity: Some(self.identity.clone()),
+            lease_duration_seconds: Some(self.lease_duration.as_secs() as i32),
+            acquire_time: Some(MicroTime(acquire_time)),
+            renew_time: Some(MicroTime(Utc::now())),
+            lease_transitions: Some(transitions),
+        }
+    }
+
+    /// Try to acquire the lease, or renew it if already held, and return whether this
+    /// replica holds the lease afterwards.
+    async fn try_acquire_or_renew(&self, observed: &mut ObservedLease) -> Result<bool> {
+        let now = Utc::now();
+        let result = match self.api.get_opt(&self.name).await? {
+            // --- Create the lease if it does not exist yet.
+            None => {
+                let lease = Lease {
+                    metadata: ObjectMeta {
+                        name: Some(self.name.clone()),
+                        ..Default::default()
+                    },
+                    spec: Some(self.lease_spec(now, 0)),
+                };
+                self.api.create(&PostParams::default(), &lease).await
+            }
+
+            // --- Otherwise, take it over if it's free or expired. The lease is replaced with
+            // --- the `resourceVersion` it was read with, so only one replica can win a race.
+            Some(lease) => {
+                let spec = lease.spec.clone().unwrap_or_default();
+                let instant = Instant::now();
+                let observed_at = observed.observe(&spec, instant);
+                if !can_acquire(&spec, &self.identity, observed_at, instant) {
+                    return Ok(false);
+                }
+                let is_holder = spec.holder_identity.as_deref() == Some(self.identity.as_str());
+                let transitions = spec.lease_transitions.unwrap_or_default();
+                let spec = match is_holder {
+                    true => LeaseSpec {
+                        renew_time: Some(MicroTime(now)),
+                        ..spec
+                    },
+                    false => self.lease_spec(now, transitions + 1),
+                };
+                let lease = Lease {
+                    metadata: lease.metadata,
+                    spec: Some(spec),
+                };
+                self.api
+                    .replace(&self.name, &PostParams::default(), &lease)
+                    .await
+            }
+        };
+
+        match result {
+            Ok(lease) => {
+                let _ = observed.observe(&lease.spec.unwrap_or_default(), Instant::now());
+                Ok(true)
+            }
+            Err(kube::Error::Api(error)) if error.code == 409 => Ok(false),
+            Err(error) => Err(Error::from(error)),
+        }
+    }
+
+    /// Run the election until the process stops, acquiring the lease when it's free and
+    /// renewing it while held. The leadership is lost as soon as another replica holds the
+    /// lease, or if the lease could not be renewed within the renew deadline.
+    #[tracing::instrument(name = "LeaderElection", skip_all, fields(identity = %self.identity))]
+    pub async fn run(&self) -> Result<()> {
+        let mut renewed_at = Instant::now();
+        let mut observed = ObservedLease::default();
+        loop {
+            // --- While leading, an attempt may not outlive the renew deadline, so that a
+            // --- hanging request to the API server cannot keep a stale leader running.
+            let deadline = match self.is_leader() {
+                true => self.renew_deadline.saturating_sub(renewed_at.elapsed()),
+                false => self.renew_deadline,
+            };
+            let attempt = self.try_acquire_or_renew(&mut observed);
+            let result = match tokio::time::timeout(deadline, attempt).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::generic(format!(
+                    "Timed out after {}s while acquiring the lease '{}'",
+                    deadline.as_secs(),
+                    self.name
+                ))
+                .with_name("E_LEASE_TIMEOUT")),
+            };
+            match result {
+                Ok(true) => {
+                    renewed_at = Instant::now();
+                    self.set_leader(true);
+                }
+                Ok(false) => self.set_leader(false),
+                Err(error) => {
+                    let _ = error.trace();
+                    if renewed_at.elapsed() >= self.renew_deadline {
+                        self.set_leader(false);
+                    }
+                }
+            }
+            tokio::time::sleep(LEADER_ELECTION_RETRY_PERIOD).await;
+        }
+    }
+
+    /// Release the lease if held, so that another replica can take over right away
+    /// instead of waiting for the lease to expire. The lease is replaced with the
+    /// `resourceVersion` it was read with, so it is left untouched if another replica
+    /// has taken it over in the meantime.
+    #[tracing::instrument(name = "StepDown", skip_all, fields(identity = %self.identity))]
+    pub async fn step_down(&self) -> Result<()> {
+        if !self.is_leader() {
+            return Ok(());
+        }
+        self.set_leader(false);
+        let Some(lease) = self.api.get_opt(&self.name).await? else {
+            return Ok(());
+        };
+        let spec = lease.spec.unwrap_or_default();
+        if spec.holder_identity.as_deref() != Some(self.identity.as_str()) {
+            return Ok(());
+        }
+        let lease = Lease {
+            metadata: lease.metadata,
+            spec: Some(LeaseSpec {
+                holder_identity: None,
+                lease_duration_seconds: Some(1),
+                ..spec
+            }),
+        };
+        match self
+            .api
+            .replace(&self.name, &PostParams::default(), &lease)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(kube::Error::Api(error)) if error.code == 409 => Ok(()),
+            Err(error) => Err(Error::from(error)),
+        }
+    }
+}
+
+/// Wait until the process receives either `SIGTERM` or `SIGINT`.
+pub async fn shutdown_signal() {
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                let _ = signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        () = terminate => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lease(holder: Option<&str>) -> LeaseSpec {
+        LeaseSpec {
+            holder_identity: holder.map(ToString::to_string),
+            lease_duration_seconds: Some(15),
+            renew_time: Some(MicroTime(Utc::now())),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_can_acquire_free_lease() {
+        let now = Instant::now();
+        assert!(can_acquire(&lease(None), "a", now, now));
+        assert!(can_acquire(&lease(Some("")), "a", now, now));
+    }
+
+    #[test]
+    fn test_can_acquire_own_lease() {
+        let now = Instant::now();
+        assert!(can_acquire(&lease(Some("a")), "a", now, now));
+    }
+
+    #[test]
+    fn test_can_acquire_held_lease() {
+        let now = Instant::now();
+        let later = now + Duration::from_secs(5);
+        assert!(!can_acquire(&lease(Some("b")), "a", now, later));
+    }
+
+    #[test]
+    fn test_can_acquire_expired_lease() {
+        let now = Instant::now();
+        let later = now + Duration::from_secs(30);
+        assert!(can_acquire(&lease(Some("b")), "a", now, later));
+    }
+
+    #[test]
+    fn test_observe_lease_uses_local_clock() {
+        let mut observed = ObservedLease::default();
+        let now = Instant::now();
+        let spec = lease(Some("b"));
+
+        // --- The renew time of the holder is ignored, only changes of the record matter.
+        let spec_skewed = LeaseSpec {
+            renew_time: Some(MicroTime(Utc::now() - chrono::Duration::hours(1))),
+            ..spec.clone()
+        };
+        assert_eq!(observed.observe(&spec_skewed, now), now);
+        assert!(!can_acquire(&spec_skewed, "a", now, now));
+
+        // --- An unchanged record keeps the time it was first observed at.
+        let later = now + Duration::from_secs(30);
+        assert_eq!(observed.observe(&spec_skewed, later), now);
+        assert!(can_acquire(&spec_skewed, "a", now, later));
+
+        // --- A renewed record resets the time it was observed at.
+        assert_eq!(observed.observe(&spec, later), later);
+        assert!(!can_acquire(&spec, "a", later, later));
+    }
+}
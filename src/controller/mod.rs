@@ -1,9 +1,18 @@
+mod health;
+mod leader_election;
 mod operator;
+mod pool_operator;
 
-use crate::{get_kube_client, Kubeconfig, Result};
+pub use health::*;
+pub use leader_election::*;
+
+use crate::{get_kube_config, Kubeconfig, Result};
 use clap::Parser;
-use kube::Client;
+use k8s_openapi::NamespaceResourceScope;
+use kube::{Api, Client, Resource};
 use std::fmt::Debug;
+use std::net::IpAddr;
+use std::time::Duration;
 
 /// The name of the Kubernetes operator manager. Used to identify the operator in the Kubernetes API.
 pub const NMCP_OPERATOR: &str = "mcpserver.nmcp.nwrx.io/operator";
@@ -19,21 +28,75 @@ pub struct ControllerOptions {
     #[arg(short, long, default_value = "default", env = "KUBECTL_NAMESPACE")]
     pub namespace: String,
 
+    /// Operate in all the namespaces of the cluster instead of a single one.
+    #[arg(long, conflicts_with = "watch_namespaces")]
+    pub all_namespaces: bool,
+
+    /// Comma-separated list of namespaces to operate in instead of a single one.
+    #[arg(long, value_delimiter = ',')]
+    pub watch_namespaces: Vec<String>,
+
     /// Path to Kubernetes config file.
     #[arg(short, long, env = "KUBECONFIG")]
     pub kubeconfig: Kubeconfig,
+
+    /// Interval between periodic reconciliations (in seconds). Changes to the owned Pods
+    /// and Services already trigger a reconciliation, so this is only a fallback used to
+    /// catch idle servers and missed events.
+    #[arg(long, default_value = "60")]
+    pub requeue_interval: u64,
+}
+
+/// Configuration for the operator process
+#[derive(Debug, Clone, Parser)]
+pub struct OperatorOptions {
+    /// Host address for the health server to bind to
+    #[arg(long, default_value = "0.0.0.0")]
+    pub health_host: IpAddr,
+
+    /// Port for the health server to listen on
+    #[arg(long, default_value = "8081")]
+    pub health_port: u16,
+
+    /// Port for the Prometheus metrics endpoint to listen on, bound to the health host
+    #[arg(long, default_value = "9090")]
+    pub metrics_port: u16,
+
+    /// Elect a leader among the replicas of the operator, so that only one of them
+    /// reconciles the resources at a time while the others stand by.
+    #[arg(long)]
+    pub leader_election: bool,
+
+    /// Name of the `Lease` used for the leader election, in the namespace of the operator.
+    #[arg(long, default_value = "nmcp-operator")]
+    pub lease_name: String,
+
+    /// Duration of the lease (in seconds). Standing-by replicas wait for this long after
+    /// the last renewal before taking over the lease of an unresponsive leader.
+    #[arg(long, default_value = "15")]
+    pub lease_duration: u64,
+
+    /// Duration (in seconds) the leader keeps retrying to renew its lease before stepping
+    /// down. Must be lower than the lease duration to avoid two concurrent leaders.
+    #[arg(long, default_value = "10")]
+    pub renew_deadline: u64,
 }
 
 #[derive(Clone)]
 pub struct Controller {
     client: Client,
     namespace: String,
+    /// The namespaces to operate in, or `None` to operate in the whole cluster.
+    watch_namespaces: Option<Vec<String>>,
+    requeue_interval: Duration,
 }
 
 impl Debug for Controller {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Controller")
             .field("namespace", &self.namespace)
+            .field("watch_namespaces", &self.watch_namespaces)
+            .field("requeue_interval", &self.requeue_interval)
             .field("client", &"Client(...)")
             .finish()
     }
@@ -41,9 +104,24 @@ impl Debug for Controller {
 
 impl Controller {
     pub async fn new(options: &ControllerOptions) -> Result<Self> {
+        let watch_namespaces = if options.all_namespaces {
+            None
+        } else if options.watch_namespaces.is_empty() {
+            Some(vec![options.namespace.clone()])
+        } else {
+            Some(options.watch_namespaces.clone())
+        };
+
+        // --- Use the namespace of the options as the default namespace of the client, so
+        // --- that lookups without an explicit namespace target the expected namespace.
+        let mut config = get_kube_config(options.kubeconfig.clone()).await?;
+        config.default_namespace = options.namespace.clone();
+
         Ok(Self {
             namespace: options.namespace.clone(),
-            client: get_kube_client(options.kubeconfig.clone()).await?,
+            watch_namespaces,
+            requeue_interval: Duration::from_secs(options.requeue_interval.max(1)),
+            client: Client::try_from(config)?,
         })
     }
 
@@ -54,4 +132,44 @@ impl Controller {
     pub fn get_namespace(&self) -> String {
         self.namespace.clone()
     }
+
+    pub fn get_requeue_interval(&self) -> Duration {
+        self.requeue_interval
+    }
+
+    /// Check if the controller operates in more than its default namespace, in which case
+    /// resources must be addressed by both their namespace and their name.
+    pub fn is_multi_namespace(&self) -> bool {
+        self.watch_namespaces
+            .as_ref()
+            .is_none_or(|namespaces| namespaces != std::slice::from_ref(&self.namespace))
+    }
+
+    /// Check if the given namespace is one the controller operates in.
+    pub fn is_namespace_watched(&self, namespace: &str) -> bool {
+        self.watch_namespaces
+            .as_ref()
+            .is_none_or(|namespaces| namespaces.iter().any(|watched| watched == namespace))
+    }
+
+    /// Returns the scopes the operators must watch resources in. A scope of `None` stands
+    /// for the whole cluster, otherwise each watched namespace gets its own scope.
+    pub fn get_scopes(&self) -> Vec<Option<String>> {
+        match &self.watch_namespaces {
+            None => vec![None],
+            Some(namespaces) => namespaces.iter().cloned().map(Some).collect(),
+        }
+    }
+
+    /// Returns the `Api` of the given resource type within a scope returned by `get_scopes`.
+    pub fn get_api<K>(&self, scope: Option<&str>) -> Api<K>
+    where
+        K: Resource<Scope = NamespaceResourceScope>,
+        <K as Resource>::DynamicType: Default,
+    {
+        match scope {
+            Some(namespace) => Api::namespaced(self.get_client(), namespace),
+            None => Api::all(self.get_client()),
+        }
+    }
 }
@@ -1,14 +1,21 @@
-use super::{Controller, NMCP_FINALIZER};
-use crate::{Error, MCPServer, Result};
+use super::{
+    serve_health, shutdown_signal, Controller, LeaderElection, OperatorOptions, NMCP_FINALIZER,
+};
+use crate::{
+    serve_metrics, Error, MCPPool, MCPServer, MCPServerPhase, ResourceManager, Result, METRICS,
+};
 use futures::StreamExt;
 use k8s_openapi::api::core::v1;
 use kube::runtime::controller::Action;
 use kube::runtime::finalizer;
 use kube::runtime::finalizer::Event;
+use kube::runtime::reflector::ObjectRef;
 use kube::runtime::{watcher::Config, Controller as RuntimeController};
-use kube::{Api, ResourceExt};
+use kube::ResourceExt;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::watch;
 
 #[derive(Debug)]
 struct ReconcileReportError(Error);
@@ -34,12 +41,17 @@ impl Controller {
         &self,
         server: Arc<MCPServer>,
     ) -> core::result::Result<Action, finalizer::Error<ReconcileReportError>> {
-        let api = Api::<MCPServer>::namespaced(self.get_client(), &self.get_namespace());
+        let api = server.api(&self.get_client());
+        let timer = METRICS
+            .reconcile_duration
+            .with_label_values(&["MCPServer"])
+            .start_timer();
 
         // --- Handle the reconciliation process using finalizers to ensure
         // --- that the cleanup process is completed before the resource is deleted.
-        finalizer(&api, NMCP_FINALIZER, server, {
+        let result = finalizer(&api, NMCP_FINALIZER, server, {
             let client = self.get_client();
+            let requeue_interval = self.get_requeue_interval();
             move |event| async move {
                 match event {
                     Event::Cleanup(server) => {
@@ -47,7 +59,10 @@ impl Controller {
                             .ensure_pod_is_terminated(&client)
                             .await
                             .map_err(ReconcileReportError)?;
-                        // server.ensure_service_is_destroted(&client).await.map_err(ReconcileReportError)?;
+                        server
+                            .ensure_service_is_destroyed(&client)
+                            .await
+                            .map_err(ReconcileReportError)?;
                         Ok(Action::requeue(Duration::from_secs(5)))
                     }
                     Event::Apply(server) => async {
@@ -55,7 +70,7 @@ impl Controller {
                             .reconcile_server(&client)
                             .await
                             .map_err(ReconcileReportError)?;
-                        Result::Ok(Action::requeue(Duration::from_secs(5)))
+                        Result::Ok(Action::requeue(requeue_interval))
                     }
                     // The `kube::runtime::finalizer` expects it's reconcile closure to return an error that
                     // implements `std::error::Error`, however, since we are using `error_stack::Report` and
@@ -66,7 +81,17 @@ impl Controller {
                 }
             }
         })
-        .await
+        .await;
+
+        // --- Record the duration and outcome of the reconciliation.
+        timer.observe_duration();
+        if result.is_err() {
+            METRICS
+                .reconcile_errors
+                .with_label_values(&["MCPServer"])
+                .inc();
+        }
+        result
     }
 
     /// Handle an error during the reconciliation process.
@@ -89,22 +114,54 @@ impl Controller {
         }
     }
 
-    /// Start the operator for managing MCPServer resources.
+    /// Start the operator for managing `MCPServer` resources in every watched scope.
     #[tracing::instrument(name = "Operator", skip_all, err)]
     pub async fn start_server_operator(&self) -> Result<()> {
-        let ns = self.get_namespace();
+        let scopes = self.get_scopes();
+        let operators = scopes
+            .iter()
+            .map(|scope| self.start_server_operator_in(scope.as_deref()));
+        let _ = futures::future::try_join_all(operators).await?;
+        Ok(())
+    }
+
+    /// Start the operator for managing `MCPServer` resources within a single scope.
+    async fn start_server_operator_in(&self, scope: Option<&str>) -> Result<()> {
         let wc = Config::default();
 
         // --- Create API clients for MCPServer, Pod, and Service.
-        let api = Api::<MCPServer>::namespaced(self.get_client(), &ns);
-        let api_pod = Api::<v1::Pod>::namespaced(self.get_client(), &ns);
-        let api_services = Api::<v1::Service>::namespaced(self.get_client(), &ns);
+        let api = self.get_api::<MCPServer>(scope);
+        let api_pod = self.get_api::<v1::Pod>(scope);
+        let api_services = self.get_api::<v1::Service>(scope);
+        let api_pools = self.get_api::<MCPPool>(scope);
 
         // --- Start the controller for MCPServer resources.
-        tracing::info!("Starting MCPServer operator in namespace '{}'", ns);
-        let stream = RuntimeController::new(api, wc.clone())
+        match scope {
+            Some(ns) => tracing::info!("Starting MCPServer operator in namespace '{}'", ns),
+            None => tracing::info!("Starting MCPServer operator in all namespaces"),
+        }
+        let controller = RuntimeController::new(api, wc.clone());
+        let store = controller.store();
+        let stream = controller
             .owns(api_pod, Default::default())
             .owns(api_services, Default::default())
+            // --- Requeue the servers waiting for capacity whenever the status of their
+            // --- pool changes, so they are started as soon as a slot frees up.
+            .watches(api_pools, wc.clone(), move |pool| {
+                store
+                    .state()
+                    .into_iter()
+                    .filter(|server| server.spec.pool == pool.name_any())
+                    .filter(|server| server.namespace() == pool.namespace())
+                    .filter(|server| {
+                        server
+                            .status
+                            .as_ref()
+                            .is_some_and(|status| status.phase == MCPServerPhase::Requested)
+                    })
+                    .map(|server| ObjectRef::from_obj(&*server))
+                    .collect::<Vec<_>>()
+            })
             .run(
                 |server, controller| async move { controller.reconcile(server).await },
                 |server, error, controller| controller.error_policy(&server, error).unwrap(),
@@ -116,4 +173,55 @@ impl Controller {
 
         Ok(())
     }
+
+    /// Run the operators for managing `MCPServer` and `MCPPool` resources side by side, for as
+    /// long as this replica is the leader. The operators are stopped as soon as the leadership
+    /// is lost, and started again once it's regained.
+    async fn run_while_leader(&self, mut is_leader: watch::Receiver<bool>) -> Result<()> {
+        loop {
+            let _ = is_leader
+                .wait_for(|is_leader| *is_leader)
+                .await
+                .map_err(|_| Error::generic("Leader election stopped unexpectedly"))?;
+
+            tracing::info!("Starting the operators as the leader");
+            tokio::select! {
+                result = async {
+                    tokio::try_join!(self.start_server_operator(), self.start_pool_operator())
+                } => {
+                    let _ = result?;
+                    return Ok(());
+                }
+                _ = is_leader.wait_for(|is_leader| !*is_leader) => {
+                    tracing::warn!("Stopping the operators after losing the leadership");
+                }
+            }
+        }
+    }
+
+    /// Start the operators for managing `MCPServer` and `MCPPool` resources. When leader election
+    /// is enabled, the operators only run on the replica holding the lease, which is released
+    /// on shutdown so that another replica can take over right away.
+    pub async fn start_operator(&self, options: &OperatorOptions) -> Result<()> {
+        let election = LeaderElection::new(self, options)?;
+        let campaign = async {
+            if options.leader_election {
+                election.run().await
+            } else {
+                election.set_leader(true);
+                std::future::pending().await
+            }
+        };
+
+        tokio::select! {
+            result = self.run_while_leader(election.subscribe()) => result,
+            result = campaign => result,
+            result = serve_health(options, election.clone()) => result,
+            result = serve_metrics(SocketAddr::new(options.health_host, options.metrics_port)) => result,
+            () = shutdown_signal() => {
+                tracing::info!("Received shutdown signal, stepping down");
+                election.step_down().await
+            }
+        }
+    }
 }
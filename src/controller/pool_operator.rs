@@ -0,0 +1,96 @@
+use super::Controller;
+use crate::{Error, MCPPool, MCPServer, Result, MCP_SESSION_LABEL, METRICS};
+use futures::StreamExt;
+use k8s_openapi::api::core::v1;
+use kube::runtime::controller::Action;
+use kube::runtime::reflector::ObjectRef;
+use kube::runtime::{watcher::Config, Controller as RuntimeController};
+use kube::ResourceExt;
+use std::sync::Arc;
+use std::time::Duration;
+
+impl Controller {
+    /// Reconcile the `MCPPool` resource by recomputing its status from the servers referencing it.
+    #[tracing::instrument(name = "ReconcilePool", skip_all, fields(pool = %pool.name_any()))]
+    async fn reconcile_pool(&self, pool: Arc<MCPPool>) -> Result<Action> {
+        let timer = METRICS
+            .reconcile_duration
+            .with_label_values(&["MCPPool"])
+            .start_timer();
+        let result = pool.reconcile_pool(&self.get_client()).await;
+
+        // --- Record the duration and outcome of the reconciliation.
+        timer.observe_duration();
+        if result.is_err() {
+            METRICS
+                .reconcile_errors
+                .with_label_values(&["MCPPool"])
+                .inc();
+        }
+        result?;
+        Ok(Action::requeue(self.get_requeue_interval()))
+    }
+
+    /// Handle an error during the reconciliation process of an `MCPPool`.
+    #[tracing::instrument(name = "ErrorPolicyPool", skip_all)]
+    fn error_policy_pool(&self, _pool: &MCPPool, error: &Error) -> Action {
+        let _ = error.clone().trace();
+        Action::requeue(Duration::from_secs(5))
+    }
+
+    /// Start the operator for managing `MCPPool` resources in every watched scope.
+    #[tracing::instrument(name = "PoolOperator", skip_all, err)]
+    pub async fn start_pool_operator(&self) -> Result<()> {
+        let scopes = self.get_scopes();
+        let operators = scopes
+            .iter()
+            .map(|scope| self.start_pool_operator_in(scope.as_deref()));
+        let _ = futures::future::try_join_all(operators).await?;
+        Ok(())
+    }
+
+    /// Start the operator for managing `MCPPool` resources within a single scope.
+    async fn start_pool_operator_in(&self, scope: Option<&str>) -> Result<()> {
+        let wc = Config::default();
+
+        // --- Create API clients for MCPPool, MCPServer and the pods of isolated sessions.
+        let api = self.get_api::<MCPPool>(scope);
+        let api_servers = self.get_api::<MCPServer>(scope);
+        let api_pods = self.get_api::<v1::Pod>(scope);
+        let wc_pods = Config::default().labels(MCP_SESSION_LABEL);
+
+        // --- Start the controller for MCPPool resources. Every change to an MCPServer, or to
+        // --- the pod of a session, triggers the reconciliation of the pool it belongs to, so
+        // --- the sessions are counted as soon as they open or close.
+        match scope {
+            Some(ns) => tracing::info!("Starting MCPPool operator in namespace '{}'", ns),
+            None => tracing::info!("Starting MCPPool operator in all namespaces"),
+        }
+        let stream = RuntimeController::new(api, wc.clone())
+            .watches(api_servers, wc, |server| {
+                let pool = ObjectRef::<MCPPool>::new(&server.spec.pool);
+                Some(match server.namespace() {
+                    Some(namespace) => pool.within(&namespace),
+                    None => pool,
+                })
+            })
+            .watches(api_pods, wc_pods, |pod| {
+                let pool = pod.labels().get("nmcp.nwrx.io/pool")?;
+                let pool = ObjectRef::<MCPPool>::new(pool);
+                Some(match pod.namespace() {
+                    Some(namespace) => pool.within(&namespace),
+                    None => pool,
+                })
+            })
+            .run(
+                |pool, controller| async move { controller.reconcile_pool(pool).await },
+                |pool, error, controller| controller.error_policy_pool(&pool, error),
+                Arc::new(self.clone()),
+            );
+
+        // --- Loop to handle the reconciliation stream.
+        stream.for_each(|_| futures::future::ready(())).await;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{Controller, Error, MCPPool, MCPServer, Result};
+use axum::http::StatusCode;
+use futures::StreamExt;
+use kube::core::NamespaceResourceScope;
+use kube::runtime::reflector::{self, ObjectRef, Store};
+use kube::runtime::{watcher, WatchStreamExt};
+use kube::Resource;
+use serde::de::DeserializeOwned;
+use std::fmt::{Debug, Formatter};
+use std::hash::Hash;
+
+/// A local copy of the resources served by the gateway, kept up to date by watching them in
+/// every scope of the controller. Reading the resources from the cache spares a round trip to
+/// the API server on every request.
+#[derive(Clone)]
+pub struct ResourceCache<K>
+where
+    K: Resource + Clone + 'static,
+    K::DynamicType: Hash + Eq + Clone,
+{
+    stores: Vec<Store<K>>,
+}
+
+impl<K> Debug for ResourceCache<K>
+where
+    K: Resource + Clone + 'static,
+    K::DynamicType: Hash + Eq + Clone,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResourceCache")
+            .field("stores", &self.stores.len())
+            .finish()
+    }
+}
+
+/// The `MCPServer` resources served by the gateway.
+pub type ServerCache = ResourceCache<MCPServer>;
+
+/// The `MCPPool` resources the servers of the gateway belong to.
+pub type PoolCache = ResourceCache<MCPPool>;
+
+impl<K> ResourceCache<K>
+where
+    K: Resource<Scope = NamespaceResourceScope> + Clone + Debug + DeserializeOwned + Send + Sync,
+    K: 'static,
+    K::DynamicType: Default + Hash + Eq + Clone,
+{
+    /// Create the cache and spawn the reflectors populating it, one for each scope.
+    pub fn new(controller: &Controller) -> Self {
+        let stores = controller
+            .get_scopes()
+            .into_iter()
+            .map(|scope| {
+                let (store, writer) = reflector::store();
+                let api = controller.get_api::<K>(scope.as_deref());
+                let stream = reflector::reflector(writer, watcher(api, watcher::Config::default()))
+                    .default_backoff()
+                    .for_each(|event| async {
+                        if let Err(error) = event {
+                            let _ = Error::from(error).trace();
+                        }
+                    });
+                drop(tokio::spawn(stream));
+                store
+            })
+            .collect();
+        Self { stores }
+    }
+
+    /// Get a resource from the cache. Returns `None` if the resource is unknown to the cache,
+    /// which is also the case while the cache is still being populated.
+    pub fn get(&self, namespace: &str, name: &str) -> Option<K> {
+        let key = ObjectRef::new(name).within(namespace);
+        self.stores
+            .iter()
+            .find_map(|store| store.get(&key))
+            .map(|resource| (*resource).clone())
+    }
+
+    /// Wait until the initial listing of every scope has been received, so that a resource
+    /// missing from the cache can be trusted to not exist.
+    pub async fn wait_until_ready(&self) -> Result<()> {
+        for store in &self.stores {
+            if store.wait_until_ready().await.is_err() {
+                return Err(Error::generic("The resource cache is no longer updated")
+                    .with_name("E_CACHE_UNAVAILABLE")
+                    .with_status(StatusCode::SERVICE_UNAVAILABLE));
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,18 +1,35 @@
-use crate::{Controller, Error, MCPServer, Result, Transport};
+use super::{
+    IsolatedSession, PoolCache, ServerCache, ServerCounters, SessionExpiry, SessionOutbox,
+    SessionStore, SessionStreamGuard, SseSession, SseSessionStore, StreamableSession,
+    StreamableSessionStore,
+};
+use crate::{
+    authenticate, propagate_trace_context, render_metrics, Authenticator, Controller, Error,
+    MCPAccessOperation, MCPServer, MCPServerCondition as Condition, MCPServerIsolation,
+    MCPServerPhase, MCPServerRequestedState as RequestState, Principal, ResourceManager, Result,
+    Transport, TransportPeer, DEFAULT_SESSION_HEARTBEAT_INTERVAL, METRICS,
+};
 use aide::axum::routing::get;
 use aide::axum::ApiRouter;
 use aide::openapi::OpenApi;
 use aide::scalar::Scalar;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::Response;
 use axum::Extension;
 use clap::Parser;
+use futures::StreamExt;
 use kube::{Client, ResourceExt};
 use moka::sync::Cache;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::fmt::{Debug, Formatter};
 use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpListener;
 use tower_http::trace::TraceLayer;
+use uuid::Uuid;
 
 /// Configuration for the API server
 #[derive(Debug, Copy, Clone, Parser)]
@@ -47,15 +64,81 @@ pub struct GatewayOptions {
     /// but low enough to avoid excessive memory usage.
     #[arg(long, default_value = "1024")]
     pub max_cache_capacity: u64,
+
+    /// Maximum time a session opened through the Streamable HTTP
+    /// endpoint can be idle before it is closed (10 minutes by
+    /// default). Sessions are never closed while a stream of the
+    /// session is open.
+    #[arg(long, default_value = "600")]
+    pub session_idle_timeout: u64,
+
+    /// Interval for flushing the request and connection counters
+    /// to the status of the servers (in seconds)
+    #[arg(long, default_value = "1")]
+    pub flush_interval: u64,
+
+    /// Maximum size of a single message read from the stdout of
+    /// a server process (4 MiB by default). Larger messages are
+    /// discarded without closing the transport.
+    #[arg(long, default_value = "4194304")]
+    pub max_message_size: usize,
 }
 
 pub type TransportStore = Cache<String, Result<Transport>>;
 
+/// The path parameters identifying an `MCPServer` in the routes of the gateway. The namespace
+/// is only part of the routes when the gateway operates in more than one namespace.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
+pub struct ServerPath {
+    /// The namespace of the server, defaults to the namespace of the gateway.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    /// The name of the server.
+    pub name: String,
+}
+
+impl ServerPath {
+    /// Returns the base path of the routes of the server.
+    pub fn base_path(&self) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("/{namespace}/{}", self.name),
+            None => format!("/{}", self.name),
+        }
+    }
+}
+
+/// Handler for the `/metrics` endpoint of the gateway.
+async fn metrics(State(ctx): State<GatewayContext>) -> Response {
+    let size = ctx.transports.entry_count();
+    METRICS
+        .transport_cache_size
+        .set(i64::try_from(size).unwrap_or(i64::MAX));
+    render_metrics().await
+}
+
+/// The error returned for sessions that do not exist, or that the request may not access.
+fn session_not_found(session_id: &str) -> Error {
+    Error::generic(format!("Session with ID {session_id} not found"))
+        .with_name("E_SESSION_NOT_FOUND")
+        .with_status(StatusCode::NOT_FOUND)
+}
+
 /// Server struct for the API server
 pub struct Gateway {
     address: SocketAddr,
     controller: Controller,
     transports: TransportStore,
+    sessions: SessionStore,
+    streamable_sessions: StreamableSessionStore,
+    sse_sessions: SseSessionStore,
+    session_idle_timeout: Duration,
+    servers: ServerCache,
+    pools: PoolCache,
+    counters: Arc<ServerCounters>,
+    flush_interval: Duration,
+    cleanup_interval: Duration,
+    max_message_size: usize,
+    authenticator: Authenticator,
 }
 
 impl Debug for Gateway {
@@ -64,6 +147,16 @@ impl Debug for Gateway {
             .field("address", &self.address)
             .field("controller", &"Controller(...)")
             .field("transports", &self.transports.entry_count())
+            .field("sessions", &self.sessions.entry_count())
+            .field(
+                "streamable_sessions",
+                &self.streamable_sessions.entry_count(),
+            )
+            .field("sse_sessions", &self.sse_sessions.entry_count())
+            .field("servers", &self.servers)
+            .field("pools", &self.pools)
+            .field("counters", &self.counters)
+            .field("authenticator", &self.authenticator)
             .finish()
     }
 }
@@ -72,15 +165,60 @@ pub type GatewayContext = Arc<Gateway>;
 
 impl Gateway {
     /// Create a new server instance
-    pub async fn new(options: GatewayOptions, controller: Controller) -> Result<Self> {
+    pub async fn new(
+        options: GatewayOptions,
+        controller: Controller,
+        authenticator: Authenticator,
+    ) -> Result<Self> {
+        // --- Isolated sessions are torn down once closed or idle for the `idleTimeout` of
+        // --- their server, closing their transport and deleting their pod in the background.
+        // --- They are neither bounded in number nor in age, since their pods already are.
+        let client = controller.get_client();
+        let handle = tokio::runtime::Handle::current();
+        let sessions: SessionStore = Cache::builder()
+            .expire_after(SessionExpiry)
+            .eviction_listener(move |_, session: IsolatedSession, _| {
+                let client = client.clone();
+                drop(handle.spawn(async move { session.close(&client).await }));
+            })
+            .build();
+
+        // --- Streamable HTTP sessions are only known by their ID, so they are closed once idle
+        // --- or terminated by their client, removing their peer from its transport along with
+        // --- the isolated session backing it, if any.
+        let isolated = sessions.clone();
+        let handle = tokio::runtime::Handle::current();
+        let streamable_sessions = Cache::builder()
+            .expire_after(SessionExpiry)
+            .eviction_listener(move |id: Arc<String>, session: StreamableSession, _| {
+                isolated.invalidate(id.as_str());
+                drop(handle.spawn(async move {
+                    if let Err(error) = session.transport.remove_peer(id.to_string()).await {
+                        let _ = error.trace();
+                    }
+                }));
+            })
+            .build();
+
         Ok(Self {
             address: SocketAddr::new(options.host, options.port),
+            servers: ServerCache::new(&controller),
+            pools: PoolCache::new(&controller),
             controller,
             transports: Cache::builder()
                 .max_capacity(options.max_cache_capacity)
                 .time_to_live(Duration::from_secs(options.max_age))
                 .time_to_idle(Duration::from_secs(options.max_idle_age))
                 .build(),
+            sessions,
+            streamable_sessions,
+            sse_sessions: Cache::builder().build(),
+            session_idle_timeout: Duration::from_secs(options.session_idle_timeout),
+            counters: Arc::default(),
+            flush_interval: Duration::from_secs(options.flush_interval.max(1)),
+            cleanup_interval: Duration::from_secs(options.cleanup_interval.max(1)),
+            max_message_size: options.max_message_size,
+            authenticator,
         })
     }
 
@@ -89,6 +227,82 @@ impl Gateway {
         self.controller.get_client()
     }
 
+    /// Get the request and connection counters of the servers, flushed periodically.
+    pub fn counters(&self) -> &Arc<ServerCounters> {
+        &self.counters
+    }
+
+    /// Get the `MCPServer` targeted by the path of a request, from the cache if possible. Servers
+    /// living outside of the namespaces the gateway operates in are reported as not found.
+    pub async fn get_server(&self, path: &ServerPath) -> Result<MCPServer> {
+        let client = self.get_client().await;
+        let namespace = path
+            .namespace
+            .clone()
+            .unwrap_or_else(|| self.controller.get_namespace());
+        if !self.controller.is_namespace_watched(&namespace) {
+            return Err(Error::generic(format!(
+                "Namespace '{namespace}' is not served by the gateway"
+            ))
+            .with_name("E_NAMESPACE_NOT_WATCHED")
+            .with_status(StatusCode::NOT_FOUND));
+        }
+        match self.servers.get(&namespace, &path.name) {
+            Some(server) => Ok(server),
+            None => MCPServer::get_by_name_in(&client, &namespace, &path.name).await,
+        }
+    }
+
+    /// Ensure the principal of a request is granted the operation on the server, or return a
+    /// `403` error otherwise. The pool of the server is read from the cache, which is awaited
+    /// until populated so that a missing pool can be trusted to define no rules.
+    pub async fn authorize(
+        &self,
+        server: &MCPServer,
+        principal: &Principal,
+        operation: MCPAccessOperation,
+    ) -> Result<()> {
+        if !server.spec.access.is_empty() {
+            return server.authorize(None, principal, operation);
+        }
+        let client = self.get_client().await;
+        let namespace = server.namespace_or_default(&client);
+        self.pools.wait_until_ready().await?;
+        let pool = self.pools.get(&namespace, &server.spec.pool);
+        server.authorize(pool.as_ref(), principal, operation)
+    }
+
+    /// Register a request to the server, and make sure it is ready to handle it. Servers that are
+    /// already `Ready` are only counted, so forwarding messages to a running server does not
+    /// involve the API server. Otherwise, the server is requested and awaited until it is ready.
+    pub async fn request_server(
+        &self,
+        server: &MCPServer,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        self.counters.notify_request(server);
+
+        // --- Servers isolating their sessions are never started as a whole, the pod of each
+        // --- session is created when the session is opened.
+        if server.spec.isolation == MCPServerIsolation::Session {
+            return Ok(());
+        }
+        if server
+            .status
+            .as_ref()
+            .is_some_and(|status| status.phase == MCPServerPhase::Ready)
+        {
+            return Ok(());
+        }
+
+        // --- Update the status so it can be picked-up by the operator.
+        let client = self.get_client().await;
+        let condition = Condition::Requested(RequestState::Connection);
+        server.request(&client).await?;
+        server.push_condition(&client, condition).await?;
+        server.wait_until_ready(&client, timeout).await
+    }
+
     /// Get or create the `Transport` instance for a given server. If the transport does not exist,
     /// it will be created within a task and stored in the `transports` map. Since `Transport` instanciation
     /// may take some time, this method returns a `Result<Transport>` to ensure no concurrent access issues arise.
@@ -96,18 +310,239 @@ impl Gateway {
     pub fn get_transport(&self, server: &MCPServer) -> Result<Transport> {
         let client = self.controller.get_client();
         let server = server.clone();
-        let key = format!("{}-{}", client.default_namespace(), server.name_any());
+        let namespace = server.namespace_or_default(&client);
+        let key = format!("{}-{}", namespace, server.name_any());
 
         // --- Get or create a transport for the server. Uses `moka::sync::Cache` to handle concurrent
         // --- requests safely - if multiple requests across multiple threads try to get the same transport,
         // --- only one will create it, while others will wait for the result. This ensures that we do not
         // --- create multiple transports for the same server.
-        self.transports
-            .clone()
-            .get_with(key, || Transport::new(&client, &server))
+        self.transports.clone().get_with(key, || {
+            Transport::new(&client, &server, self.max_message_size)
+        })
+    }
+
+    /// Open a new session with the server and return its peer. Sessions share the transport of
+    /// the server, unless the server isolates them, in which case an ephemeral pod is created
+    /// for the session and awaited until ready, with a dedicated transport connected to it.
+    pub async fn open_session(
+        &self,
+        server: &MCPServer,
+        timeout: Option<Duration>,
+    ) -> Result<TransportPeer> {
+        if server.spec.isolation == MCPServerIsolation::Shared {
+            let mut transport = self.get_transport(server)?;
+            return transport.subscribe().await;
+        }
+
+        // --- Create the pod of the session and connect a dedicated transport to it.
+        let client = self.get_client().await;
+        let pod_id = Uuid::new_v4().to_string();
+        let pod = server.create_session_pod(&client, &pod_id, timeout).await?;
+        let mut transport = Transport::for_session(&client, server, &pod, self.max_message_size)?;
+        let session = IsolatedSession {
+            server: server.clone(),
+            pod_id,
+            transport: transport.clone(),
+            streams: Arc::default(),
+        };
+
+        // --- Sessions are indexed by the ID of their peer, which is the ID known to clients.
+        match transport.subscribe().await {
+            Ok(peer) => {
+                self.sessions.insert(peer.id.clone(), session);
+                Ok(peer)
+            }
+            Err(error) => {
+                session.close(&client).await;
+                Err(error)
+            }
+        }
+    }
+
+    /// Get the peer of an open session with the server. Isolated sessions opened with another
+    /// server are reported as not found.
+    pub async fn get_peer(&self, server: &MCPServer, session_id: String) -> Result<TransportPeer> {
+        if server.spec.isolation == MCPServerIsolation::Shared {
+            return self.get_transport(server)?.get_peer(session_id).await;
+        }
+        match self.sessions.get(&session_id) {
+            Some(session) if session.is_opened_with(server) => {
+                session.transport.get_peer(session_id).await
+            }
+            _ => Err(session_not_found(&session_id)),
+        }
+    }
+
+    /// Get the ID of the ephemeral pod of an isolated session with the server. Sessions opened
+    /// with another server are reported as not found.
+    pub fn get_session_pod_id(&self, server: &MCPServer, session_id: &str) -> Result<String> {
+        match self.sessions.get(session_id) {
+            Some(session) if session.is_opened_with(server) => Ok(session.pod_id),
+            _ => Err(session_not_found(session_id)),
+        }
+    }
+
+    /// Open a new session with the server through the SSE endpoint, on behalf of the principal.
+    /// The session lives until it is closed with `close_sse_session` once its stream ends.
+    pub async fn open_sse_session(
+        &self,
+        server: &MCPServer,
+        principal: &Principal,
+        timeout: Option<Duration>,
+    ) -> Result<TransportPeer> {
+        let peer = self.open_session(server, timeout).await?;
+        let session = SseSession {
+            server: server.clone(),
+            principal: principal.clone(),
+        };
+        self.sse_sessions.insert(peer.id.clone(), session);
+        Ok(peer)
+    }
+
+    /// Get the peer of a session opened through the SSE endpoint. Sessions opened with another
+    /// server or by another principal are reported as not found.
+    pub async fn get_sse_peer(
+        &self,
+        server: &MCPServer,
+        principal: &Principal,
+        session_id: String,
+    ) -> Result<TransportPeer> {
+        match self.sse_sessions.get(&session_id) {
+            Some(session) if session.is_owned_by(server, principal) => {
+                self.get_peer(server, session_id).await
+            }
+            _ => Err(session_not_found(&session_id)),
+        }
+    }
+
+    /// Close a session opened through the SSE endpoint, once its stream ends.
+    pub async fn close_sse_session(&self, server: &MCPServer, session_id: String) -> Result<()> {
+        self.sse_sessions.invalidate(&session_id);
+        self.close_session(server, session_id).await
+    }
+
+    /// Open a new session with the server through the Streamable HTTP endpoint, on behalf of
+    /// the principal. The session is closed once idle for the `session_idle_timeout` of the
+    /// gateway, or once terminated by its client.
+    pub async fn open_streamable_session(
+        &self,
+        server: &MCPServer,
+        principal: &Principal,
+        timeout: Option<Duration>,
+    ) -> Result<TransportPeer> {
+        let peer = self.open_session(server, timeout).await?;
+        let transport = match server.spec.isolation {
+            MCPServerIsolation::Shared => self.get_transport(server),
+            MCPServerIsolation::Session => match self.sessions.get(&peer.id) {
+                Some(session) => Ok(session.transport),
+                None => Err(Error::generic("Isolated session closed while opening it")),
+            },
+        };
+        let transport = match transport {
+            Ok(transport) => transport,
+            Err(error) => {
+                self.close_session(server, peer.id).await?;
+                return Err(error);
+            }
+        };
+
+        // --- Deliver the messages initiated by the server to the streams of the session until
+        // --- the peer is closed, which then ends the streams.
+        let outbox = Arc::new(SessionOutbox::default());
+        let messages = peer.messages().await;
+        let _task = tokio::spawn({
+            let outbox = outbox.clone();
+            async move {
+                let mut messages = Box::pin(messages);
+                while let Some(message) = messages.next().await {
+                    outbox.dispatch(message);
+                }
+                outbox.close();
+            }
+        });
+        let session = StreamableSession {
+            server: server.clone(),
+            principal: principal.clone(),
+            transport,
+            max_idle_time: self.session_idle_timeout,
+            streams: Arc::default(),
+            connection: Arc::new(self.counters.connect(server)),
+            outbox,
+        };
+        self.streamable_sessions.insert(peer.id.clone(), session);
+        Ok(peer)
+    }
+
+    /// Get the peer of a session opened through the Streamable HTTP endpoint. Sessions opened
+    /// with another server or by another principal are reported as not found.
+    pub async fn get_streamable_peer(
+        &self,
+        server: &MCPServer,
+        principal: &Principal,
+        session_id: String,
+    ) -> Result<TransportPeer> {
+        match self.streamable_sessions.get(&session_id) {
+            Some(session) if session.is_owned_by(server, principal) => {
+                session.transport.get_peer(session_id).await
+            }
+            _ => Err(session_not_found(&session_id)),
+        }
+    }
+
+    /// Get the outbox delivering the messages initiated by the server to the streams of a
+    /// session opened through the Streamable HTTP endpoint.
+    pub fn get_streamable_outbox(&self, session_id: &str) -> Result<Arc<SessionOutbox>> {
+        match self.streamable_sessions.get(session_id) {
+            Some(session) => Ok(session.outbox),
+            None => Err(session_not_found(session_id)),
+        }
+    }
+
+    /// Keep a session opened through the Streamable HTTP endpoint from idling out while the
+    /// client holds a stream of it open, until the returned guard is dropped.
+    pub fn hold_streamable_session(
+        &self,
+        session_id: &str,
+    ) -> Option<SessionStreamGuard<StreamableSession>> {
+        SessionStreamGuard::new(&self.streamable_sessions, session_id)
+    }
+
+    /// Close a session opened through the Streamable HTTP endpoint. Its peer is removed from its
+    /// transport in the background.
+    pub fn close_streamable_session(&self, session_id: &str) {
+        self.streamable_sessions.invalidate(session_id);
+    }
+
+    /// Keep an isolated session from idling out while the client holds a stream of it open,
+    /// until the returned guard is dropped. Sessions sharing the pod of the server are only
+    /// bound to the lifetime of the pod and need no guard.
+    pub fn hold_session(
+        &self,
+        server: &MCPServer,
+        session_id: &str,
+    ) -> Option<SessionStreamGuard<IsolatedSession>> {
+        match server.spec.isolation {
+            MCPServerIsolation::Shared => None,
+            MCPServerIsolation::Session => SessionStreamGuard::new(&self.sessions, session_id),
+        }
+    }
+
+    /// Close a session with the server, typically when the client explicitly terminates it.
+    pub async fn close_session(&self, server: &MCPServer, session_id: String) -> Result<()> {
+        if server.spec.isolation == MCPServerIsolation::Shared {
+            return self.get_transport(server)?.remove_peer(session_id).await;
+        }
+        self.release_session(&session_id);
+        Ok(())
+    }
+
+    /// Tear down the isolated session with the given ID, if any. Its transport is closed and
+    /// its pod deleted in the background.
+    pub fn release_session(&self, session_id: &str) {
+        self.sessions.invalidate(session_id);
     }
 
-    /// Start the HTTP server and listen for incoming requests. This method sets up the API routes,
     /// Start the HTTP server and listen for incoming requests. This method sets up the API routes,
     /// binds to the specified address, and starts serving the API using Axum + Aide.
     #[tracing::instrument(name = "Gateway", skip_all)]
@@ -117,15 +552,78 @@ impl Gateway {
         let ctx = Arc::new(self);
         let mut api = OpenApi::default();
 
+        // --- Periodically flush the counters to the status of the servers.
+        let counters = ctx.counters.clone();
+        let client = ctx.get_client().await;
+        let mut interval = tokio::time::interval(ctx.flush_interval);
+        let _flush = tokio::spawn(async move {
+            loop {
+                let _ = interval.tick().await;
+                counters.flush(&client).await;
+            }
+        });
+
+        // --- Periodically evict the idle sessions, so that their pods are deleted even
+        // --- when the gateway does not receive any other request.
+        let sessions = ctx.sessions.clone();
+        let streamable_sessions = ctx.streamable_sessions.clone();
+        let mut interval = tokio::time::interval(ctx.cleanup_interval);
+        let _eviction = tokio::spawn(async move {
+            loop {
+                let _ = interval.tick().await;
+                streamable_sessions.run_pending_tasks();
+                sessions.run_pending_tasks();
+            }
+        });
+
+        // --- Periodically refresh the heartbeat of the pods of the open sessions, so that
+        // --- the operator only collects the pods the gateway lost track of.
+        let sessions = ctx.sessions.clone();
+        let client = ctx.get_client().await;
+        let period = Duration::from_secs(DEFAULT_SESSION_HEARTBEAT_INTERVAL);
+        let mut interval = tokio::time::interval(period);
+        let _heartbeat = tokio::spawn(async move {
+            loop {
+                let _ = interval.tick().await;
+                let heartbeats = sessions
+                    .iter()
+                    .map(|(_, session)| {
+                        let client = client.clone();
+                        async move { session.touch(&client).await }
+                    })
+                    .collect::<Vec<_>>();
+                let _ = futures::future::join_all(heartbeats).await;
+            }
+        });
+
+        // --- Servers are addressed by their namespace only when the gateway operates
+        // --- in more than one namespace, to keep the routes short in the common case.
+        let base_path = match ctx.controller.is_multi_namespace() {
+            true => "/{namespace}/{name}",
+            false => "/{name}",
+        };
+
+        // --- Only the routes of the servers require authentication, the health, metrics
+        // --- and documentation endpoints are left open for probes and scrapers.
+        let auth = axum::middleware::from_fn_with_state(ctx.authenticator.clone(), authenticate);
+
         // --- Set up the API router with the routes.
         let router = ApiRouter::new()
             .route("/openapi.json", get(super::docs::serve))
             .route("/", Scalar::new("/openapi.json").axum_route())
-            .nest_api_service("/{name}", super::sse::router(ctx.clone()))
+            .route("/metrics", get(metrics))
+            .nest_api_service(
+                base_path,
+                super::sse::router(ctx.clone())
+                    .merge(super::mcp::router(ctx.clone()))
+                    .merge(super::ws::router(ctx.clone()))
+                    .layer(auth),
+            )
             .nest_api_service("/health", super::health::router(ctx.clone()))
             .finish_api_with(&mut api, super::docs::openapi)
             .layer(Extension(api))
             .layer(TraceLayer::new_for_http())
+            .layer(axum::middleware::from_fn(propagate_trace_context))
             .with_state(ctx.clone());
 
         // --- Set up the TCP listener and bind to the address.
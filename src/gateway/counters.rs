@@ -0,0 +1,136 @@
+use crate::{MCPServer, MCPServerCounters, METRICS};
+use chrono::Utc;
+use kube::{Client, ResourceExt};
+use prometheus::IntGauge;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Key identifying an `MCPServer` by its namespace and name.
+type ServerKey = (String, String);
+
+/// Returns the gauge of the sessions opened through the gateway to the server.
+fn sessions_gauge(server: &MCPServer) -> IntGauge {
+    let namespace = server.namespace().unwrap_or_default();
+    let name = server.name_any();
+    METRICS
+        .gateway_sessions
+        .with_label_values(&[namespace.as_str(), name.as_str()])
+}
+
+/// Counts the requests and connections of the `MCPServer` resources served by the gateway in
+/// memory, so that handling a request does not require a round trip to the API server. The
+/// counters are periodically flushed to the status of the servers with [`ServerCounters::flush`].
+#[derive(Debug, Default)]
+pub struct ServerCounters {
+    pending: Mutex<HashMap<ServerKey, (MCPServer, MCPServerCounters)>>,
+}
+
+impl ServerCounters {
+    /// Update the pending counters of a server.
+    fn update(&self, server: &MCPServer, f: impl FnOnce(&mut MCPServerCounters)) {
+        let key = (server.namespace().unwrap_or_default(), server.name_any());
+        let mut pending = self
+            .pending
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        let (_, counters) = pending
+            .entry(key)
+            .or_insert_with(|| (server.clone(), MCPServerCounters::default()));
+        f(counters);
+    }
+
+    /// Register that the server received a request.
+    pub fn notify_request(&self, server: &MCPServer) {
+        self.update(server, |counters| {
+            counters.requests = counters.requests.saturating_add(1);
+            counters.last_request_at = Some(Utc::now());
+        });
+    }
+
+    /// Register an active connection to the server, until the returned guard is dropped.
+    pub fn connect(self: &Arc<Self>, server: &MCPServer) -> ConnectionGuard {
+        self.notify_connect(server);
+        ConnectionGuard {
+            counters: self.clone(),
+            server: server.clone(),
+        }
+    }
+
+    /// Register that an active connection to the server has been established.
+    pub fn notify_connect(&self, server: &MCPServer) {
+        self.update(server, |counters| counters.connections += 1);
+        sessions_gauge(server).inc();
+    }
+
+    /// Register that an active connection to the server has been closed.
+    pub fn notify_disconnect(&self, server: &MCPServer) {
+        self.update(server, |counters| counters.connections -= 1);
+        sessions_gauge(server).dec();
+    }
+
+    /// Apply the pending counters to the status of their servers. The counters that could not
+    /// be applied are kept, and merged with the ones accumulated in the meantime, so that they
+    /// are retried on the next flush.
+    pub async fn flush(&self, client: &Client) {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()));
+        for (key, (server, counters)) in pending {
+            if let Err(error) = server.apply_counters(client, &counters).await {
+                let _ = error.trace();
+                let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+                let (_, current) = pending
+                    .entry(key)
+                    .or_insert_with(|| (server, Default::default()));
+                current.merge(counters);
+            }
+        }
+    }
+}
+
+/// Counts an active connection to a server until dropped, so that the connection is released
+/// on every exit path, such as when the client disconnects or its session expires.
+#[derive(Debug)]
+pub struct ConnectionGuard {
+    counters: Arc<ServerCounters>,
+    server: MCPServer,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.counters.notify_disconnect(&self.server);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MCPServerSpec;
+
+    #[test]
+    fn test_server_counters_accumulate() {
+        let counters = ServerCounters::default();
+        let server = MCPServer::new("my-server", MCPServerSpec::default());
+        counters.notify_connect(&server);
+        counters.notify_request(&server);
+        counters.notify_request(&server);
+        counters.notify_disconnect(&server);
+
+        let pending = counters.pending.lock().unwrap();
+        let (_, counters) = pending
+            .get(&(String::new(), "my-server".to_string()))
+            .unwrap();
+        assert_eq!(counters.requests, 2);
+        assert_eq!(counters.connections, 0);
+        assert!(counters.last_request_at.is_some());
+    }
+
+    #[test]
+    fn test_connection_guard_releases_connection() {
+        let counters = Arc::new(ServerCounters::default());
+        let server = MCPServer::new("my-server", MCPServerSpec::default());
+        let connection = counters.connect(&server);
+        let key = (String::new(), "my-server".to_string());
+        assert_eq!(counters.pending.lock().unwrap()[&key].1.connections, 1);
+        drop(connection);
+        assert_eq!(counters.pending.lock().unwrap()[&key].1.connections, 0);
+    }
+}
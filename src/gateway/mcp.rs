@@ -0,0 +1,317 @@
+use super::{mcp_docs, GatewayContext, ServerPath};
+use crate::{
+    internal_error, request_ids, Error, MCPAccessOperation as Operation, Principal, Result,
+    TransportPeer, MCP_SESSION_ID_HEADER,
+};
+use aide::axum::routing::post_with;
+use aide::axum::{ApiRouter, IntoApiResponse};
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::sse::Event;
+use axum::response::{IntoResponse, Sse};
+use axum::{Extension, Json};
+use futures::{Stream, StreamExt};
+use rmcp::model::{
+    ClientJsonRpcMessage, ClientRequest, JsonRpcBatchResponseItem, JsonRpcMessage, JsonRpcRequest,
+    NumberOrString,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct McpQuery {
+    /// The maximum time to wait for the server to be ready before sending the message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout: Option<u64>,
+}
+
+/// Returns the value of the `Mcp-Session-Id` header, if any.
+fn session_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(MCP_SESSION_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(ToOwned::to_owned)
+}
+
+/// Returns the value of the `Mcp-Session-Id` header, or an error if it is missing.
+fn require_session_id(headers: &HeaderMap) -> Result<String> {
+    session_id(headers).ok_or_else(|| {
+        Error::generic("Missing Mcp-Session-Id header")
+            .with_name("E_SESSION_REQUIRED")
+            .with_status(StatusCode::BAD_REQUEST)
+    })
+}
+
+/// Returns whether the client accepts JSON responses. Clients are expected to accept both
+/// JSON and SSE responses, but we fall back to SSE for those that explicitly do not.
+fn accepts_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_none_or(|value| value.contains("application/json") || value.contains("*/*"))
+}
+
+/// Returns whether the client accepts SSE responses.
+fn accepts_sse(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("text/event-stream"))
+}
+
+/// Returns the SSE event carrying a message sent by the server.
+fn message_event(message: &JsonRpcMessage) -> Option<Event> {
+    let data = serde_json::to_string(message).ok()?;
+    Some(Event::default().event("message").data(data))
+}
+
+/// Returns the message answering the requests of a message whose exchange with the server
+/// failed, with an error for each of them, as a batch if the requests were sent as a batch.
+fn exchange_error(
+    request_ids: Vec<NumberOrString>,
+    is_batch: bool,
+    error: &Error,
+) -> Option<JsonRpcMessage> {
+    let mut errors = request_ids
+        .into_iter()
+        .map(|request_id| internal_error(request_id, error.to_string()));
+    match is_batch {
+        true => Some(JsonRpcMessage::BatchResponse(
+            errors.map(JsonRpcBatchResponseItem::Error).collect(),
+        )),
+        false => errors.next().map(JsonRpcMessage::Error),
+    }
+}
+
+/// Returns the SSE stream answering the requests of a POST: the messages initiated by the
+/// server while the requests are in flight, followed by their results, after which it ends.
+fn post_stream(
+    peer: TransportPeer,
+    outbox: mpsc::UnboundedReceiver<JsonRpcMessage>,
+    message: ClientJsonRpcMessage,
+) -> impl Stream<Item = core::result::Result<Event, Infallible>> {
+    let results = tokio::spawn(async move {
+        let request_ids = request_ids(&message);
+        let is_batch = matches!(message, JsonRpcMessage::BatchRequest(_));
+        match peer.send_request(message).await {
+            Ok(results) => results,
+            Err(error) => {
+                let results = exchange_error(request_ids, is_batch, &error);
+                let _ = error.trace();
+                results
+            }
+        }
+    });
+
+    // --- Forward the messages of the outbox first, so the ones sent by the server before the
+    // --- results are delivered before them, and end the stream with the results.
+    futures::stream::unfold(Some((outbox, results)), |state| async move {
+        let (mut outbox, mut results) = state?;
+        tokio::select! {
+            biased;
+            Some(message) = outbox.recv() => Some((Some(message), Some((outbox, results)))),
+            results = &mut results => Some((results.ok().flatten(), None)),
+        }
+    })
+    .filter_map(|message| async move { Some(Ok(message_event(&message?)?)) })
+}
+
+/// Returns whether the message is an `initialize` request.
+fn is_initialize(message: &ClientJsonRpcMessage) -> bool {
+    matches!(
+        message,
+        JsonRpcMessage::Request(JsonRpcRequest {
+            request: ClientRequest::InitializeRequest(_),
+            ..
+        })
+    )
+}
+
+/// Handler for POST /{name}/mcp
+#[tracing::instrument(name = "POST /{name}/mcp", skip_all)]
+async fn post_message(
+    State(ctx): State<GatewayContext>,
+    Path(path): Path<ServerPath>,
+    Query(query): Query<McpQuery>,
+    headers: HeaderMap,
+    Extension(principal): Extension<Principal>,
+    Json(message): Json<ClientJsonRpcMessage>,
+) -> impl IntoApiResponse {
+    async {
+        let server = ctx.get_server(&path).await?;
+        ctx.authorize(&server, &principal, Operation::Connect)
+            .await?;
+        let timeout = query.timeout.map(Duration::from_secs);
+
+        // --- Request the server and wait until it's ready.
+        ctx.request_server(&server, timeout).await?;
+
+        // --- An `initialize` request without a session opens a new session, backed by a new
+        // --- peer of the transport. Every other message must target an existing session of
+        // --- the principal.
+        let peer = match session_id(&headers) {
+            Some(session_id) => {
+                ctx.get_streamable_peer(&server, &principal, session_id)
+                    .await?
+            }
+            None if is_initialize(&message) => {
+                ctx.open_streamable_session(&server, &principal, timeout)
+                    .await?
+            }
+            None => {
+                return Err(
+                    Error::generic("Only `initialize` requests may open a new session")
+                        .with_name("E_SESSION_REQUIRED")
+                        .with_status(StatusCode::BAD_REQUEST),
+                )
+            }
+        };
+
+        // --- Answer the requests with an SSE stream when the client accepts it, carrying the
+        // --- requests and notifications the server sends until the requests are answered. The
+        // --- session is held while the stream is open, so it never idles out.
+        let session = [(MCP_SESSION_ID_HEADER, peer.id.clone())];
+        if accepts_sse(&headers) && !request_ids(&message).is_empty() {
+            let outbox = ctx.get_streamable_outbox(&peer.id)?.open_post_stream();
+            let guard = (
+                ctx.hold_streamable_session(&peer.id),
+                ctx.hold_session(&server, &peer.id),
+            );
+            let stream = post_stream(peer, outbox, message).map(move |event| {
+                let _ = &guard;
+                event
+            });
+            return Ok((session, Sse::new(stream)).into_response());
+        }
+
+        // --- Otherwise, send the message and answer with the result as JSON, or as a single
+        // --- event SSE stream. Notifications and responses are acknowledged without a body.
+        let response = match peer.send_request(message).await? {
+            None => (StatusCode::ACCEPTED, session).into_response(),
+            Some(result) if accepts_json(&headers) => (session, Json(result)).into_response(),
+            Some(result) => {
+                let data = serde_json::to_string(&result)?;
+                let event = Event::default().event("message").data(data);
+                let stream = futures::stream::once(async { Ok::<_, Infallible>(event) });
+                (session, Sse::new(stream)).into_response()
+            }
+        };
+        Ok::<_, Error>(response)
+    }
+    .await
+    .map_err(|e| e.trace())
+    .into_response()
+}
+
+/// Handler for GET /{name}/mcp
+#[tracing::instrument(name = "GET /{name}/mcp", skip_all)]
+async fn get_stream(
+    State(ctx): State<GatewayContext>,
+    Path(path): Path<ServerPath>,
+    headers: HeaderMap,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoApiResponse {
+    async {
+        let session_id = require_session_id(&headers)?;
+        let server = ctx.get_server(&path).await?;
+        ctx.authorize(&server, &principal, Operation::Connect)
+            .await?;
+
+        // --- Get the peer of the session and stream the messages initiated by the server
+        // --- while no POST of the session is in flight, since those are delivered on its
+        // --- response. The session is held while the stream is open, so it never idles out.
+        let peer = ctx
+            .get_streamable_peer(&server, &principal, session_id)
+            .await?;
+        let outbox = ctx
+            .get_streamable_outbox(&peer.id)?
+            .open_standalone_stream();
+        let guard = (
+            ctx.hold_streamable_session(&peer.id),
+            ctx.hold_session(&server, &peer.id),
+        );
+        let stream = UnboundedReceiverStream::new(outbox).filter_map(move |message| {
+            let _ = &guard;
+            async move { Some(Ok::<_, Infallible>(message_event(&message)?)) }
+        });
+        Ok::<_, Error>(Sse::new(stream))
+    }
+    .await
+    .map_err(|e| e.trace())
+    .into_response()
+}
+
+/// Handler for DELETE /{name}/mcp
+#[tracing::instrument(name = "DELETE /{name}/mcp", skip_all)]
+async fn delete_session(
+    State(ctx): State<GatewayContext>,
+    Path(path): Path<ServerPath>,
+    headers: HeaderMap,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoApiResponse {
+    async {
+        let session_id = require_session_id(&headers)?;
+        let server = ctx.get_server(&path).await?;
+        ctx.authorize(&server, &principal, Operation::Connect)
+            .await?;
+
+        // --- Close the peer of the session, which releases its connection.
+        let peer = ctx
+            .get_streamable_peer(&server, &principal, session_id)
+            .await?;
+        ctx.close_streamable_session(&peer.id);
+        Ok::<_, Error>(StatusCode::NO_CONTENT)
+    }
+    .await
+    .map_err(|e| e.trace())
+    .into_response()
+}
+
+/// Router for the Streamable HTTP endpoint
+pub fn router(ctx: GatewayContext) -> ApiRouter<()> {
+    ApiRouter::new()
+        .api_route(
+            "/mcp",
+            post_with(post_message, mcp_docs::post_docs)
+                .get_with(get_stream, mcp_docs::get_docs)
+                .delete_with(delete_session, mcp_docs::delete_docs),
+        )
+        .with_state(ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_sse() {
+        let mut headers = HeaderMap::new();
+        assert!(!accepts_sse(&headers));
+        let _ = headers.insert(
+            header::ACCEPT,
+            "application/json, text/event-stream".parse().unwrap(),
+        );
+        assert!(accepts_sse(&headers));
+    }
+
+    #[test]
+    fn test_exchange_error_answers_every_request() {
+        let error = Error::generic("Server unavailable");
+        let request_ids = vec![NumberOrString::Number(1), NumberOrString::Number(2)];
+        let Some(JsonRpcMessage::BatchResponse(items)) =
+            exchange_error(request_ids.clone(), true, &error)
+        else {
+            panic!("Expected a batch response");
+        };
+        assert_eq!(items.len(), 2);
+        let Some(JsonRpcMessage::Error(error)) = exchange_error(request_ids, false, &error) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(error.id, NumberOrString::Number(1));
+    }
+}
@@ -0,0 +1,30 @@
+use aide::transform::TransformOperation;
+use axum::Json;
+use rmcp::model::ServerJsonRpcMessage;
+
+/// Documentation for the POST /{name}/mcp endpoint
+pub fn post_docs(op: TransformOperation<'_>) -> TransformOperation<'_> {
+    op.id("postServerMcp")
+        .tag("Server")
+        .summary("Post MCP Message")
+        .description("Sends a message to the server using the Streamable HTTP transport. An `initialize` request without an `Mcp-Session-Id` header opens a new session, whose ID is returned in the `Mcp-Session-Id` response header. Requests, including batches, are answered with an SSE stream when the client accepts `text/event-stream`, carrying the requests and notifications sent by the server until their results, or with a JSON body otherwise. Notifications and responses are acknowledged with a `202 Accepted`. Sessions are closed once idle, and are only accessible to the principal that opened them, other sessions are answered with a `404 Not Found`.")
+        .response::<200, Json<ServerJsonRpcMessage>>()
+        .response::<202, ()>()
+}
+
+/// Documentation for the GET /{name}/mcp endpoint
+pub fn get_docs(op: TransformOperation<'_>) -> TransformOperation<'_> {
+    op.id("getServerMcp")
+        .tag("Server")
+        .summary("Server MCP Stream")
+        .description("Opens a Server-Sent Events (SSE) stream for the session identified by the `Mcp-Session-Id` header. The stream carries the requests and notifications initiated by the server while no request of the session is in flight.")
+}
+
+/// Documentation for the DELETE /{name}/mcp endpoint
+pub fn delete_docs(op: TransformOperation<'_>) -> TransformOperation<'_> {
+    op.id("deleteServerMcp")
+        .tag("Server")
+        .summary("Terminate MCP Session")
+        .description("Terminates the session identified by the `Mcp-Session-Id` header. Does not return or send any data.")
+        .response::<204, ()>()
+}
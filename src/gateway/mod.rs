@@ -1,9 +1,19 @@
+mod cache;
 mod controller;
+mod counters;
 mod docs;
 mod event;
 mod health;
 mod health_docs;
+mod mcp;
+mod mcp_docs;
+mod sessions;
 mod sse;
 mod sse_docs;
+mod ws;
+mod ws_docs;
 
+pub use cache::*;
 pub use controller::*;
+pub use counters::*;
+pub use sessions::*;
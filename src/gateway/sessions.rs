@@ -0,0 +1,373 @@
+use super::ConnectionGuard;
+use crate::{MCPServer, Principal, Transport};
+use kube::{Client, ResourceExt};
+use moka::sync::Cache;
+use moka::Expiry;
+use rmcp::model::JsonRpcMessage;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// A session of an `MCPServer` isolating its sessions, with the ephemeral pod created for it
+/// and the transport connected to that pod.
+#[derive(Debug, Clone)]
+pub struct IsolatedSession {
+    /// The server the session was opened with.
+    pub server: MCPServer,
+
+    /// The ID the ephemeral pod of the session was created with.
+    pub pod_id: String,
+
+    /// The transport connected to the ephemeral pod of the session.
+    pub transport: Transport,
+
+    /// The number of streams of the session currently open with the client.
+    pub streams: Arc<AtomicUsize>,
+}
+
+/// A session whose client may hold streams of it open, during which it never idles out.
+pub trait HeldSession: Clone + Send + Sync + 'static {
+    /// The number of streams of the session currently open with the client.
+    fn streams(&self) -> &Arc<AtomicUsize>;
+
+    /// The time the session may stay idle before it is closed, while no stream is open.
+    fn max_idle_time(&self) -> Duration;
+
+    /// Returns the time the session may stay idle before it is closed. Sessions with an open
+    /// stream never idle out.
+    fn idle_timeout(&self) -> Option<Duration> {
+        match self.streams().load(Ordering::SeqCst) {
+            0 => Some(self.max_idle_time()),
+            _ => None,
+        }
+    }
+}
+
+impl HeldSession for IsolatedSession {
+    fn streams(&self) -> &Arc<AtomicUsize> {
+        &self.streams
+    }
+
+    /// The `idleTimeout` of the server.
+    fn max_idle_time(&self) -> Duration {
+        Duration::from_secs(self.server.spec.idle_timeout.into())
+    }
+}
+
+/// Check if two `MCPServer` resources are the same server, by namespace and name.
+fn is_same_server(a: &MCPServer, b: &MCPServer) -> bool {
+    a.namespace() == b.namespace() && a.name_any() == b.name_any()
+}
+
+/// Check if two principals are the same, by their name and the method they were
+/// authenticated with.
+fn is_same_principal(a: &Principal, b: &Principal) -> bool {
+    a.name == b.name && a.method == b.method
+}
+
+impl IsolatedSession {
+    /// Check if the session was opened with the server.
+    pub fn is_opened_with(&self, server: &MCPServer) -> bool {
+        is_same_server(&self.server, server)
+    }
+
+    /// Refresh the heartbeat of the pod of the session, so the operator knows it is still open.
+    pub async fn touch(&self, client: &Client) {
+        if let Err(error) = self.server.touch_session_pod(client, &self.pod_id).await {
+            let _ = error.trace();
+        }
+    }
+
+    /// Close the transport of the session and delete its pod.
+    pub async fn close(mut self, client: &Client) {
+        if let Err(error) = self.transport.close().await {
+            let _ = error.trace();
+        }
+        if let Err(error) = self.server.delete_session_pod(client, &self.pod_id).await {
+            let _ = error.trace();
+        }
+    }
+}
+
+/// A session opened through the Streamable HTTP endpoint of the gateway. Its client is only
+/// known by the `Mcp-Session-Id` of the session, so the session is closed once idle rather than
+/// when a connection ends, and is only accessible to the principal that opened it.
+#[derive(Debug, Clone)]
+pub struct StreamableSession {
+    /// The server the session was opened with.
+    pub server: MCPServer,
+
+    /// The principal that opened the session.
+    pub principal: Principal,
+
+    /// The transport the peer of the session belongs to.
+    pub transport: Transport,
+
+    /// The time the session may stay idle before it is closed.
+    pub max_idle_time: Duration,
+
+    /// The number of streams of the session currently open with the client.
+    pub streams: Arc<AtomicUsize>,
+
+    /// The connection of the session, released once the session is closed.
+    pub connection: Arc<ConnectionGuard>,
+
+    /// Delivers the messages initiated by the server to the streams of the session.
+    pub outbox: Arc<SessionOutbox>,
+}
+
+/// Delivers each message initiated by the server on a single stream of a session, as the
+/// Streamable HTTP transport requires. Messages go to the SSE response of the most recent POST
+/// still waiting for its results, and to the standalone GET stream when no POST is in flight.
+/// Messages sent while no stream is open are dropped.
+#[derive(Debug, Default)]
+pub struct SessionOutbox {
+    posts: Mutex<Vec<mpsc::UnboundedSender<JsonRpcMessage>>>,
+    standalone: Mutex<Option<mpsc::UnboundedSender<JsonRpcMessage>>>,
+}
+
+impl SessionOutbox {
+    /// Open the stream of a POST, receiving the messages until it is dropped.
+    pub fn open_post_stream(&self) -> mpsc::UnboundedReceiver<JsonRpcMessage> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.posts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(tx);
+        rx
+    }
+
+    /// Open the standalone stream of the session, replacing the previous one, if any.
+    pub fn open_standalone_stream(&self) -> mpsc::UnboundedReceiver<JsonRpcMessage> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        *self.standalone.lock().unwrap_or_else(|e| e.into_inner()) = Some(tx);
+        rx
+    }
+
+    /// Deliver a message initiated by the server. Responses are skipped since they are
+    /// delivered on the POST that triggered them.
+    pub fn dispatch(&self, message: JsonRpcMessage) {
+        if matches!(
+            message,
+            JsonRpcMessage::Response(_)
+                | JsonRpcMessage::Error(_)
+                | JsonRpcMessage::BatchResponse(_)
+        ) {
+            return;
+        }
+
+        // --- Forget the streams of the POSTs that completed, then deliver the message on the
+        // --- most recent one still open, if any.
+        let message = {
+            let mut posts = self.posts.lock().unwrap_or_else(|e| e.into_inner());
+            posts.retain(|tx| !tx.is_closed());
+            match posts.last().map(|tx| tx.send(message)) {
+                Some(Ok(())) => return,
+                Some(Err(error)) => error.0,
+                None => message,
+            }
+        };
+        if let Some(tx) = self
+            .standalone
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+        {
+            let _ = tx.send(message);
+        }
+    }
+
+    /// Close every stream of the session, typically once its peer is closed.
+    pub fn close(&self) {
+        self.posts.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        *self.standalone.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+impl HeldSession for StreamableSession {
+    fn streams(&self) -> &Arc<AtomicUsize> {
+        &self.streams
+    }
+
+    fn max_idle_time(&self) -> Duration {
+        self.max_idle_time
+    }
+}
+
+impl StreamableSession {
+    /// Check if the session was opened with the server by the principal. Principals are
+    /// identified by their name and the method they were authenticated with.
+    pub fn is_owned_by(&self, server: &MCPServer, principal: &Principal) -> bool {
+        is_same_server(&self.server, server) && is_same_principal(&self.principal, principal)
+    }
+}
+
+/// A session opened through the SSE endpoint of the gateway. It lives as long as the stream
+/// of the session is open, and its messages are only accepted from the principal that opened it.
+#[derive(Debug, Clone)]
+pub struct SseSession {
+    /// The server the session was opened with.
+    pub server: MCPServer,
+
+    /// The principal that opened the session.
+    pub principal: Principal,
+}
+
+impl SseSession {
+    /// Check if the session was opened with the server by the principal.
+    pub fn is_owned_by(&self, server: &MCPServer, principal: &Principal) -> bool {
+        is_same_server(&self.server, server) && is_same_principal(&self.principal, principal)
+    }
+}
+
+/// Expires the sessions once they have been idle for their maximum idle time, which is the
+/// `idleTimeout` of their server for isolated sessions. Every read of a session counts as
+/// activity and restarts the countdown.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SessionExpiry;
+
+impl<S: HeldSession> Expiry<String, S> for SessionExpiry {
+    fn expire_after_create(&self, _: &String, session: &S, _: Instant) -> Option<Duration> {
+        session.idle_timeout()
+    }
+
+    fn expire_after_read(
+        &self,
+        _: &String,
+        session: &S,
+        _: Instant,
+        _: Option<Duration>,
+        _: Instant,
+    ) -> Option<Duration> {
+        session.idle_timeout()
+    }
+
+    fn expire_after_update(
+        &self,
+        _: &String,
+        session: &S,
+        _: Instant,
+        _: Option<Duration>,
+    ) -> Option<Duration> {
+        session.idle_timeout()
+    }
+}
+
+/// The isolated sessions opened through the gateway, by the ID of their peer.
+pub type SessionStore = Cache<String, IsolatedSession>;
+
+/// The sessions opened through the Streamable HTTP endpoint of the gateway, by their ID.
+pub type StreamableSessionStore = Cache<String, StreamableSession>;
+
+/// The sessions opened through the SSE endpoint of the gateway, by their ID.
+pub type SseSessionStore = Cache<String, SseSession>;
+
+/// Keeps a session from idling out while a stream of the session is open with the client.
+/// Once dropped, the idle countdown of the session restarts.
+#[derive(Debug)]
+pub struct SessionStreamGuard<S: HeldSession> {
+    sessions: Cache<String, S>,
+    session_id: String,
+    streams: Arc<AtomicUsize>,
+}
+
+impl<S: HeldSession> SessionStreamGuard<S> {
+    /// Hold the session with the given ID, if it is still open.
+    pub fn new(sessions: &Cache<String, S>, session_id: &str) -> Option<Self> {
+        let session = sessions.get(session_id)?;
+        let streams = session.streams().clone();
+        let _ = streams.fetch_add(1, Ordering::SeqCst);
+
+        // --- Read the session again so its expiration is cleared now the stream is counted.
+        let _ = sessions.get(session_id);
+        Some(Self {
+            sessions: sessions.clone(),
+            session_id: session_id.to_string(),
+            streams,
+        })
+    }
+}
+
+impl<S: HeldSession> Drop for SessionStreamGuard<S> {
+    fn drop(&mut self) {
+        let _ = self.streams.fetch_sub(1, Ordering::SeqCst);
+        let _ = self.sessions.get(&self.session_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AuthMethod, MCPServerSpec};
+    use kube::Resource;
+
+    fn server(namespace: &str, name: &str) -> MCPServer {
+        let mut server = MCPServer::new(name, MCPServerSpec::default());
+        server.meta_mut().namespace = Some(namespace.to_string());
+        server
+    }
+
+    fn message(value: serde_json::Value) -> JsonRpcMessage {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_outbox_delivers_on_a_single_stream() {
+        let outbox = SessionOutbox::default();
+        let progress = || {
+            message(serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/progress",
+                "params": { "progressToken": 1, "progress": 1 }
+            }))
+        };
+        let mut standalone = outbox.open_standalone_stream();
+        let mut post = outbox.open_post_stream();
+
+        // --- The POST in flight receives the messages, but not the responses.
+        outbox.dispatch(progress());
+        outbox.dispatch(message(
+            serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": {} }),
+        ));
+        assert!(post.try_recv().is_ok());
+        assert!(post.try_recv().is_err());
+        assert!(standalone.try_recv().is_err());
+
+        // --- Once the POST completed, the standalone stream receives them.
+        drop(post);
+        outbox.dispatch(progress());
+        assert!(standalone.try_recv().is_ok());
+
+        // --- Closing the outbox ends the streams.
+        outbox.close();
+        assert!(matches!(
+            standalone.try_recv(),
+            Err(mpsc::error::TryRecvError::Disconnected)
+        ));
+    }
+
+    #[test]
+    fn test_is_same_server() {
+        assert!(is_same_server(&server("a", "x"), &server("a", "x")));
+        assert!(!is_same_server(&server("a", "x"), &server("a", "y")));
+        assert!(!is_same_server(&server("a", "x"), &server("b", "x")));
+    }
+
+    #[test]
+    fn test_sse_session_is_owned_by() {
+        let principal = |name: &str, method| Principal {
+            name: name.to_string(),
+            groups: Vec::new(),
+            method,
+        };
+        let session = SseSession {
+            server: server("a", "x"),
+            principal: principal("alice", AuthMethod::Jwt),
+        };
+        assert!(session.is_owned_by(&server("a", "x"), &principal("alice", AuthMethod::Jwt)));
+        assert!(!session.is_owned_by(&server("a", "y"), &principal("alice", AuthMethod::Jwt)));
+        assert!(!session.is_owned_by(&server("a", "x"), &principal("bob", AuthMethod::Jwt)));
+        assert!(!session.is_owned_by(&server("a", "x"), &principal("alice", AuthMethod::ApiKey)));
+    }
+}
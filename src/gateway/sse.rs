@@ -1,15 +1,15 @@
-use super::{sse_docs, GatewayContext};
+use super::{sse_docs, GatewayContext, ServerPath};
 use crate::{
-    Error, MCPServer, MCPServerCondition as Condition, MCPServerRequestedState as RequestState,
-    ResourceManager,
+    Error, MCPAccessOperation as Operation, MCPServerCondition as Condition, MCPServerIsolation,
+    MCPServerRequestedState as RequestState, Principal,
 };
 use aide::axum::routing::{get_with, post_with};
 use aide::axum::{ApiRouter, IntoApiResponse};
 use axum::body::Body;
 use axum::extract::{Path, Query, State};
-use axum::http::header;
+use axum::http::{header, StatusCode};
 use axum::response::IntoResponse;
-use axum::Json;
+use axum::{Extension, Json};
 use futures::AsyncBufReadExt;
 use rmcp::model::ClientJsonRpcMessage;
 use schemars::JsonSchema;
@@ -28,31 +28,40 @@ pub struct SseQuery {
 /// Handler for GET /{name}/sse
 #[tracing::instrument(name = "GET /{name}/sse", skip_all)]
 async fn sse(
-    Path(name): Path<String>,
+    Path(path): Path<ServerPath>,
     Query(query): Query<SseQuery>,
     State(ctx): State<GatewayContext>,
+    Extension(principal): Extension<Principal>,
 ) -> impl IntoApiResponse {
     async {
-        let client = ctx.get_client().await;
-        let server = MCPServer::get_by_name(&client, &name).await?;
+        let server = ctx.get_server(&path).await?;
+        ctx.authorize(&server, &principal, Operation::Connect)
+            .await?;
         let timeout = query.timeout.map(Duration::from_secs);
-        let reason = RequestState::Connection;
-        let condition = Condition::Requested(reason);
-
-        // --- Update the status so it can be picked-up by the operator.
-        server.request(&client).await?;
-        server.notify_connect(&client).await?;
-        server.push_condition(&client, condition).await?;
-        server.wait_until_ready(&client, timeout).await?;
-
-        // --- Get the transport for the server and create a peer.
-        let mut transport = ctx.get_transport(&server)?;
-        let peer = transport.subscribe().await?;
-        let endpoint = format!("/{name}/message");
 
-        // --- Create the handler for the SSE stream closure.
-        let server = server.clone();
-        let on_close = || tokio::spawn(async move { server.notify_disconnect(&client).await });
+        // --- Request the server and wait until it's ready.
+        ctx.request_server(&server, timeout).await?;
+
+        // --- Open a session with the server and count the connection until the stream closes.
+        let peer = ctx.open_sse_session(&server, &principal, timeout).await?;
+        let connection = ctx.counters().connect(&server);
+        let endpoint = format!("{}/message", path.base_path());
+
+        // --- Create the handler for the SSE stream closure, closing the session along with
+        // --- the pod of an isolated session. It runs once the stream is dropped, including
+        // --- when the client disconnects. The session is held for as long as the stream is
+        // --- open, so it never idles out.
+        let session_id = peer.id.clone();
+        let guard = ctx.hold_session(&server, &session_id);
+        let on_close = move || {
+            drop(guard);
+            drop(connection);
+            drop(tokio::spawn(async move {
+                if let Err(error) = ctx.close_sse_session(&server, session_id).await {
+                    let _ = error.trace();
+                }
+            }));
+        };
         let stream = peer.sse(endpoint, on_close).await;
         Ok::<_, Error>(stream)
     }
@@ -74,43 +83,77 @@ pub struct MessageQuery {
 #[tracing::instrument(name = "POST /{name}/message", skip_all)]
 async fn message(
     State(ctx): State<GatewayContext>,
-    Path(name): Path<String>,
+    Path(path): Path<ServerPath>,
     Query(query): Query<MessageQuery>,
+    Extension(principal): Extension<Principal>,
     Json(request): Json<ClientJsonRpcMessage>,
 ) -> impl IntoApiResponse {
     async {
-        let client = ctx.get_client().await;
-        let server = MCPServer::get_by_name(&client, &name).await?;
+        let server = ctx.get_server(&path).await?;
+        ctx.authorize(&server, &principal, Operation::Connect)
+            .await?;
         let timeout = query.timeout.map(Duration::from_secs);
-        let reason = RequestState::Connection;
-        let condition = Condition::Requested(reason);
 
         // --- Request the server and wait until it's ready.
-        server.request(&client).await?;
-        server.push_condition(&client, condition).await?;
-        server.wait_until_ready(&client, timeout).await?;
-
-        // --- Get the transport for the server and send the request.
-        let transport = ctx.get_transport(&server)?;
-        let peer = transport.get_peer(query.session_id).await?;
-        let result = peer.send_request(request).await?;
-        Ok::<_, Error>(Json(result))
+        ctx.request_server(&server, timeout).await?;
+
+        // --- Get the peer of the session, which must have been opened by the same principal,
+        // --- and send the request.
+        let peer = ctx
+            .get_sse_peer(&server, &principal, query.session_id)
+            .await?;
+        let response = match peer.send_request(request).await? {
+            Some(result) => Json(result).into_response(),
+            None => StatusCode::ACCEPTED.into_response(),
+        };
+        Ok::<_, Error>(response)
     }
     .await
     .map_err(|e| e.trace())
     .into_response()
 }
 
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsQuery {
+    /// The ID of the session whose pod logs should be streamed, required for the servers
+    /// isolating their sessions since they have no shared pod.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    session_id: Option<String>,
+}
+
 /// Handler for `GET /{name}/logs`
 #[tracing::instrument(name = "GET /{name}/logs", skip_all)]
-async fn logs(Path(name): Path<String>, State(ctx): State<GatewayContext>) -> impl IntoApiResponse {
+async fn logs(
+    Path(path): Path<ServerPath>,
+    Query(query): Query<LogsQuery>,
+    State(ctx): State<GatewayContext>,
+    Extension(principal): Extension<Principal>,
+) -> impl IntoApiResponse {
     async {
         let client = ctx.get_client().await;
-        let server = MCPServer::get_by_name(&client, &name).await?;
-        server.wait_until_ready(&client, None).await?;
-
-        // --- Get the log stream for the server.
-        let stream = server.get_logs(&client).await?;
+        let server = ctx.get_server(&path).await?;
+        ctx.authorize(&server, &principal, Operation::Logs).await?;
+
+        // --- Get the log stream for the pod of the server, or for the pod of the requested
+        // --- session if the server isolates its sessions.
+        let pod_id = match (server.spec.isolation, query.session_id) {
+            (MCPServerIsolation::Shared, _) => {
+                server.wait_until_ready(&client, None).await?;
+                None
+            }
+            (MCPServerIsolation::Session, Some(session_id)) => {
+                Some(ctx.get_session_pod_id(&server, &session_id)?)
+            }
+            (MCPServerIsolation::Session, None) => {
+                return Err(Error::generic(
+                    "Servers isolating their sessions require a `sessionId` to stream logs",
+                )
+                .with_name("E_SESSION_REQUIRED")
+                .with_status(StatusCode::BAD_REQUEST))
+            }
+        };
+        let stream = server.get_logs(&client, pod_id.as_deref()).await?;
         let stream = stream.lines();
         let stream = futures::StreamExt::map(stream, |bytes| match bytes {
             Ok(bytes) => Ok(bytes::Bytes::from(bytes)),
@@ -136,12 +179,15 @@ async fn logs(Path(name): Path<String>, State(ctx): State<GatewayContext>) -> im
 /// Handler for POST /{name}/request
 #[tracing::instrument(name = "POST /{name}/request", skip_all)]
 async fn request(
-    Path(name): Path<String>,
+    Path(path): Path<ServerPath>,
     State(ctx): State<GatewayContext>,
+    Extension(principal): Extension<Principal>,
 ) -> impl IntoApiResponse {
     async {
         let client = ctx.get_client().await;
-        let server = MCPServer::get_by_name(&client, &name).await?;
+        let server = ctx.get_server(&path).await?;
+        ctx.authorize(&server, &principal, Operation::Request)
+            .await?;
         let reason = RequestState::ManualStart;
         let condition = Condition::Requested(reason);
 
@@ -157,12 +203,15 @@ async fn request(
 /// Handler for `POST /{name}/shutdown`
 #[tracing::instrument(name = "POST /{name}/shutdown", skip_all)]
 async fn shutdown(
-    Path(name): Path<String>,
+    Path(path): Path<ServerPath>,
     State(ctx): State<GatewayContext>,
+    Extension(principal): Extension<Principal>,
 ) -> impl IntoApiResponse {
     async {
         let client = ctx.get_client().await;
-        let server = MCPServer::get_by_name(&client, &name).await?;
+        let server = ctx.get_server(&path).await?;
+        ctx.authorize(&server, &principal, Operation::Shutdown)
+            .await?;
         let reason = RequestState::ManualStop;
         let condition = Condition::Requested(reason);
 
@@ -15,8 +15,9 @@ pub fn message_docs(op: TransformOperation<'_>) -> TransformOperation<'_> {
     op.id("postServerSseMessage")
         .tag("Server")
         .summary("Post SSE Message")
-        .description("Sends a message to the server. The server will process the message and return a response.")
+        .description("Sends a message to the server. Requests are answered with the response of the server, while notifications and responses are acknowledged with a `202 Accepted`.")
         .response::<200, Json<ServerJsonRpcMessage>>()
+        .response::<202, ()>()
 }
 
 /// Documentation for the GET /{name}/logs endpoint
@@ -24,7 +25,7 @@ pub fn logs_docs(op: TransformOperation<'_>) -> TransformOperation<'_> {
     op.id("getServerLogs")
         .tag("Server")
         .summary("Get Server Logs")
-        .description("Retrieves the logs for the server. This is useful for debugging and monitoring server activity.")
+        .description("Retrieves the logs for the server. This is useful for debugging and monitoring server activity. Servers isolating their sessions have no shared pod, so the `sessionId` of the session whose pod logs should be streamed is required, otherwise a `400 Bad Request` is returned.")
         .response::<200, String>()
 }
 
@@ -0,0 +1,151 @@
+use super::{ws_docs, GatewayContext, ServerPath};
+use crate::{Error, MCPAccessOperation as Operation, MCPServer, Principal, TransportPeer};
+use aide::axum::routing::get_with;
+use aide::axum::{ApiRouter, IntoApiResponse};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, Query, State};
+use axum::response::IntoResponse;
+use axum::Extension;
+use futures::{SinkExt, StreamExt};
+use rmcp::model::{ClientJsonRpcMessage, JsonRpcMessage};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WsQuery {
+    /// The maximum time to wait for the server to be ready before upgrading the connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout: Option<u64>,
+}
+
+/// Pipe the JSON-RPC messages between the WebSocket and the peer until either side closes.
+/// The messages of the client are sent with `TransportPeer::send_request`, each from its own
+/// task, and their responses are delivered from the result rather than from the message stream
+/// of the peer, which only carries the messages initiated by the server.
+async fn pipe(socket: WebSocket, peer: &TransportPeer) {
+    let (mut sink, mut stream) = socket.split();
+    let (results_tx, mut results_rx) = mpsc::unbounded_channel::<JsonRpcMessage>();
+    let mut messages = Box::pin(peer.messages().await.filter(|message| {
+        let is_response = matches!(
+            message,
+            JsonRpcMessage::Response(_) | JsonRpcMessage::Error(_)
+        );
+        std::future::ready(!is_response)
+    }));
+
+    // --- Forward every message of the server and every result to the client as a text frame.
+    let outbound = async {
+        loop {
+            let message = tokio::select! {
+                message = messages.next() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+                Some(result) = results_rx.recv() => result,
+            };
+            let Ok(data) = serde_json::to_string(&message) else {
+                continue;
+            };
+            if sink.send(Message::Text(data.into())).await.is_err() {
+                break;
+            }
+        }
+    };
+
+    // --- Forward every text or binary frame of the client to the server. The pending requests
+    // --- are dropped along with the socket.
+    let inbound = async {
+        let mut requests = JoinSet::new();
+        while let Some(Ok(frame)) = stream.next().await {
+            let message = match frame {
+                Message::Text(text) => serde_json::from_str::<ClientJsonRpcMessage>(text.as_str()),
+                Message::Binary(bytes) => serde_json::from_slice::<ClientJsonRpcMessage>(&bytes),
+                Message::Ping(_) | Message::Pong(_) => continue,
+                Message::Close(_) => break,
+            };
+            match message {
+                Ok(message) => {
+                    let peer = peer.clone();
+                    let results_tx = results_tx.clone();
+                    let _ = requests.spawn(async move {
+                        match peer.send_request(message).await {
+                            Ok(Some(result)) => {
+                                let _ = results_tx.send(result);
+                            }
+                            Ok(None) => {}
+                            Err(error) => {
+                                let _ = error.trace();
+                            }
+                        }
+                    });
+                }
+                Err(error) => {
+                    tracing::warn!("Ignoring malformed message from client: {}", error);
+                }
+            }
+            while requests.try_join_next().is_some() {}
+        }
+    };
+
+    tokio::select! {
+        () = outbound => tracing::info!("WebSocket closed by the server"),
+        () = inbound => tracing::info!("WebSocket closed by the client"),
+    }
+}
+
+/// Handler for GET /{name}/ws
+#[tracing::instrument(name = "GET /{name}/ws", skip_all)]
+async fn ws(
+    Path(path): Path<ServerPath>,
+    Query(query): Query<WsQuery>,
+    State(ctx): State<GatewayContext>,
+    Extension(principal): Extension<Principal>,
+    upgrade: WebSocketUpgrade,
+) -> impl IntoApiResponse {
+    async {
+        let server = ctx.get_server(&path).await?;
+        ctx.authorize(&server, &principal, Operation::Connect)
+            .await?;
+        let timeout = query.timeout.map(Duration::from_secs);
+
+        // --- Request the server and wait until it's ready.
+        ctx.request_server(&server, timeout).await?;
+
+        // --- Open a session with the server and count the connection until the socket closes,
+        // --- or until the upgrade fails and the callback is dropped without being called.
+        let peer = ctx.open_session(&server, timeout).await?;
+        let connection = ctx.counters().connect(&server);
+
+        // --- Upgrade the connection and release the peer once the socket is closed. The
+        // --- session is held while the socket is open, so it never idles out.
+        let guard = ctx.hold_session(&server, &peer.id);
+        let response = upgrade.on_upgrade(move |socket| async move {
+            pipe(socket, &peer).await;
+            drop(guard);
+            on_close(&ctx, server, peer).await;
+            drop(connection);
+        });
+        Ok::<_, Error>(response)
+    }
+    .await
+    .map_err(|e| e.trace())
+    .into_response()
+}
+
+/// Close the session of the peer.
+async fn on_close(ctx: &GatewayContext, server: MCPServer, peer: TransportPeer) {
+    if let Err(error) = ctx.close_session(&server, peer.id).await {
+        let _ = error.trace();
+    }
+}
+
+/// Router for the WebSocket endpoint
+pub fn router(ctx: GatewayContext) -> ApiRouter<()> {
+    ApiRouter::new()
+        .api_route("/ws", get_with(ws, ws_docs::ws_docs))
+        .with_state(ctx)
+}
@@ -0,0 +1,10 @@
+use aide::transform::TransformOperation;
+
+/// Documentation for the GET /{name}/ws endpoint
+pub fn ws_docs(op: TransformOperation<'_>) -> TransformOperation<'_> {
+    op.id("getServerWs")
+        .tag("Server")
+        .summary("Server WebSocket")
+        .description("Upgrades the connection to a WebSocket connected to the server. Every text or binary frame sent by the client is a JSON-RPC message forwarded to the server, and every message of the server is sent back as a text frame.")
+        .response::<101, ()>()
+}
@@ -1,7 +1,7 @@
 use clap::Parser;
 use kube::CustomResourceExt;
 use nmcp::{install_tracing, serialize};
-use nmcp::{Cli, Command, Controller, ErrorInner, Gateway, Result, ResultExt};
+use nmcp::{Authenticator, Cli, Command, Controller, ErrorInner, Gateway, Result, ResultExt};
 use nmcp::{MCPPool, MCPServer};
 use tokio::fs::File;
 use tokio::io::{stdout, AsyncWriteExt};
@@ -10,31 +10,39 @@ use tokio::io::{stdout, AsyncWriteExt};
 #[tokio::main]
 async fn main() -> Result<()> {
     let arguments = Cli::parse();
-    install_tracing(&arguments.tracing_options);
+    let _tracing = install_tracing(&arguments.tracing_options)?;
 
     // --- Start the operator or API server based on the command
     let result = match arguments.command {
         // Start the operator.
-        Command::Operator { controller_options } => {
+        Command::Operator {
+            controller_options,
+            operator_options,
+        } => {
             let controller = Controller::new(&controller_options).await?;
-            controller.start_server_operator().await
+            controller.start_operator(&operator_options).await
         }
         // Start the gateway API server.
         Command::Gateway {
             controller_options,
             gateway_options,
+            auth_options,
         } => {
             let controller = Controller::new(&controller_options).await?;
-            let server = Gateway::new(gateway_options, controller).await?;
+            let authenticator = Authenticator::new(&auth_options, &controller).await?;
+            let server = Gateway::new(gateway_options, controller, authenticator).await?;
             server.start().await
         }
         // Start the manager API server.
         Command::Manager {
             controller_options,
             manager_options,
+            auth_options,
         } => {
             let controller = Controller::new(&controller_options).await?;
-            let server = nmcp::manager::Manager::new(manager_options, controller).await?;
+            let authenticator = Authenticator::new(&auth_options, &controller).await?;
+            let server =
+                nmcp::manager::Manager::new(manager_options, controller, authenticator).await?;
             server.start().await
         }
         // Export CRD or schema
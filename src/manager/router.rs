@@ -1,4 +1,6 @@
-use crate::{Controller, Error, Result};
+use crate::{
+    authenticate, authorize_manager, render_metrics, Authenticator, Controller, Error, Result,
+};
 use aide::axum::routing::get;
 use aide::axum::ApiRouter;
 use aide::openapi::OpenApi;
@@ -30,6 +32,7 @@ pub struct ManagerOptions {
 pub struct Manager {
     address: SocketAddr,
     controller: Controller,
+    authenticator: Authenticator,
 }
 
 impl std::fmt::Debug for Manager {
@@ -37,6 +40,7 @@ impl std::fmt::Debug for Manager {
         f.debug_struct("Manager")
             .field("address", &self.address)
             .field("controller", &"Controller(...)")
+            .field("authenticator", &self.authenticator)
             .finish()
     }
 }
@@ -45,10 +49,15 @@ pub type ManagerContext = Arc<Manager>;
 
 impl Manager {
     /// Create a new server instance
-    pub async fn new(options: ManagerOptions, controller: Controller) -> Result<Self> {
+    pub async fn new(
+        options: ManagerOptions,
+        controller: Controller,
+        authenticator: Authenticator,
+    ) -> Result<Self> {
         Ok(Self {
             address: SocketAddr::new(options.host, options.port),
             controller,
+            authenticator,
         })
     }
 
@@ -69,14 +78,31 @@ impl Manager {
         let ctx = Arc::new(self);
         let mut api = OpenApi::default();
 
+        // --- Only the resource routes require authentication, the health, metrics
+        // --- and documentation endpoints are left open for probes and scrapers. The
+        // --- resource routes are further restricted to the principals allowed to manage
+        // --- the servers, since they can run arbitrary images in the cluster.
+        let auth = axum::middleware::from_fn_with_state(ctx.authenticator.clone(), authenticate);
+        let manager =
+            axum::middleware::from_fn_with_state(ctx.authenticator.clone(), authorize_manager);
+
         // --- Set up the API router with the routes.
         let router = ApiRouter::new()
             .route("/openapi.json", get(super::docs::serve))
             .route("/", Scalar::new("/openapi.json").axum_route())
             .route("/redoc", Redoc::new("/openapi.json").axum_route())
             .route("/swagger", Swagger::new("/openapi.json").axum_route())
-            .nest_api_service("/api/v1/servers", super::server::router(ctx.clone()))
-            .nest_api_service("/api/v1/pools", super::pool::router(ctx.clone()))
+            .route("/metrics", get(render_metrics))
+            .nest_api_service(
+                "/api/v1/servers",
+                super::server::router(ctx.clone())
+                    .layer(manager.clone())
+                    .layer(auth.clone()),
+            )
+            .nest_api_service(
+                "/api/v1/pools",
+                super::pool::router(ctx.clone()).layer(manager).layer(auth),
+            )
             .nest_api_service("/health", super::health::router(ctx.clone()))
             .finish_api_with(&mut api, super::docs::openapi)
             .layer(Extension(api))
@@ -0,0 +1,135 @@
+use crate::Principal;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// An operation of the gateway that can be granted to the clients of a server.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum MCPAccessOperation {
+    /// Open a session with the server and exchange messages with it, over any transport.
+    Connect,
+
+    /// Stream the logs of the server.
+    Logs,
+
+    /// Manually request the server to start.
+    Request,
+
+    /// Manually request the server to shut down.
+    Shutdown,
+}
+
+impl Display for MCPAccessOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connect => write!(f, "connect"),
+            Self::Logs => write!(f, "logs"),
+            Self::Request => write!(f, "request"),
+            Self::Shutdown => write!(f, "shutdown"),
+        }
+    }
+}
+
+/// A rule granting a set of principals access to the servers it is attached to, either
+/// directly on the `MCPServer` or on the `MCPPool` they belong to.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MCPAccessRule {
+    /// Names of the principals granted access, such as the name of an API key, the subject
+    /// of a JWT or the username of a Kubernetes service account.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub principals: Vec<String>,
+
+    /// Groups whose members are granted access.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+
+    /// Operations granted by the rule. All operations are granted if omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operations: Option<Vec<MCPAccessOperation>>,
+}
+
+impl MCPAccessRule {
+    /// Check if the rule grants the principal the given operation.
+    pub fn allows(&self, principal: &Principal, operation: MCPAccessOperation) -> bool {
+        let is_principal_matched = self.principals.contains(&principal.name)
+            || principal
+                .groups
+                .iter()
+                .any(|group| self.groups.contains(group));
+        let is_operation_matched = self
+            .operations
+            .as_ref()
+            .is_none_or(|operations| operations.contains(&operation));
+        is_principal_matched && is_operation_matched
+    }
+
+    /// Check if a set of rules grants the principal the given operation. An empty set of
+    /// rules does not restrict the access, so servers without rules remain open.
+    pub fn is_allowed(
+        rules: &[Self],
+        principal: &Principal,
+        operation: MCPAccessOperation,
+    ) -> bool {
+        rules.is_empty() || rules.iter().any(|rule| rule.allows(principal, operation))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AuthMethod;
+
+    fn principal(name: &str, groups: &[&str]) -> Principal {
+        Principal {
+            name: name.to_string(),
+            groups: groups.iter().map(ToString::to_string).collect(),
+            method: AuthMethod::ApiKey,
+        }
+    }
+
+    #[test]
+    fn test_access_rule_matches_principals_and_groups() {
+        let rule = MCPAccessRule {
+            principals: vec!["alice".to_string()],
+            groups: vec!["admins".to_string()],
+            operations: None,
+        };
+        let operation = MCPAccessOperation::Connect;
+        assert!(rule.allows(&principal("alice", &[]), operation));
+        assert!(rule.allows(&principal("bob", &["admins"]), operation));
+        assert!(!rule.allows(&principal("bob", &["users"]), operation));
+    }
+
+    #[test]
+    fn test_access_rule_restricts_operations() {
+        let rule = MCPAccessRule {
+            principals: vec!["alice".to_string()],
+            groups: vec![],
+            operations: Some(vec![MCPAccessOperation::Connect, MCPAccessOperation::Logs]),
+        };
+        let alice = principal("alice", &[]);
+        assert!(rule.allows(&alice, MCPAccessOperation::Logs));
+        assert!(!rule.allows(&alice, MCPAccessOperation::Shutdown));
+    }
+
+    #[test]
+    fn test_access_rules_are_open_when_empty() {
+        let alice = principal("alice", &[]);
+        assert!(MCPAccessRule::is_allowed(
+            &[],
+            &alice,
+            MCPAccessOperation::Shutdown
+        ));
+        let rules = [MCPAccessRule {
+            principals: vec!["bob".to_string()],
+            ..Default::default()
+        }];
+        assert!(!MCPAccessRule::is_allowed(
+            &rules,
+            &alice,
+            MCPAccessOperation::Connect
+        ));
+    }
+}
@@ -1,3 +1,4 @@
+mod access_rule;
 mod pool_controller;
 mod pool_spec;
 mod pool_status;
@@ -5,17 +6,20 @@ mod server_condition;
 mod server_controller;
 mod server_into_pod;
 mod server_into_service;
+mod server_isolation;
 mod server_spec;
 mod server_status;
 mod server_transport;
 mod trait_into_resource;
 mod trait_manager;
 
+pub use access_rule::{MCPAccessOperation, MCPAccessRule};
 pub use pool_spec::{MCPPool, MCPPoolSpec};
 pub use pool_status::*;
 pub use server_condition::*;
+pub use server_isolation::MCPServerIsolation;
 pub use server_spec::{MCPServer, MCPServerSpec};
-pub use server_status::{MCPServerPhase, MCPServerStatus};
+pub use server_status::{MCPServerCounters, MCPServerPhase, MCPServerStatus};
 pub use server_transport::MCPServerTransport;
 pub use trait_into_resource::IntoResource;
 pub use trait_manager::ResourceManager;
@@ -1,5 +1,13 @@
-use super::{MCPPool, ResourceManager};
-use kube::api::ObjectMeta;
+use super::server_controller::PodStatus;
+use super::{MCPPool, MCPPoolStatus, MCPServer, MCPServerPhase as Phase, ResourceManager};
+use crate::{
+    Error, Result, DEFAULT_SESSION_HEARTBEAT_TIMEOUT, MCP_SESSION_HEARTBEAT_ANNOTATION,
+    MCP_SESSION_LABEL, METRICS,
+};
+use chrono::{DateTime, Utc};
+use k8s_openapi::api::core::v1;
+use kube::api::{DeleteParams, ListParams, ObjectMeta};
+use kube::{Api, Client, ResourceExt};
 
 impl ResourceManager for MCPPool {
     fn new(name: &str, spec: Self::Spec) -> Self {
@@ -13,3 +21,432 @@ impl ResourceManager for MCPPool {
         }
     }
 }
+
+impl MCPPool {
+    /// Returns the `MCPServer` resources referencing this pool, ranked by creation time. The
+    /// oldest servers come first and are the ones admitted within the `max_servers_limit`.
+    pub fn rank_servers<'a>(&self, servers: &'a [MCPServer]) -> Vec<&'a MCPServer> {
+        let name = self.name_any();
+        let mut servers: Vec<_> = servers
+            .iter()
+            .filter(|server| server.spec.pool == name)
+            .collect();
+        servers.sort_by(|a, b| {
+            a.metadata
+                .creation_timestamp
+                .cmp(&b.metadata.creation_timestamp)
+                .then_with(|| a.name_any().cmp(&b.name_any()))
+        });
+        servers
+    }
+
+    /// Check if the given `MCPServer` is admitted by the pool, meaning it does not overflow
+    /// the `max_servers_limit` of the pool once ranked by creation time.
+    pub fn is_server_admitted(&self, server: &MCPServer, servers: &[MCPServer]) -> bool {
+        let limit = self.spec.max_servers_limit as usize;
+        self.rank_servers(servers)
+            .into_iter()
+            .take(limit)
+            .any(|admitted| admitted.name_any() == server.name_any())
+    }
+
+    /// Compute the `MCPPoolStatus` from the given `MCPServer` resources. Servers that do not
+    /// reference this pool are ignored, so the full list of servers can be passed as-is. The
+    /// given number of live session pods is counted as active, since they hold a slot of the
+    /// `max_servers_active` of the pool just like the active servers.
+    pub fn compute_status(&self, servers: &[MCPServer], sessions: usize) -> MCPPoolStatus {
+        let limit = self.spec.max_servers_limit as usize;
+        let mut status = MCPPoolStatus::default();
+        for (rank, server) in self.rank_servers(servers).into_iter().enumerate() {
+            status.total_servers_count += 1;
+
+            // --- Servers overflowing the limit are unmanaged, they never get a Pod
+            // --- and are therefore neither active nor pending.
+            if rank >= limit {
+                status.unmanaged_servers_count += 1;
+                continue;
+            }
+
+            let phase = server.status.clone().unwrap_or_default().phase;
+            match phase {
+                Phase::Starting | Phase::Ready | Phase::Stopping => {
+                    status.active_servers_count += 1
+                }
+                Phase::Requested => status.pending_servers_count += 1,
+                Phase::Idle | Phase::Degraded => {}
+            }
+            status.managed_servers_count += 1;
+        }
+        status.active_servers_count += sessions as u32;
+        status
+    }
+
+    /// Export the number of servers in each phase and the capacity usage of the pool.
+    pub fn record_metrics(&self, servers: &[MCPServer], status: &MCPPoolStatus) {
+        let namespace = self.namespace().unwrap_or_default();
+        let name = self.name_any();
+        for phase in [
+            Phase::Idle,
+            Phase::Requested,
+            Phase::Starting,
+            Phase::Ready,
+            Phase::Stopping,
+            Phase::Degraded,
+        ] {
+            let count = servers
+                .iter()
+                .filter(|server| server.spec.pool == name)
+                .filter(|server| server.status.clone().unwrap_or_default().phase == phase)
+                .count();
+            let phase = format!("{phase:?}");
+            METRICS
+                .servers
+                .with_label_values(&[namespace.as_str(), name.as_str(), phase.as_str()])
+                .set(count as i64);
+        }
+        METRICS
+            .pool_active_servers
+            .with_label_values(&[namespace.as_str(), name.as_str()])
+            .set(status.active_servers_count.into());
+        METRICS
+            .pool_max_active_servers
+            .with_label_values(&[namespace.as_str(), name.as_str()])
+            .set(self.spec.max_servers_active.into());
+    }
+
+    /// List all the `MCPServer` resources referencing this pool within its namespace.
+    pub async fn get_servers(&self, client: &Client) -> Result<Vec<MCPServer>> {
+        let name = self.name_any();
+        let namespace = self.namespace_or_default(client);
+        let servers = MCPServer::search_in(client, &namespace, None).await?;
+        let servers = servers
+            .into_iter()
+            .filter(|server| server.spec.pool == name)
+            .collect();
+        Ok(servers)
+    }
+
+    /// List the ephemeral `v1::Pod` resources created by the gateway for the sessions of the
+    /// servers of the pool that isolate their sessions.
+    pub async fn get_session_pods(&self, client: &Client) -> Result<Vec<v1::Pod>> {
+        let namespace = self.namespace_or_default(client);
+        let selector = format!("nmcp.nwrx.io/pool={},{MCP_SESSION_LABEL}", self.name_any());
+        let params = ListParams::default().labels(&selector);
+        let pods = Api::<v1::Pod>::namespaced(client.clone(), &namespace)
+            .list(&params)
+            .await?;
+        Ok(pods.items)
+    }
+
+    /// Check if the ephemeral `v1::Pod` of a session was orphaned, either because its process
+    /// exited or because the gateway stopped refreshing its heartbeat, typically after it was
+    /// restarted or crashed while the session was open. Pods without heartbeat fall back to
+    /// their creation time.
+    pub fn is_session_pod_orphaned(pod: &v1::Pod, now: DateTime<Utc>) -> bool {
+        if matches!(
+            PodStatus::from_pod(pod),
+            PodStatus::Succeeded | PodStatus::Failed { .. }
+        ) {
+            return true;
+        }
+        let heartbeat = pod
+            .annotations()
+            .get(MCP_SESSION_HEARTBEAT_ANNOTATION)
+            .and_then(|heartbeat| DateTime::parse_from_rfc3339(heartbeat).ok())
+            .map(|heartbeat| heartbeat.with_timezone(&Utc))
+            .or_else(|| pod.creation_timestamp().map(|time| time.0));
+        let timeout = chrono::Duration::seconds(DEFAULT_SESSION_HEARTBEAT_TIMEOUT as i64);
+        heartbeat.is_some_and(|heartbeat| now - heartbeat > timeout)
+    }
+
+    /// Rank the live `v1::Pod` resources of the sessions by creation time, then by name so
+    /// that every replica of the gateway agrees on the order. Pods being deleted or orphaned
+    /// are left out, since they no longer hold a slot in the pool.
+    pub fn rank_session_pods(pods: &[v1::Pod], now: DateTime<Utc>) -> Vec<&v1::Pod> {
+        let mut pods: Vec<_> = pods
+            .iter()
+            .filter(|pod| pod.metadata.deletion_timestamp.is_none())
+            .filter(|pod| !Self::is_session_pod_orphaned(pod, now))
+            .collect();
+        pods.sort_by(|a, b| {
+            a.metadata
+                .creation_timestamp
+                .cmp(&b.metadata.creation_timestamp)
+                .then_with(|| a.name_any().cmp(&b.name_any()))
+        });
+        pods
+    }
+
+    /// Returns the number of servers or sessions that can still be started in the pool. The
+    /// pods of the sessions are counted against the `max_servers_active` of the pool, just
+    /// like the active servers.
+    fn count_free_slots(&self, servers: &[MCPServer]) -> usize {
+        // --- The sessions are left out here, since the callers rank them by themselves.
+        let status = self.compute_status(servers, 0);
+        (self.spec.max_servers_active as usize).saturating_sub(status.active_servers_count as usize)
+    }
+
+    /// Check if the pool can run one more server or session. The counts are computed from the
+    /// live resources rather than the pool status, which may lag behind by a reconcile.
+    pub async fn has_capacity(&self, client: &Client) -> Result<bool> {
+        let servers = self.get_servers(client).await?;
+        let pods = self.get_session_pods(client).await?;
+        let sessions = Self::rank_session_pods(&pods, Utc::now()).len();
+        Ok(sessions < self.count_free_slots(&servers))
+    }
+
+    /// Check if the `v1::Pod` of a session, once created, fits within the capacity of the pool.
+    /// Concurrent sessions may all have passed the capacity check before creating their pod,
+    /// in which case only the oldest pods are admitted and the others must back off.
+    pub async fn is_session_admitted(&self, client: &Client, name: &str) -> Result<bool> {
+        let servers = self.get_servers(client).await?;
+        let pods = self.get_session_pods(client).await?;
+        let is_admitted = Self::rank_session_pods(&pods, Utc::now())
+            .into_iter()
+            .take(self.count_free_slots(&servers))
+            .any(|pod| pod.name_any() == name);
+        Ok(is_admitted)
+    }
+
+    /// Delete the orphaned `v1::Pod` resources of the sessions of the pool, so they neither
+    /// keep running nor hold a slot of the pool once the gateway lost track of them.
+    pub async fn collect_session_pods(&self, client: &Client) -> Result<()> {
+        let namespace = self.namespace_or_default(client);
+        let api = Api::<v1::Pod>::namespaced(client.clone(), &namespace);
+        let now = Utc::now();
+        for pod in self.get_session_pods(client).await? {
+            if pod.metadata.deletion_timestamp.is_some()
+                || !Self::is_session_pod_orphaned(&pod, now)
+            {
+                continue;
+            }
+            match api.delete(&pod.name_any(), &DeleteParams::default()).await {
+                Ok(..) => tracing::info!("Deleted orphaned session pod {}", pod.name_any()),
+                Err(kube::Error::Api(error)) if error.code == 404 => {}
+                Err(error) => return Err(Error::from(error)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Collect the orphaned pods of the sessions, then recompute the status of the pool from
+    /// the phases of its servers and its live session pods, and patch it if it changed since
+    /// the last reconciliation.
+    pub async fn reconcile_pool(&self, client: &Client) -> Result<()> {
+        self.collect_session_pods(client).await?;
+        let servers = self.get_servers(client).await?;
+        let pods = self.get_session_pods(client).await?;
+        let sessions = Self::rank_session_pods(&pods, Utc::now()).len();
+        let status = self.compute_status(&servers, sessions);
+        self.record_metrics(&servers, &status);
+        if self.get_status(client).await? != status {
+            let _ = self.patch_status(client, status).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MCPPoolSpec, MCPServerSpec, MCPServerStatus};
+    use chrono::{TimeZone, Utc};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+
+    fn server(name: &str, pool: &str, phase: Phase) -> MCPServer {
+        let mut server = MCPServer::new(
+            name,
+            MCPServerSpec {
+                pool: pool.to_string(),
+                ..Default::default()
+            },
+        );
+        server.status = Some(MCPServerStatus {
+            phase,
+            ..Default::default()
+        });
+        server
+    }
+
+    fn server_created_at(name: &str, minutes: i64, phase: Phase) -> MCPServer {
+        let mut server = server(name, "default", phase);
+        let created_at = Utc.with_ymd_and_hms(2025, 5, 1, 10, 0, 0).unwrap();
+        let created_at = created_at + chrono::Duration::minutes(minutes);
+        server.metadata.creation_timestamp = Some(Time(created_at));
+        server
+    }
+
+    fn pool_with_limit(limit: u32) -> MCPPool {
+        MCPPool::new(
+            "default",
+            MCPPoolSpec {
+                max_servers_limit: limit,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_compute_status_empty() {
+        let pool = MCPPool::new("default", MCPPoolSpec::default());
+        let status = pool.compute_status(&[], 0);
+        assert_eq!(status, MCPPoolStatus::default());
+    }
+
+    #[test]
+    fn test_compute_status_counts_phases() {
+        let pool = MCPPool::new("default", MCPPoolSpec::default());
+        let servers = vec![
+            server("a", "default", Phase::Ready),
+            server("b", "default", Phase::Starting),
+            server("c", "default", Phase::Stopping),
+            server("d", "default", Phase::Requested),
+            server("e", "default", Phase::Idle),
+            server("f", "default", Phase::Degraded),
+        ];
+
+        let status = pool.compute_status(&servers, 0);
+        assert_eq!(status.active_servers_count, 3);
+        assert_eq!(status.pending_servers_count, 1);
+        assert_eq!(status.managed_servers_count, 6);
+        assert_eq!(status.unmanaged_servers_count, 0);
+        assert_eq!(status.total_servers_count, 6);
+    }
+
+    #[test]
+    fn test_compute_status_ignores_other_pools() {
+        let pool = MCPPool::new("default", MCPPoolSpec::default());
+        let servers = vec![
+            server("a", "default", Phase::Ready),
+            server("b", "other", Phase::Ready),
+            server("c", "other", Phase::Requested),
+        ];
+
+        let status = pool.compute_status(&servers, 0);
+        assert_eq!(status.active_servers_count, 1);
+        assert_eq!(status.pending_servers_count, 0);
+        assert_eq!(status.total_servers_count, 1);
+    }
+
+    #[test]
+    fn test_rank_servers_by_creation_time() {
+        let pool = pool_with_limit(10);
+        let servers = vec![
+            server_created_at("c", 2, Phase::Idle),
+            server_created_at("a", 0, Phase::Idle),
+            server_created_at("b", 1, Phase::Idle),
+        ];
+
+        let ranked = pool.rank_servers(&servers);
+        let names: Vec<_> = ranked.iter().map(|server| server.name_any()).collect();
+        assert_eq!(names, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_is_server_admitted() {
+        let pool = pool_with_limit(2);
+        let a = server_created_at("a", 0, Phase::Idle);
+        let b = server_created_at("b", 1, Phase::Idle);
+        let c = server_created_at("c", 2, Phase::Idle);
+        let servers = vec![c.clone(), a.clone(), b.clone()];
+
+        assert!(pool.is_server_admitted(&a, &servers));
+        assert!(pool.is_server_admitted(&b, &servers));
+        assert!(!pool.is_server_admitted(&c, &servers));
+    }
+
+    fn session_pod(name: &str, minutes: i64, heartbeat: Option<i64>) -> v1::Pod {
+        let created_at = Utc.with_ymd_and_hms(2025, 5, 1, 10, 0, 0).unwrap();
+        let mut pod = v1::Pod::default();
+        pod.metadata.name = Some(name.to_string());
+        pod.metadata.creation_timestamp =
+            Some(Time(created_at + chrono::Duration::minutes(minutes)));
+        if let Some(heartbeat) = heartbeat {
+            let heartbeat = created_at + chrono::Duration::minutes(heartbeat);
+            let _ = pod.annotations_mut().insert(
+                MCP_SESSION_HEARTBEAT_ANNOTATION.to_string(),
+                heartbeat.to_rfc3339(),
+            );
+        }
+        pod
+    }
+
+    #[test]
+    fn test_session_pod_orphaned_without_heartbeat() {
+        let now = Utc.with_ymd_and_hms(2025, 5, 1, 10, 10, 0).unwrap();
+        assert!(!MCPPool::is_session_pod_orphaned(
+            &session_pod("a", 9, None),
+            now
+        ));
+        assert!(MCPPool::is_session_pod_orphaned(
+            &session_pod("b", 0, None),
+            now
+        ));
+        assert!(!MCPPool::is_session_pod_orphaned(
+            &session_pod("c", 0, Some(9)),
+            now
+        ));
+        assert!(MCPPool::is_session_pod_orphaned(
+            &session_pod("d", 0, Some(5)),
+            now
+        ));
+    }
+
+    #[test]
+    fn test_session_pod_orphaned_when_terminated() {
+        let now = Utc.with_ymd_and_hms(2025, 5, 1, 10, 0, 0).unwrap();
+        let mut pod = session_pod("a", 0, Some(0));
+        pod.status = Some(v1::PodStatus {
+            phase: Some("Succeeded".to_string()),
+            ..Default::default()
+        });
+        assert!(MCPPool::is_session_pod_orphaned(&pod, now));
+    }
+
+    #[test]
+    fn test_rank_session_pods() {
+        let now = Utc.with_ymd_and_hms(2025, 5, 1, 10, 10, 0).unwrap();
+        let pods = vec![
+            session_pod("c", 9, None),
+            session_pod("b", 8, None),
+            session_pod("a", 9, None),
+            session_pod("orphan", 0, None),
+        ];
+        let ranked = MCPPool::rank_session_pods(&pods, now);
+        let names: Vec<_> = ranked.iter().map(|pod| pod.name_any()).collect();
+        assert_eq!(names, vec!["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_compute_status_counts_unmanaged() {
+        let pool = pool_with_limit(2);
+        let servers = vec![
+            server_created_at("a", 0, Phase::Ready),
+            server_created_at("b", 1, Phase::Requested),
+            server_created_at("c", 2, Phase::Requested),
+            server_created_at("d", 3, Phase::Idle),
+        ];
+
+        let status = pool.compute_status(&servers, 0);
+        assert_eq!(status.active_servers_count, 1);
+        assert_eq!(status.pending_servers_count, 1);
+        assert_eq!(status.managed_servers_count, 2);
+        assert_eq!(status.unmanaged_servers_count, 2);
+        assert_eq!(status.total_servers_count, 4);
+    }
+
+    #[test]
+    fn test_compute_status_counts_sessions_as_active() {
+        let pool = pool_with_limit(10);
+        let servers = vec![
+            server_created_at("a", 0, Phase::Ready),
+            server_created_at("b", 1, Phase::Requested),
+        ];
+
+        let status = pool.compute_status(&servers, 2);
+        assert_eq!(status.active_servers_count, 3);
+        assert_eq!(status.pending_servers_count, 1);
+        assert_eq!(status.managed_servers_count, 2);
+        assert_eq!(status.total_servers_count, 2);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::MCPPoolStatus;
+use crate::{MCPAccessRule, MCPPoolStatus};
 use k8s_openapi::api::core::v1;
 use kube::CustomResource;
 use schemars::JsonSchema;
@@ -15,18 +15,17 @@ use serde::{Deserialize, Serialize};
     shortname = "mcpp",
     namespaced,
     status = "MCPPoolStatus",
-    printcolumn = r#"{"name":"In Use", "type":"integer", "jsonPath":".status.serverInUse"}"#,
-    printcolumn = r#"{"name":"Waiting", "type":"integer", "jsonPath":".status.serverWaiting"}"#,
+    printcolumn = r#"{"name":"Active", "type":"integer", "jsonPath":".status.activeServersCount"}"#,
+    printcolumn = r#"{"name":"Pending", "type":"integer", "jsonPath":".status.pendingServersCount"}"#,
+    printcolumn = r#"{"name":"Total", "type":"integer", "jsonPath":".status.totalServersCount"}"#,
     printcolumn = r#"{"name":"Age", "type":"date", "jsonPath":".metadata.creationTimestamp"}"#
 )]
 #[serde(rename_all = "camelCase")]
 pub struct MCPPoolSpec {
-    /// Maximum amount of `MCPServer` resources that can be managed by this `MCPPool`. After
-    /// this limit is reached, the overflow servers will be marked as "ignored" and no Pod
-    /// or Service resources will be created for them until older `MCPServer` resources are
-    /// deleted.
-    ///
-    /// TODO: Deprecated in favor of `maxActiveServers`.
+    /// Maximum amount of `MCPServer` resources that can be managed by this `MCPPool`. Servers
+    /// are ranked by creation time, after this limit is reached the overflow servers will be
+    /// marked as "unmanaged" with a `PoolAdmitted=False` condition and no Pod or Service
+    /// resources will be created for them until older `MCPServer` resources are deleted.
     #[serde(default = "default_max_servers")]
     pub max_servers_limit: u32,
 
@@ -50,6 +49,11 @@ pub struct MCPPoolSpec {
     /// too many servers at once.
     #[serde(default)]
     pub default_resources: v1::ResourceRequirements,
+
+    /// The rules granting the clients of the gateway access to the servers of the pool,
+    /// unless a server defines its own `access` rules.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub access: Vec<MCPAccessRule>,
 }
 
 /// Default maximum servers
@@ -69,6 +73,7 @@ impl Default for MCPPoolSpec {
             max_servers_active: default_max_servers(),
             default_idle_timeout: default_idle_timeout(),
             default_resources: v1::ResourceRequirements::default(),
+            access: vec![],
         }
     }
 }
@@ -88,6 +93,22 @@ mod tests {
         assert_eq!(crd.spec.versions.first().unwrap().name, "v1");
     }
 
+    #[test]
+    fn test_mcp_pool_crd_access() {
+        let crd = MCPPool::crd();
+        let properties = crd
+            .spec
+            .versions
+            .first()
+            .and_then(|version| version.schema.as_ref())
+            .and_then(|schema| schema.open_api_v3_schema.as_ref())
+            .and_then(|schema| schema.properties.as_ref())
+            .and_then(|properties| properties.get("spec"))
+            .and_then(|spec| spec.properties.as_ref())
+            .unwrap();
+        assert!(properties.contains_key("access"));
+    }
+
     #[test]
     fn test_mcp_pool_spec_defaults() {
         let spec = MCPPoolSpec::default();
@@ -171,6 +192,7 @@ mod tests {
                     claims: None,
                 },
                 default_idle_timeout: 120,
+                access: vec![],
             },
             status: None,
         };
@@ -2,11 +2,12 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Status of the `MCPPool` custom resource
-#[derive(Debug, Clone, Copy, Deserialize, Serialize, JsonSchema, Default)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, JsonSchema, Default, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct MCPPoolStatus {
     /// Number of servers currently in use (active) in the pool. Meaning
-    /// that the server is running and has a pod and service created.
+    /// that the server is running and has a pod and service created. The
+    /// pods of the sessions of servers isolating their sessions are counted too.
     pub active_servers_count: u32,
 
     /// Number of servers waiting to be created in the pool. Meaning
@@ -49,6 +49,43 @@ impl Display for MCPServerPodScheduledState {
     }
 }
 
+/// The various states of a `ServiceCreated` reasons.
+#[derive(Debug, Clone)]
+pub enum MCPServerServiceCreatedState {
+    /// The service could not be created
+    Failed(Error),
+    /// The service has been created
+    Created,
+    /// The service has been deleted
+    Deleted,
+}
+
+impl Display for MCPServerServiceCreatedState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Failed(_) => write!(f, "Failed"),
+            Self::Created => write!(f, "Created"),
+            Self::Deleted => write!(f, "Deleted"),
+        }
+    }
+}
+
+/// The various states of a `PoolAdmitted` reasons.
+#[derive(Debug, Copy, Clone)]
+pub enum MCPServerPoolAdmittedState {
+    /// The server is within the `maxServersLimit` of its pool and is managed by the operator.
+    Admitted,
+    /// The server overflows the `maxServersLimit` of its pool and will not be started
+    /// until older servers of the pool are deleted.
+    LimitReached,
+}
+
+impl Display for MCPServerPoolAdmittedState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
 /// `MCPServerConditionType` follows Kubernetes condition pattern
 /// Each condition has a type that represents a specific aspect of the resource's state
 #[derive(Debug, Clone)]
@@ -58,7 +95,9 @@ pub enum MCPServerCondition {
     /// Pod resource has been created
     PodScheduled(MCPServerPodScheduledState),
     /// Service resource has been created
-    ServiceCreated(Option<Error>),
+    ServiceCreated(MCPServerServiceCreatedState),
+    /// The server has been admitted by its pool
+    PoolAdmitted(MCPServerPoolAdmittedState),
 }
 
 impl Display for MCPServerCondition {
@@ -67,6 +106,7 @@ impl Display for MCPServerCondition {
             Self::Requested(_) => write!(f, "Requested"),
             Self::PodScheduled(_) => write!(f, "PodScheduled"),
             Self::ServiceCreated(_) => write!(f, "ServiceCreated"),
+            Self::PoolAdmitted(_) => write!(f, "PoolAdmitted"),
         }
     }
 }
@@ -114,16 +154,41 @@ impl From<MCPServerCondition> for v1::Condition {
                     MCPServerPodScheduledState::Running => "Pod is running".to_string(),
                 },
             },
-            MCPServerCondition::ServiceCreated(error) => Self {
+            MCPServerCondition::ServiceCreated(state) => Self {
                 type_: condition.to_string(),
-                reason: condition.to_string(),
+                reason: state.to_string(),
                 observed_generation: None,
                 last_transition_time: v1::Time(Utc::now()),
-                status: if error.is_some() { "False" } else { "True" }.to_string(),
-                message: error
-                    .clone()
-                    .map(|e| e.to_string())
-                    .unwrap_or_else(|| "Service has been created".to_string()),
+                status: match state {
+                    MCPServerServiceCreatedState::Failed(_) => "False",
+                    MCPServerServiceCreatedState::Deleted => "False",
+                    MCPServerServiceCreatedState::Created => "True",
+                }
+                .to_owned(),
+                message: match state {
+                    MCPServerServiceCreatedState::Failed(error) => error.to_string(),
+                    MCPServerServiceCreatedState::Created => "Service has been created".to_string(),
+                    MCPServerServiceCreatedState::Deleted => "Service has been deleted".to_string(),
+                },
+            },
+            MCPServerCondition::PoolAdmitted(state) => Self {
+                type_: condition.to_string(),
+                reason: state.to_string(),
+                observed_generation: None,
+                last_transition_time: v1::Time(Utc::now()),
+                status: match state {
+                    MCPServerPoolAdmittedState::Admitted => "True",
+                    MCPServerPoolAdmittedState::LimitReached => "False",
+                }
+                .to_owned(),
+                message: match state {
+                    MCPServerPoolAdmittedState::Admitted => {
+                        "Server is managed by its pool".to_string()
+                    }
+                    MCPServerPoolAdmittedState::LimitReached => {
+                        "Server overflows the maxServersLimit of its pool".to_string()
+                    }
+                },
             },
         }
     }
@@ -1,22 +1,38 @@
 use super::{
-    IntoResource, MCPPool, MCPServer, MCPServerCondition as Condition, MCPServerPhase as Phase,
-    MCPServerPodScheduledState as PodScheduledState, MCPServerRequestedState as RequestedState,
+    IntoResource, MCPAccessOperation as Operation, MCPAccessRule, MCPPool, MCPServer,
+    MCPServerCondition as Condition, MCPServerCounters as Counters,
+    MCPServerIsolation as Isolation, MCPServerPhase as Phase,
+    MCPServerPodScheduledState as PodScheduledState, MCPServerPoolAdmittedState as AdmittedState,
+    MCPServerRequestedState as RequestedState, MCPServerServiceCreatedState as ServiceState,
     ResourceManager,
 };
-use crate::{Error, ErrorInner, Result, MCP_SERVER_CONTAINER_NAME};
+use crate::{
+    Error, ErrorInner, Principal, Result, DEFAULT_SESSION_HEARTBEAT_INTERVAL,
+    MCP_SERVER_CONTAINER_NAME, MCP_SESSION_HEARTBEAT_ANNOTATION, METRICS, NMCP_OPERATOR,
+};
 use axum::http::StatusCode;
 use chrono::Utc;
-use futures::AsyncBufRead;
+use futures::{AsyncBufRead, StreamExt};
 use k8s_openapi::api::core::v1;
 use k8s_openapi::apimachinery::pkg::apis::meta;
 use kube::api::LogParams;
 use kube::api::ObjectMeta;
-use kube::{Api, Client};
+use kube::api::{Patch, PatchParams, PostParams};
+use kube::runtime::events::{Event, EventType, Recorder};
+use kube::runtime::{watcher, WatchStreamExt};
+use kube::{Client, Resource, ResourceExt};
 use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Number of attempts to update the counters of a status before giving up on conflicts.
+const MAX_STATUS_CONFLICT_RETRIES: usize = 5;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PodStatus {
     Running,
+    /// The pod is running, but the server container does not pass its readiness probe yet.
+    Unready,
     Pending,
     Succeeded,
     Failed {
@@ -27,6 +43,35 @@ pub enum PodStatus {
     NotFound,
 }
 
+impl PodStatus {
+    /// Returns the status of a pod from its phase and the readiness of the server container.
+    pub fn from_pod(pod: &v1::Pod) -> Self {
+        let status = pod.status.clone().unwrap_or_default();
+        let phase = status.phase.unwrap_or_default();
+        match phase.as_str() {
+            "Running" => {
+                let is_ready = status
+                    .container_statuses
+                    .unwrap_or_default()
+                    .iter()
+                    .find(|container| container.name == MCP_SERVER_CONTAINER_NAME)
+                    .is_some_and(|container| container.ready);
+                match is_ready {
+                    true => Self::Running,
+                    false => Self::Unready,
+                }
+            }
+            "Pending" => Self::Pending,
+            "Succeeded" => Self::Succeeded,
+            "Failed" => Self::Failed {
+                message: status.message,
+                reason: status.reason,
+            },
+            _ => Self::Unknown,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum ContainerStatus {
     Waiting(v1::ContainerStateWaiting),
@@ -96,38 +141,101 @@ impl MCPServer {
         let mut status = self.get_status(client).await?;
         if status.phase != phase {
             status.phase = phase;
-            let _ = self.patch_status(client, status).await?;
+            let _ = self.patch_status(client, status.clone()).await?;
+
+            // --- Record the time it took the server to start since it was requested.
+            if let (Phase::Ready, Some(requested_at)) = (phase, status.requested_at) {
+                let namespace = self.namespace().unwrap_or_default();
+                let elapsed = Utc::now().signed_duration_since(requested_at);
+                METRICS
+                    .cold_start
+                    .with_label_values(&[namespace.as_str(), self.spec.pool.as_str()])
+                    .observe(elapsed.num_milliseconds() as f64 / 1000.0);
+            }
+
+            // --- Report the transition as an event, with the reason of the failure if any.
+            let (type_, reason) = match phase {
+                Phase::Idle => (EventType::Normal, "Terminated"),
+                Phase::Requested => (EventType::Normal, "Requested"),
+                Phase::Starting => (EventType::Normal, "Scheduled"),
+                Phase::Ready => (EventType::Normal, "Ready"),
+                Phase::Stopping => (EventType::Normal, "Stopping"),
+                Phase::Degraded => (EventType::Warning, "Degraded"),
+            };
+            let note = match phase {
+                Phase::Degraded => status
+                    .get_condition("PodScheduled")
+                    .map(|condition| condition.message.clone()),
+                _ => None,
+            };
+            self.publish_event(client, type_, reason, note).await;
         }
         Ok(())
     }
 
+    /// Publish a `core/v1` event about the server so its lifecycle shows up in `kubectl describe`
+    /// and `kubectl get events`. Failing to publish an event is logged but otherwise ignored.
+    pub async fn publish_event(
+        &self,
+        client: &Client,
+        type_: EventType,
+        reason: &str,
+        note: Option<String>,
+    ) {
+        let recorder = Recorder::new(client.clone(), NMCP_OPERATOR.into());
+        let event = Event {
+            type_,
+            reason: reason.to_string(),
+            note,
+            action: "Reconcile".to_string(),
+            secondary: None,
+        };
+        if let Err(error) = recorder.publish(&event, &self.object_ref(&())).await {
+            let _ = Error::from(error).trace();
+        }
+    }
+
     /***********************************************************************/
     /* Notifications                                                       */
     /***********************************************************************/
 
-    /// Register that an `MCPServer` resource has been requested.
-    pub async fn notify_request(&self, client: &Client) -> Result<()> {
-        let mut status = self.get_status(client).await?;
-        status.last_request_at = Some(Utc::now());
-        status.total_requests += 1;
-        let _ = self.patch_status(client, status).await?;
-        Ok(())
-    }
-
-    /// Register that an active connection has been established.
-    pub async fn notify_connect(&self, client: &Client) -> Result<()> {
-        let mut status = self.get_status(client).await?;
-        status.current_connections += 1;
-        let _ = self.patch_status(client, status).await?;
-        Ok(())
-    }
+    /// Apply the counters accumulated by the gateway to the `MCPServer` status. The status is
+    /// patched with the `resourceVersion` it was read with, so concurrent updates are never
+    /// lost: on conflict, the status is read again and the counters re-applied.
+    pub async fn apply_counters(&self, client: &Client, counters: &Counters) -> Result<()> {
+        if counters.is_empty() {
+            return Ok(());
+        }
+        let api = self.api(client);
+        let name = self.name_any();
+        for _ in 0..MAX_STATUS_CONFLICT_RETRIES {
+            let server = api.get_status(&name).await?;
+            let mut status = server.status.clone().unwrap_or_default();
+            counters.apply(&mut status);
 
-    /// Register that an active connection has been closed.
-    pub async fn notify_disconnect(&self, client: &Client) -> Result<()> {
-        let mut status = self.get_status(client).await?;
-        status.current_connections -= 1;
-        let _ = self.patch_status(client, status).await?;
-        Ok(())
+            // --- Only patch the counters, guarded by the `resourceVersion` of the read.
+            let patch = serde_json::json!({
+                "metadata": { "resourceVersion": server.resource_version() },
+                "status": {
+                    "totalRequests": status.total_requests,
+                    "currentConnections": status.current_connections,
+                    "lastRequestAt": status.last_request_at,
+                }
+            });
+            match api
+                .patch_status(&name, &PatchParams::default(), &Patch::Merge(&patch))
+                .await
+            {
+                Ok(_) => return Ok(()),
+                Err(kube::Error::Api(error)) if error.code == 409 => {}
+                Err(error) => return Err(Error::from(error)),
+            }
+        }
+        Err(Error::generic(format!(
+            "Could not update the status of MCPServer '{name}' after {MAX_STATUS_CONFLICT_RETRIES} conflicting attempts"
+        ))
+        .with_name("E_STATUS_CONFLICT")
+        .with_status(StatusCode::CONFLICT))
     }
 
     /// Register the datetime when the server was started.
@@ -163,21 +271,7 @@ impl MCPServer {
     /// Get the pod status for the given `MCPServer`.
     pub async fn get_pod_status(&self, client: &Client) -> Result<PodStatus> {
         match <Self as IntoResource<v1::Pod>>::get_resource(self, client).await {
-            Ok(pod) => {
-                let status = pod.status.unwrap_or_default();
-                let phase = status.phase.unwrap_or_default();
-                match phase.as_str() {
-                    "Running" => Ok(PodStatus::Running),
-                    "Pending" => Ok(PodStatus::Pending),
-                    "Succeeded" => Ok(PodStatus::Succeeded),
-                    "Failed" => {
-                        let message = status.message.clone();
-                        let reason = status.reason.clone();
-                        Ok(PodStatus::Failed { message, reason })
-                    }
-                    _ => Ok(PodStatus::Unknown),
-                }
-            }
+            Ok(pod) => Ok(PodStatus::from_pod(&pod)),
             Err(error) => match error.source() {
                 ErrorInner::KubeError(kube::Error::Api(error)) if error.code == 404 => {
                     Ok(PodStatus::NotFound)
@@ -229,8 +323,9 @@ impl MCPServer {
     pub async fn ensure_pod_is_scheduled(&self, client: &Client) -> Result<()> {
         if self.get_pod_status(client).await? == PodStatus::NotFound {
             tracing::info!("Pod not found, creating it for server");
+            let pool = self.get_pool(client).await?;
             self.notify_started(client).await?;
-            let _ = <Self as IntoResource<v1::Pod>>::patch_resource(self, client).await?;
+            let _ = <Self as IntoResource<v1::Pod>>::patch_resource(self, client, &pool).await?;
         }
         Ok(())
     }
@@ -243,27 +338,317 @@ impl MCPServer {
         Ok(())
     }
 
+    /***********************************************************************/
+    /* Sessions                                                            */
+    /***********************************************************************/
+
+    /// Create the ephemeral `Pod` of a session and wait until it is ready, for servers isolating
+    /// their sessions. The pod is counted against the `max_servers_active` of the pool, and the
+    /// session is rejected if the pool has no capacity left.
+    pub async fn create_session_pod(
+        &self,
+        client: &Client,
+        session_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<v1::Pod> {
+        let pool = self.get_pool(client).await?;
+        if !pool.has_capacity(client).await? {
+            return Err(Self::pool_exhausted(&pool));
+        }
+
+        // --- Create the pod with a fresh heartbeat, so the operator does not collect it
+        // --- before the gateway starts refreshing it.
+        let params = PostParams {
+            field_manager: Some(NMCP_OPERATOR.to_string()),
+            ..Default::default()
+        };
+        let mut pod = self.session_pod(&pool, session_id);
+        let _ = pod.annotations_mut().insert(
+            MCP_SESSION_HEARTBEAT_ANNOTATION.to_string(),
+            Utc::now().to_rfc3339(),
+        );
+        let pod = <Self as IntoResource<v1::Pod>>::resource_api(self, client)
+            .create(&params, &pod)
+            .await?;
+        tracing::info!("Created pod for session {}", session_id);
+
+        // --- Another session may have been created concurrently for the last slot of the
+        // --- pool, in which case only the oldest pods are admitted and this one backs off.
+        if !pool.is_session_admitted(client, &pod.name_any()).await? {
+            self.delete_session_pod(client, session_id).await?;
+            return Err(Self::pool_exhausted(&pool));
+        }
+
+        // --- Wait for the pod while refreshing its heartbeat, since pulling the image may
+        // --- take longer than the heartbeat timeout. Delete it if it never becomes ready.
+        let heartbeat = self.spawn_session_heartbeat(client, session_id);
+        let result = self
+            .wait_until_session_ready(client, session_id, timeout)
+            .await;
+        heartbeat.abort();
+        match result {
+            Ok(pod) => Ok(pod),
+            Err(error) => {
+                self.delete_session_pod(client, session_id).await?;
+                Err(error)
+            }
+        }
+    }
+
+    /// Returns the error rejecting a session when the pool has no capacity left.
+    fn pool_exhausted(pool: &MCPPool) -> Error {
+        Error::generic(format!(
+            "Pool '{}' has reached its maximum number of active servers",
+            pool.name_any()
+        ))
+        .with_name("E_POOL_EXHAUSTED")
+        .with_status(StatusCode::SERVICE_UNAVAILABLE)
+    }
+
+    /// Refresh the heartbeat of the `Pod` of a session, telling the operator that the session
+    /// is still open so that its pod is not collected as orphaned.
+    pub async fn touch_session_pod(&self, client: &Client, session_id: &str) -> Result<()> {
+        let name = self.session_pod_name(session_id);
+        let patch = serde_json::json!({
+            "metadata": {
+                "annotations": {
+                    MCP_SESSION_HEARTBEAT_ANNOTATION: Utc::now().to_rfc3339()
+                }
+            }
+        });
+        let _ = <Self as IntoResource<v1::Pod>>::resource_api(self, client)
+            .patch(&name, &PatchParams::default(), &Patch::Merge(&patch))
+            .await?;
+        Ok(())
+    }
+
+    /// Spawn a task refreshing the heartbeat of the `Pod` of a session periodically, until it
+    /// is aborted.
+    fn spawn_session_heartbeat(&self, client: &Client, session_id: &str) -> JoinHandle<()> {
+        let server = self.clone();
+        let client = client.clone();
+        let session_id = session_id.to_string();
+        let period = Duration::from_secs(DEFAULT_SESSION_HEARTBEAT_INTERVAL);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval_at(Instant::now() + period, period);
+            loop {
+                let _ = interval.tick().await;
+                if let Err(error) = server.touch_session_pod(&client, &session_id).await {
+                    let _ = error.trace();
+                }
+            }
+        })
+    }
+
+    /// Return a `Future` that will finish once the `Pod` of a session is ready, or fail if the
+    /// pod terminates before.
+    async fn wait_until_session_ready(
+        &self,
+        client: &Client,
+        session_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<v1::Pod> {
+        let name = self.session_pod_name(session_id);
+        let api = <Self as IntoResource<v1::Pod>>::resource_api(self, client);
+        let config = watcher::Config::default().fields(&format!("metadata.name={name}"));
+        let stream = watcher(api, config).default_backoff();
+
+        let wait = async {
+            let mut stream = std::pin::pin!(stream);
+            while let Some(event) = stream.next().await {
+                let pod = match event {
+                    Ok(watcher::Event::Apply(pod) | watcher::Event::InitApply(pod)) => pod,
+                    Ok(watcher::Event::Delete(..)) => {
+                        return Err(
+                            Error::generic("Session pod was deleted before it was ready")
+                                .with_name("E_SESSION_FAILED")
+                                .with_status(StatusCode::SERVICE_UNAVAILABLE),
+                        );
+                    }
+                    Ok(..) => continue,
+                    Err(error) => {
+                        let _ = Error::from(error).trace();
+                        continue;
+                    }
+                };
+                match PodStatus::from_pod(&pod) {
+                    PodStatus::Running => return Ok(pod),
+                    PodStatus::Failed { message, .. } => {
+                        let message = message.unwrap_or_else(|| "Session pod failed".to_string());
+                        return Err(Error::generic(message)
+                            .with_name("E_SESSION_FAILED")
+                            .with_status(StatusCode::SERVICE_UNAVAILABLE));
+                    }
+                    PodStatus::Succeeded => {
+                        return Err(Error::generic("Session pod exited before it was ready")
+                            .with_name("E_SESSION_FAILED")
+                            .with_status(StatusCode::SERVICE_UNAVAILABLE));
+                    }
+                    _ => {}
+                }
+            }
+            Err(
+                Error::generic("Session pod watch ended before it became ready")
+                    .with_name("E_SESSION_NOT_READY")
+                    .with_status(StatusCode::SERVICE_UNAVAILABLE),
+            )
+        };
+
+        // --- Check for timeout.
+        match timeout {
+            None => wait.await,
+            Some(timeout) => match tokio::time::timeout(timeout, wait).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::generic("Session pod did not become ready in time")
+                    .with_name("E_SESSION_NOT_READY")
+                    .with_status(StatusCode::REQUEST_TIMEOUT)),
+            },
+        }
+    }
+
+    /// Delete the ephemeral `Pod` of a session, once the session is closed or idled out.
+    pub async fn delete_session_pod(&self, client: &Client, session_id: &str) -> Result<()> {
+        let name = self.session_pod_name(session_id);
+        match <Self as IntoResource<v1::Pod>>::resource_api(self, client)
+            .delete(&name, &Default::default())
+            .await
+        {
+            Ok(..) => {
+                tracing::info!("Deleted pod for session {}", session_id);
+                Ok(())
+            }
+            Err(kube::Error::Api(error)) if error.code == 404 => Ok(()),
+            Err(error) => Err(Error::from(error)),
+        }
+    }
+
+    /***********************************************************************/
+    /* Pool                                                                */
+    /***********************************************************************/
+
+    /// Get the `MCPPool` this server belongs to, which lives in the same namespace.
+    pub async fn get_pool(&self, client: &Client) -> Result<MCPPool> {
+        let namespace = self.namespace_or_default(client);
+        MCPPool::get_by_name_in(client, &namespace, &self.spec.pool).await
+    }
+
+    /// Ensure the principal is granted the operation on the server, by the `access` rules of
+    /// the server or, if it defines none, by the ones of its pool. A pool that does not exist
+    /// defines no rules.
+    pub fn authorize(
+        &self,
+        pool: Option<&MCPPool>,
+        principal: &Principal,
+        operation: Operation,
+    ) -> Result<()> {
+        let rules = match (self.spec.access.is_empty(), pool) {
+            (false, _) => self.spec.access.as_slice(),
+            (true, Some(pool)) => pool.spec.access.as_slice(),
+            (true, None) => &[],
+        };
+        if MCPAccessRule::is_allowed(rules, principal, operation) {
+            return Ok(());
+        }
+        Err(Error::generic(format!(
+            "Principal '{principal}' is not allowed to {operation} the server '{}'",
+            self.name_any()
+        ))
+        .with_name("E_FORBIDDEN")
+        .with_status(StatusCode::FORBIDDEN))
+    }
+
+    /***********************************************************************/
+    /* Service                                                             */
+    /***********************************************************************/
+
+    /// Ensure that the `Service` for the `MCPServer` exists. Servers using the `stdio`
+    /// transport are reached through the Pod itself and do not need a `Service`.
+    pub async fn ensure_service_is_created(&self, client: &Client) -> Result<()> {
+        if self.spec.transport.port().is_none() {
+            return Ok(());
+        }
+        if <Self as IntoResource<v1::Service>>::resource_exists(self, client).await? {
+            return Ok(());
+        }
+
+        // --- Create the service and report the outcome in the `ServiceCreated` condition.
+        tracing::info!("Service not found, creating it for server");
+        let pool = self.get_pool(client).await?;
+        match <Self as IntoResource<v1::Service>>::patch_resource(self, client, &pool).await {
+            Ok(_) => {
+                let condition = Condition::ServiceCreated(ServiceState::Created);
+                self.push_condition(client, condition).await
+            }
+            Err(error) => {
+                let condition = Condition::ServiceCreated(ServiceState::Failed(error.clone()));
+                self.push_condition(client, condition).await?;
+                Err(error)
+            }
+        }
+    }
+
+    /// Ensure that the `Service` for the `MCPServer` is deleted.
+    pub async fn ensure_service_is_destroyed(&self, client: &Client) -> Result<()> {
+        if <Self as IntoResource<v1::Service>>::resource_exists(self, client).await? {
+            <Self as IntoResource<v1::Service>>::delete_resource(self, client).await?;
+            let condition = Condition::ServiceCreated(ServiceState::Deleted);
+            self.push_condition(client, condition).await?;
+        }
+        Ok(())
+    }
+
     /***********************************************************************/
     /* Lifecycle                                                           */
     /***********************************************************************/
 
+    /// Check if the server is admitted by its pool, meaning it does not overflow the
+    /// `max_servers_limit` of the pool once ranked by creation time, and record the
+    /// result in the `PoolAdmitted` condition.
+    pub async fn is_admitted_by_pool(&self, client: &Client) -> Result<bool> {
+        let pool = self.get_pool(client).await?;
+        let servers = pool.get_servers(client).await?;
+        let is_admitted = pool.is_server_admitted(self, &servers);
+        let state = if is_admitted {
+            AdmittedState::Admitted
+        } else {
+            AdmittedState::LimitReached
+        };
+
+        // --- Only patch the status when the admission changes, since every status
+        // --- update triggers another reconciliation of the server.
+        let condition: meta::v1::Condition = Condition::PoolAdmitted(state).into();
+        let existing = self
+            .status
+            .as_ref()
+            .and_then(|status| status.get_condition(&condition.type_));
+        let is_unchanged = existing.is_some_and(|existing| {
+            existing.status == condition.status && existing.reason == condition.reason
+        });
+        if !is_unchanged {
+            self.push_condition(client, Condition::PoolAdmitted(state))
+                .await?;
+        }
+        Ok(is_admitted)
+    }
+
     /// Check if the pool can accept more servers based on its limits.
     pub async fn can_pool_accept_more_servers(&self, client: &Client) -> Result<bool> {
-        let pool = MCPPool::get_by_name(client, &self.spec.pool).await?;
-        let status = pool.status.unwrap_or_default();
-        Ok(status.active_servers_count < pool.spec.max_servers_active)
+        self.get_pool(client).await?.has_capacity(client).await
     }
 
     /// Check if the server was idle for too long and should be stopped.
     pub async fn is_server_stale(&self, client: &Client) -> Result<bool> {
-        let pool = MCPPool::get_by_name(client, &self.spec.pool).await?;
+        let pool = self.get_pool(client).await?;
         let status = self.get_status(client).await?;
 
-        // --- Get the last request time. If it's None, fallback to
-        // --- the `started_at` time, then to `created_at`.
+        // --- Get the last activity time, which is the latest of the last request, the time
+        // --- the server was requested and the time it was started. Since requests are only
+        // --- counted when the gateway flushes its counters, `requested_at` also covers the
+        // --- requests that woke the server up. If none is set, fallback to `created_at`.
         let last_request = status
             .last_request_at
-            .or(status.started_at)
+            .max(status.requested_at)
+            .max(status.started_at)
             .unwrap_or(status.created_at);
 
         // --- Get the idle timeout from the server spec or pool spec
@@ -282,7 +667,10 @@ impl MCPServer {
         if is_stale {
             let reason = RequestedState::IdleTimeout;
             let condition = Condition::Requested(reason);
+            let note = format!("No request received for {idle_timeout} seconds");
             self.push_condition(client, condition).await?;
+            self.publish_event(client, EventType::Normal, "IdleTimeout", Some(note))
+                .await;
             self.set_phase(client, Phase::Stopping).await?;
         }
 
@@ -310,35 +698,65 @@ impl MCPServer {
         })
     }
 
-    /// Return a `Future` that will finish once the server is in the `Ready` phase.
+    /// Return a `Future` that will finish once the server is in the `Ready` phase. The server is
+    /// watched so the future resolves as soon as its phase changes, and fails right away if the
+    /// server is stopped or becomes `Degraded`.
     pub async fn wait_until_ready(&self, client: &Client, timeout: Option<Duration>) -> Result<()> {
-        let mut interval = tokio::time::interval(Duration::from_millis(500));
-        let start_time = std::time::Instant::now();
+        let config =
+            watcher::Config::default().fields(&format!("metadata.name={}", self.name_any()));
+        let stream = watcher(self.api(client), config)
+            .default_backoff()
+            .applied_objects();
 
-        loop {
-            let status = self.get_status(client).await?;
-            match status.phase {
-                Phase::Ready => return Ok(()),
-                Phase::Requested | Phase::Starting | Phase::Degraded => {
-                    let _ = interval.tick().await;
-                }
-                Phase::Idle | Phase::Stopping => {
-                    return Err(Error::generic("Server is idle")
-                        .with_name("E_SERVER_IDLE_STALE")
-                        .with_status(StatusCode::SERVICE_UNAVAILABLE));
-                }
-            }
+        let wait = async {
+            let mut stream = std::pin::pin!(stream);
+            while let Some(event) = stream.next().await {
+                let status = match event {
+                    Ok(server) => server.status.unwrap_or_default(),
+                    Err(error) => {
+                        let _ = Error::from(error).trace();
+                        continue;
+                    }
+                };
+                match status.phase {
+                    Phase::Ready => return Ok(()),
+                    Phase::Requested | Phase::Starting => {}
+                    Phase::Idle | Phase::Stopping => {
+                        return Err(Error::generic("Server is idle")
+                            .with_name("E_SERVER_IDLE_STALE")
+                            .with_status(StatusCode::SERVICE_UNAVAILABLE));
+                    }
 
-            // --- Check for timeout.
-            if let Some(timeout) = timeout {
-                if start_time.elapsed() >= timeout {
-                    return Err(Error::generic("Server did not become ready in time")
-                        .with_name("E_SERVER_NOT_READY")
-                        .with_status(StatusCode::REQUEST_TIMEOUT));
+                    // --- Report why the pod could not be scheduled, if known.
+                    Phase::Degraded => {
+                        let message = status
+                            .get_condition("PodScheduled")
+                            .map(|condition| condition.message.clone())
+                            .filter(|message| !message.is_empty())
+                            .unwrap_or_else(|| "Server is degraded".to_string());
+                        return Err(Error::generic(message)
+                            .with_name("E_SERVER_DEGRADED")
+                            .with_status(StatusCode::SERVICE_UNAVAILABLE));
+                    }
                 }
             }
+            Err(Error::generic("Server watch ended before it became ready")
+                .with_name("E_SERVER_NOT_READY")
+                .with_status(StatusCode::SERVICE_UNAVAILABLE))
+        };
+
+        // --- Check for timeout.
+        match timeout {
+            None => wait.await,
+            Some(timeout) => match tokio::time::timeout(timeout, wait).await {
+                Ok(result) => result,
+                Err(_) => Err(Error::generic("Server did not become ready in time")
+                    .with_name("E_SERVER_NOT_READY")
+                    .with_status(StatusCode::REQUEST_TIMEOUT)),
+            },
         }
     }
+
     /***********************************************************************/
     /* Reconciliation                                                      */
     /***********************************************************************/
@@ -353,7 +771,7 @@ impl MCPServer {
                 PodStatus::NotFound => {
                     // Pod hasn't been created yet, keep waiting
                 }
-                PodStatus::Pending => {
+                PodStatus::Pending | PodStatus::Unready => {
                     let reason = PodScheduledState::Scheduled;
                     let condition = Condition::PodScheduled(reason);
                     self.push_condition(client, condition).await?;
@@ -419,7 +837,7 @@ impl MCPServer {
                     self.push_condition(client, condition).await?;
                     self.set_phase(client, Phase::Degraded).await?;
                 }
-                PodStatus::Pending => {
+                PodStatus::Pending | PodStatus::Unready => {
                     // Still starting, no action needed
                 }
             },
@@ -452,8 +870,8 @@ impl MCPServer {
                     self.push_condition(client, condition).await?;
                     self.set_phase(client, Phase::Degraded).await?;
                 }
-                PodStatus::Pending => {
-                    // Pod restarted, go back to starting
+                PodStatus::Pending | PodStatus::Unready => {
+                    // Pod restarted or stopped passing its readiness probe, go back to starting
                     self.set_phase(client, Phase::Starting).await?;
                 }
             },
@@ -472,7 +890,7 @@ impl MCPServer {
                     self.set_phase(client, Phase::Idle).await?;
                     self.clear_connected_clients(client).await?;
                 }
-                PodStatus::Running | PodStatus::Pending => {
+                PodStatus::Running | PodStatus::Unready | PodStatus::Pending => {
                     let reason = PodScheduledState::Terminating;
                     let condition = Condition::PodScheduled(reason);
                     self.push_condition(client, condition).await?;
@@ -500,7 +918,7 @@ impl MCPServer {
                     let condition = Condition::PodScheduled(reason);
                     self.push_condition(client, condition).await?;
                 }
-                PodStatus::Running | PodStatus::Pending => {
+                PodStatus::Running | PodStatus::Unready | PodStatus::Pending => {
                     // Pod shouldn't be running while idle, transition to stopping
                     self.set_phase(client, Phase::Stopping).await?;
                 }
@@ -541,7 +959,7 @@ impl MCPServer {
                     self.set_phase(client, Phase::Idle).await?;
                     self.clear_connected_clients(client).await?;
                 }
-                PodStatus::Pending => {
+                PodStatus::Pending | PodStatus::Unready => {
                     // Pod is being recreated, transition to starting
                     let reason = PodScheduledState::Scheduled;
                     let condition = Condition::PodScheduled(reason);
@@ -568,10 +986,20 @@ impl MCPServer {
 
     /// Start or stop the server based on its current status and conditions.
     pub async fn reconcile_server(&self, client: &Client) -> Result<()> {
-        if self.should_server_be_up(client).await? {
+        // --- Servers overflowing the `max_servers_limit` of their pool are left
+        // --- unmanaged and never get a Pod until older servers are deleted. Servers
+        // --- isolating their sessions never get a shared Pod either, since the gateway
+        // --- creates an ephemeral Pod for each of their sessions instead.
+        let is_isolated = self.spec.isolation == Isolation::Session;
+        if !self.is_admitted_by_pool(client).await? || is_isolated {
+            self.ensure_pod_is_terminated(client).await?;
+            self.ensure_service_is_destroyed(client).await?;
+        } else if self.should_server_be_up(client).await? {
             self.ensure_pod_is_scheduled(client).await?;
+            self.ensure_service_is_created(client).await?;
         } else if self.should_server_be_down(client).await? {
             self.ensure_pod_is_terminated(client).await?;
+            self.ensure_service_is_destroyed(client).await?;
         }
 
         // --- Reconcile the server status with the pod status.
@@ -586,7 +1014,6 @@ impl MCPServer {
 
     /// Requests the server to start.
     pub async fn request(&self, client: &Client) -> Result<()> {
-        self.notify_request(client).await?;
         match self.get_status(client).await?.phase {
             Phase::Ready | Phase::Requested | Phase::Starting => Ok(()),
             Phase::Idle | Phase::Degraded | Phase::Stopping => {
@@ -612,11 +1039,20 @@ impl MCPServer {
         }
     }
 
-    /// Return a log stream for the server pod.
-    pub async fn get_logs(&self, client: &Client) -> Result<impl AsyncBufRead> {
-        Api::<v1::Pod>::namespaced(client.clone(), client.default_namespace())
+    /// Return a log stream for the server pod, or for the ephemeral pod created with the given
+    /// ID for an isolated session.
+    pub async fn get_logs(
+        &self,
+        client: &Client,
+        pod_id: Option<&str>,
+    ) -> Result<impl AsyncBufRead> {
+        let name = match pod_id {
+            Some(pod_id) => self.session_pod_name(pod_id),
+            None => <Self as IntoResource<v1::Pod>>::resource_name(self),
+        };
+        <Self as IntoResource<v1::Pod>>::resource_api(self, client)
             .log_stream(
-                &<Self as IntoResource<v1::Pod>>::resource_name(self),
+                &name,
                 &LogParams {
                     container: Some(MCP_SERVER_CONTAINER_NAME.to_string()),
                     follow: true,
@@ -1,7 +1,85 @@
 use super::IntoResource;
-use crate::{MCPServer, MCPServerTransport, MCP_SERVER_CONTAINER_NAME};
+use crate::{MCPPool, MCPServer, MCPServerTransport, MCP_SERVER_CONTAINER_NAME, MCP_SESSION_LABEL};
 use k8s_openapi::api::core::v1;
-use kube::{api::ObjectMeta, ResourceExt};
+use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
+use kube::{api::ObjectMeta, Resource, ResourceExt};
+use std::collections::BTreeMap;
+
+impl MCPServer {
+    /// Returns the labels identifying the `v1::Pod` of the `MCPServer`. They are derived from
+    /// the server identity only, so they can be used as a stable selector by the `v1::Service`
+    /// regardless of the labels set by users on the `MCPServer` resource.
+    pub fn selector_labels(&self) -> BTreeMap<String, String> {
+        let uid = self.metadata.uid.clone().unwrap_or_default();
+        let mut labels = BTreeMap::new();
+        let _ = labels.insert("nmcp.nwrx.io/server".to_string(), self.name_any());
+        let _ = labels.insert("nmcp.nwrx.io/uid".to_string(), uid);
+        let _ = labels.insert("nmcp.nwrx.io/pool".to_string(), self.spec.pool.clone());
+        labels
+    }
+
+    /// Returns the resource requirements of the server's container. The limits, requests and
+    /// claims of the server are merged over the `default_resources` of the pool, so a server
+    /// only needs to specify the resources that differ from the pool's defaults.
+    pub fn resource_requirements(&self, pool: &MCPPool) -> v1::ResourceRequirements {
+        let mut resources = pool.spec.default_resources.clone();
+        let Some(overrides) = self.spec.resources.clone() else {
+            return resources;
+        };
+
+        // --- Merge the limits and requests key by key.
+        if let Some(limits) = overrides.limits {
+            resources.limits.get_or_insert_default().extend(limits);
+        }
+        if let Some(requests) = overrides.requests {
+            resources.requests.get_or_insert_default().extend(requests);
+        }
+
+        // --- Claims are not keyed by resource name, so they are replaced as a whole.
+        if overrides.claims.is_some() {
+            resources.claims = overrides.claims;
+        }
+        resources
+    }
+
+    /// Returns the name of the ephemeral `v1::Pod` created for a session of the `MCPServer`.
+    pub fn session_pod_name(&self, session_id: &str) -> String {
+        let name = <Self as IntoResource<v1::Pod>>::resource_name(self);
+        format!("{name}-{session_id}")
+    }
+
+    /// Returns the ephemeral `v1::Pod` running a single session of an `MCPServer` isolating
+    /// its sessions. It is derived from the pod of the server, labelled with the session, and
+    /// never restarted since the state of the session is lost with its process anyway.
+    pub fn session_pod(&self, pool: &MCPPool, session_id: &str) -> v1::Pod {
+        let mut pod = <Self as IntoResource<v1::Pod>>::resource(self, pool);
+        pod.metadata.name = Some(self.session_pod_name(session_id));
+        let _ = pod
+            .metadata
+            .labels
+            .get_or_insert_default()
+            .insert(MCP_SESSION_LABEL.to_string(), session_id.to_string());
+        if let Some(spec) = pod.spec.as_mut() {
+            spec.restart_policy = Some("Never".to_string());
+            if let Some(container) = spec.containers.first_mut() {
+                container.env.get_or_insert_default().push(v1::EnvVar {
+                    name: "MCP_SESSION_ID".to_string(),
+                    value: Some(session_id.to_string()),
+                    ..Default::default()
+                });
+            }
+        }
+        pod
+    }
+
+    /// Returns the base URL of the ephemeral `v1::Pod` of a session, if the transport of the
+    /// server is exposed over the network and the pod was assigned an IP.
+    pub fn session_url(&self, pod: &v1::Pod) -> Option<String> {
+        let port = self.spec.transport.port()?;
+        let ip = pod.status.as_ref()?.pod_ip.clone()?;
+        Some(format!("http://{ip}:{port}"))
+    }
+}
 
 impl IntoResource<v1::Pod> for MCPServer {
     /// Returns the name of the `v1::Pod` for the `MCPServer`.
@@ -14,17 +92,7 @@ impl IntoResource<v1::Pod> for MCPServer {
         )
     }
 
-    // /// Returns the labels for the `MCPServer`.
-    // pub fn labels(&self) -> BTreeMap<String, String> {
-    //     let uid = self.metadata.uid.clone().unwrap_or_default();
-    //     let mut labels = std::collections::BTreeMap::new();
-    //     labels.insert("app".to_string(), self.name_pod());
-    //     labels.insert("nmcp.nwrx.io/uid".to_string(), uid);
-    //     labels.insert("nmcp.nwrx.io/pool".to_string(), self.spec.pool.clone());
-    //     labels
-    // }
-
-    fn resource(&self) -> v1::Pod {
+    fn resource(&self, pool: &MCPPool) -> v1::Pod {
         let mut pod = v1::Pod::default();
 
         // --- Set the pod metadata.
@@ -69,18 +137,34 @@ impl IntoResource<v1::Pod> for MCPServer {
             }
         }
 
+        // --- Define a probe checking that the server accepts connections on its port. The
+        // --- `stdio` transport has no port, so only a user-supplied probe can be used.
+        let tcp_probe = self.spec.transport.port().map(|port| v1::Probe {
+            tcp_socket: Some(v1::TCPSocketAction {
+                port: IntOrString::Int(port.into()),
+                ..Default::default()
+            }),
+            period_seconds: Some(2),
+            ..Default::default()
+        });
+
         // Define startup probe to ensure the process is fully initialized
         let startup_probe = match self.spec.transport {
             MCPServerTransport::Stdio => None,
-            MCPServerTransport::Sse { .. } => None,
-            MCPServerTransport::StreamableHttp { .. } => None,
+            MCPServerTransport::Sse { .. } | MCPServerTransport::StreamableHttp { .. } => {
+                tcp_probe.clone().map(|probe| v1::Probe {
+                    failure_threshold: Some(30),
+                    ..probe
+                })
+            }
         };
 
         // Define readiness probe to ensure container is ready for connections
         let readiness_probe = match self.spec.transport {
-            MCPServerTransport::Stdio => None,
-            MCPServerTransport::Sse { .. } => None,
-            MCPServerTransport::StreamableHttp { .. } => None,
+            MCPServerTransport::Stdio => self.spec.readiness_probe.clone(),
+            MCPServerTransport::Sse { .. } | MCPServerTransport::StreamableHttp { .. } => {
+                self.spec.readiness_probe.clone().or(tcp_probe)
+            }
         };
 
         // --- Create container
@@ -95,7 +179,7 @@ impl IntoResource<v1::Pod> for MCPServer {
             tty: Some(false),
             startup_probe,
             readiness_probe,
-            // resources,
+            resources: Some(self.resource_requirements(pool)),
             // security_context,
             ..Default::default()
         };
@@ -104,7 +188,14 @@ impl IntoResource<v1::Pod> for MCPServer {
         pod.metadata = ObjectMeta {
             name: Some(<Self as IntoResource<v1::Pod>>::resource_name(self)),
             namespace: self.clone().metadata.namespace,
-            labels: Some(self.labels().clone()),
+            labels: Some(
+                self.labels()
+                    .clone()
+                    .into_iter()
+                    .chain(self.selector_labels())
+                    .collect(),
+            ),
+            owner_references: self.controller_owner_ref(&()).map(|owner| vec![owner]),
             ..Default::default()
         };
 
@@ -120,3 +211,156 @@ impl IntoResource<v1::Pod> for MCPServer {
         pod
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MCPPoolSpec, MCPServerSpec};
+    use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+
+    fn quantities(values: &[(&str, &str)]) -> BTreeMap<String, Quantity> {
+        values
+            .iter()
+            .map(|(name, value)| (name.to_string(), Quantity(value.to_string())))
+            .collect()
+    }
+
+    fn pool() -> MCPPool {
+        MCPPool::new(
+            "default",
+            MCPPoolSpec {
+                default_resources: v1::ResourceRequirements {
+                    limits: Some(quantities(&[("cpu", "500m"), ("memory", "512Mi")])),
+                    requests: Some(quantities(&[("cpu", "100m"), ("memory", "256Mi")])),
+                    claims: None,
+                },
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_resource_requirements_defaults_to_pool() {
+        let server = MCPServer::new("test", MCPServerSpec::default());
+        let resources = server.resource_requirements(&pool());
+        assert_eq!(resources, pool().spec.default_resources);
+    }
+
+    #[test]
+    fn test_resource_requirements_merges_over_pool() {
+        let server = MCPServer::new(
+            "test",
+            MCPServerSpec {
+                resources: Some(v1::ResourceRequirements {
+                    limits: Some(quantities(&[("memory", "1Gi")])),
+                    requests: None,
+                    claims: None,
+                }),
+                ..Default::default()
+            },
+        );
+
+        let resources = server.resource_requirements(&pool());
+        let limits = resources.limits.unwrap();
+        assert_eq!(limits.get("cpu").unwrap().0, "500m");
+        assert_eq!(limits.get("memory").unwrap().0, "1Gi");
+        let requests = resources.requests.unwrap();
+        assert_eq!(requests.get("cpu").unwrap().0, "100m");
+        assert_eq!(requests.get("memory").unwrap().0, "256Mi");
+    }
+
+    #[test]
+    fn test_pod_labels_include_selector() {
+        let mut server = MCPServer::new("test", MCPServerSpec::default());
+        server.metadata.uid = Some("0123456789abcdef".to_string());
+        server.metadata.labels = Some(BTreeMap::from([("app".to_string(), "x".to_string())]));
+
+        let pod = <MCPServer as IntoResource<v1::Pod>>::resource(&server, &pool());
+        let labels = pod.metadata.labels.unwrap();
+        assert_eq!(labels.get("app").unwrap(), "x");
+        for (key, value) in server.selector_labels() {
+            assert_eq!(labels.get(&key), Some(&value));
+        }
+    }
+
+    #[test]
+    fn test_pod_is_owned_by_server() {
+        let mut server = MCPServer::new("test", MCPServerSpec::default());
+        server.metadata.uid = Some("0123456789abcdef".to_string());
+
+        let pod = <MCPServer as IntoResource<v1::Pod>>::resource(&server, &pool());
+        let owners = pod.metadata.owner_references.unwrap();
+        let owner = owners.first().unwrap();
+        assert_eq!(owner.kind, "MCPServer");
+        assert_eq!(owner.name, "test");
+        assert_eq!(owner.uid, "0123456789abcdef");
+        assert_eq!(owner.controller, Some(true));
+    }
+
+    #[test]
+    fn test_session_pod() {
+        let mut server = MCPServer::new("test", MCPServerSpec::default());
+        server.metadata.uid = Some("0123456789abcdef".to_string());
+
+        let pod = server.session_pod(&pool(), "session");
+        assert_eq!(
+            pod.metadata.name,
+            Some("mcp-pod-default-test-01234567-session".to_string())
+        );
+        let labels = pod.metadata.labels.unwrap();
+        assert_eq!(labels.get(MCP_SESSION_LABEL).unwrap(), "session");
+        let spec = pod.spec.unwrap();
+        assert_eq!(spec.restart_policy, Some("Never".to_string()));
+        let container = spec.containers.first().unwrap();
+        let env = container.env.clone().unwrap();
+        assert!(env
+            .iter()
+            .any(|var| var.name == "MCP_SESSION_ID" && var.value.as_deref() == Some("session")));
+    }
+
+    #[test]
+    fn test_pod_probes_for_networked_transport() {
+        let mut server = MCPServer::new(
+            "test",
+            MCPServerSpec {
+                transport: MCPServerTransport::StreamableHttp { port: 8080 },
+                ..Default::default()
+            },
+        );
+        server.metadata.uid = Some("0123456789abcdef".to_string());
+
+        let pod = <MCPServer as IntoResource<v1::Pod>>::resource(&server, &pool());
+        let spec = pod.spec.unwrap();
+        let container = spec.containers.first().unwrap();
+        let readiness = container.readiness_probe.clone().unwrap();
+        let tcp_socket = readiness.tcp_socket.unwrap();
+        assert_eq!(tcp_socket.port, IntOrString::Int(8080));
+        assert!(container.startup_probe.is_some());
+    }
+
+    #[test]
+    fn test_pod_probes_for_stdio_transport() {
+        let probe = v1::Probe {
+            exec: Some(v1::ExecAction {
+                command: Some(vec!["cat".to_string(), "/tmp/ready".to_string()]),
+            }),
+            ..Default::default()
+        };
+        let mut server = MCPServer::new("test", MCPServerSpec::default());
+        server.metadata.uid = Some("0123456789abcdef".to_string());
+
+        // --- Without a custom probe, the stdio server is not probed at all.
+        let pod = <MCPServer as IntoResource<v1::Pod>>::resource(&server, &pool());
+        let spec = pod.spec.unwrap();
+        let container = spec.containers.first().unwrap();
+        assert!(container.readiness_probe.is_none());
+        assert!(container.startup_probe.is_none());
+
+        // --- With a custom probe, it is used as the readiness probe.
+        server.spec.readiness_probe = Some(probe.clone());
+        let pod = <MCPServer as IntoResource<v1::Pod>>::resource(&server, &pool());
+        let spec = pod.spec.unwrap();
+        let container = spec.containers.first().unwrap();
+        assert_eq!(container.readiness_probe, Some(probe));
+    }
+}
@@ -1,8 +1,19 @@
 use super::IntoResource;
-use crate::{MCPServer, MCPServerTransport};
+use crate::{MCPPool, MCPServer, MCPServerTransport};
 use k8s_openapi::api::core::v1;
 use k8s_openapi::apimachinery::pkg::util::intstr::IntOrString;
-use kube::{api::ObjectMeta, ResourceExt};
+use kube::{api::ObjectMeta, Resource, ResourceExt};
+
+impl MCPServer {
+    /// Returns the in-cluster base URL of the `v1::Service` for the `MCPServer`, if the
+    /// transport of the server is exposed over the network.
+    pub fn service_url(&self) -> Option<String> {
+        let port = self.spec.transport.port()?;
+        let name = <Self as IntoResource<v1::Service>>::resource_name(self);
+        let namespace = self.namespace().unwrap_or("default".to_owned());
+        Some(format!("http://{name}.{namespace}.svc:{port}"))
+    }
+}
 
 impl IntoResource<v1::Service> for MCPServer {
     /// Returns the name of the `v1::Service` for the `MCPServer`.
@@ -16,7 +27,7 @@ impl IntoResource<v1::Service> for MCPServer {
     }
 
     /// Create a Patch for the `v1::Service` resource based on the `MCPServer` spec.
-    fn resource(&self) -> v1::Service {
+    fn resource(&self, _pool: &MCPPool) -> v1::Service {
         let mut service = v1::Service::default();
 
         let mut ports: Vec<v1::ServicePort> = Vec::new();
@@ -48,10 +59,11 @@ impl IntoResource<v1::Service> for MCPServer {
             name: Some(<Self as IntoResource<v1::Service>>::resource_name(self)),
             namespace: self.metadata.namespace.clone(),
             labels: Some(self.labels().clone()),
+            owner_references: self.controller_owner_ref(&()).map(|owner| vec![owner]),
             ..Default::default()
         };
         service.spec = Some(v1::ServiceSpec {
-            selector: Some(self.labels().clone()),
+            selector: Some(self.selector_labels()),
             ports: Some(ports),
             type_: Some("ClusterIP".to_string()),
             ..v1::ServiceSpec::default()
@@ -0,0 +1,30 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// How the sessions opened through the gateway are isolated from each other.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum MCPServerIsolation {
+    /// Every session shares the single `Pod` of the server, which is started on demand and
+    /// stopped once it has been idle for its `idleTimeout`.
+    #[default]
+    Shared,
+
+    /// Every session gets its own ephemeral `Pod`, counted against the `maxServersActive` of
+    /// the pool and deleted as soon as the session is closed or idles out. Use it for servers
+    /// keeping per-user state, such as browser sessions, working directories or credentials.
+    Session,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_isolation_serialization() {
+        let isolation: MCPServerIsolation = serde_json::from_str("\"session\"").unwrap();
+        assert_eq!(isolation, MCPServerIsolation::Session);
+        let json = serde_json::to_string(&MCPServerIsolation::Shared).unwrap();
+        assert_eq!(json, "\"shared\"");
+    }
+}
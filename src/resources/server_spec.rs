@@ -1,4 +1,4 @@
-use crate::{MCPServerStatus, MCPServerTransport};
+use crate::{MCPAccessRule, MCPServerIsolation, MCPServerStatus, MCPServerTransport};
 use k8s_openapi::api::core::v1;
 use kube::CustomResource;
 use schemars::JsonSchema;
@@ -67,6 +67,31 @@ pub struct MCPServerSpec {
     /// shutting down idle servers.
     #[serde(default = "default_idle_timeout")]
     pub idle_timeout: u32,
+
+    /// How the sessions opened through the gateway are isolated from each other. By default,
+    /// every session shares the same pod. With `session`, the gateway creates an ephemeral pod
+    /// for each session and deletes it once the session is closed or idles out.
+    #[serde(default)]
+    pub isolation: MCPServerIsolation,
+
+    /// The resource requirements for the server's pod. The limits and requests defined here
+    /// are merged over the `defaultResources` of the pool, meaning that only the resources
+    /// that differ from the pool's defaults need to be specified.
+    #[serde(default)]
+    pub resources: Option<v1::ResourceRequirements>,
+
+    /// A custom readiness probe for the server's container, used instead of the probe
+    /// generated from the transport. Servers using the `stdio` transport cannot be probed
+    /// over the network, so an `exec` probe is the only way to delay their readiness until
+    /// the process is able to answer requests.
+    #[serde(default)]
+    pub readiness_probe: Option<v1::Probe>,
+
+    /// The rules granting the clients of the gateway access to the server. When set, they
+    /// replace the `access` rules of the pool. The server is open to every client if neither
+    /// the server nor its pool define any rule.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub access: Vec<MCPAccessRule>,
 }
 
 /// Default pool name
@@ -104,6 +129,10 @@ impl Default for MCPServerSpec {
             env: default_env(),
             transport: MCPServerTransport::default(),
             idle_timeout: default_idle_timeout(),
+            isolation: MCPServerIsolation::default(),
+            resources: None,
+            readiness_probe: None,
+            access: vec![],
         }
     }
 }
@@ -122,6 +151,22 @@ mod tests {
         assert!(crd.spec.versions.first().is_some_and(|v| v.name == "v1"));
     }
 
+    #[test]
+    fn test_mcp_server_crd_access() {
+        let crd = MCPServer::crd();
+        let properties = crd
+            .spec
+            .versions
+            .first()
+            .and_then(|version| version.schema.as_ref())
+            .and_then(|schema| schema.open_api_v3_schema.as_ref())
+            .and_then(|schema| schema.properties.as_ref())
+            .and_then(|properties| properties.get("spec"))
+            .and_then(|spec| spec.properties.as_ref())
+            .unwrap();
+        assert!(properties.contains_key("access"));
+    }
+
     #[test]
     fn test_mcp_server_spec_defaults() {
         let spec = MCPServerSpec::default();
@@ -132,6 +177,8 @@ mod tests {
         assert_eq!(spec.env.len(), 0);
         assert_eq!(spec.transport, MCPServerTransport::Stdio);
         assert_eq!(spec.idle_timeout, 60);
+        assert_eq!(spec.isolation, MCPServerIsolation::Shared);
+        assert_eq!(spec.resources, None);
     }
 
     #[test]
@@ -204,6 +251,10 @@ mod tests {
                 }],
                 transport: MCPServerTransport::Sse { port: 8080 },
                 idle_timeout: 120,
+                isolation: MCPServerIsolation::Shared,
+                resources: None,
+                readiness_probe: None,
+                access: vec![],
             },
             status: None,
         };
@@ -67,10 +67,56 @@ pub struct MCPServerStatus {
     pub current_connections: u32,
 }
 
+impl MCPServerStatus {
+    /// Get the condition of the given type, if it has been observed on the server.
+    pub fn get_condition(&self, type_: &str) -> Option<&Condition> {
+        self.conditions.iter().find(|c| c.type_ == type_)
+    }
+}
+
+/// Changes to the counters of an `MCPServer` status accumulated since they were last applied.
+/// This allows the gateway to count requests and connections locally, and to apply them to the
+/// status in a single update instead of patching the status on every request.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct MCPServerCounters {
+    /// Number of requests received.
+    pub requests: u32,
+
+    /// Number of connections opened minus the number of connections closed.
+    pub connections: i64,
+
+    /// Time of the last received request.
+    pub last_request_at: Option<DateTime<Utc>>,
+}
+
+impl MCPServerCounters {
+    /// Check if there are no changes to apply.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Merge the changes of another set of counters into this one.
+    pub fn merge(&mut self, other: Self) {
+        self.requests = self.requests.saturating_add(other.requests);
+        self.connections = self.connections.saturating_add(other.connections);
+        self.last_request_at = self.last_request_at.max(other.last_request_at);
+    }
+
+    /// Apply the changes to the given status. The counters saturate instead of overflowing,
+    /// so a disconnection counted after the connections were cleared never underflows.
+    pub fn apply(&self, status: &mut MCPServerStatus) {
+        let connections = i64::from(status.current_connections).saturating_add(self.connections);
+        status.current_connections = u32::try_from(connections.max(0)).unwrap_or(u32::MAX);
+        status.total_requests = status.total_requests.saturating_add(self.requests);
+        status.last_request_at = status.last_request_at.max(self.last_request_at);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use chrono::TimeZone;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
 
     #[test]
     fn test_mcp_server_status_default() {
@@ -149,4 +195,79 @@ mod tests {
         assert_eq!(status.total_requests, 10);
         assert_eq!(status.current_connections, 2);
     }
+
+    #[test]
+    fn test_mcp_server_counters_apply() {
+        let last_request_at = Utc.with_ymd_and_hms(2025, 5, 1, 10, 30, 0).unwrap();
+        let mut status = MCPServerStatus {
+            total_requests: 10,
+            current_connections: 2,
+            ..Default::default()
+        };
+
+        let counters = MCPServerCounters {
+            requests: 3,
+            connections: 1,
+            last_request_at: Some(last_request_at),
+        };
+        counters.apply(&mut status);
+        assert_eq!(status.total_requests, 13);
+        assert_eq!(status.current_connections, 3);
+        assert_eq!(status.last_request_at, Some(last_request_at));
+    }
+
+    #[test]
+    fn test_mcp_server_counters_apply_saturates() {
+        let mut status = MCPServerStatus {
+            current_connections: 1,
+            ..Default::default()
+        };
+
+        let counters = MCPServerCounters {
+            connections: -3,
+            ..Default::default()
+        };
+        counters.apply(&mut status);
+        assert_eq!(status.current_connections, 0);
+    }
+
+    #[test]
+    fn test_mcp_server_counters_merge() {
+        let earlier = Utc.with_ymd_and_hms(2025, 5, 1, 10, 0, 0).unwrap();
+        let later = Utc.with_ymd_and_hms(2025, 5, 1, 11, 0, 0).unwrap();
+        let mut counters = MCPServerCounters {
+            requests: 1,
+            connections: 1,
+            last_request_at: Some(later),
+        };
+
+        counters.merge(MCPServerCounters {
+            requests: 2,
+            connections: -1,
+            last_request_at: Some(earlier),
+        });
+        assert_eq!(counters.requests, 3);
+        assert_eq!(counters.connections, 0);
+        assert_eq!(counters.last_request_at, Some(later));
+        assert!(!counters.is_empty());
+        assert!(MCPServerCounters::default().is_empty());
+    }
+
+    #[test]
+    fn test_mcp_server_status_get_condition() {
+        let condition = Condition {
+            type_: "PodScheduled".to_string(),
+            status: "False".to_string(),
+            message: "Image pull failed".to_string(),
+            reason: "Failed".to_string(),
+            observed_generation: None,
+            last_transition_time: Time(Utc::now()),
+        };
+        let status = MCPServerStatus {
+            conditions: vec![condition.clone()],
+            ..Default::default()
+        };
+        assert_eq!(status.get_condition("PodScheduled"), Some(&condition));
+        assert_eq!(status.get_condition("Requested"), None);
+    }
 }
@@ -1,4 +1,4 @@
-use crate::{Error, Result, NMCP_OPERATOR};
+use crate::{Error, MCPPool, Result, NMCP_OPERATOR};
 use k8s_openapi::NamespaceResourceScope;
 use kube::api::{Patch, PatchParams};
 use kube::core::object::{HasSpec, HasStatus};
@@ -20,20 +20,33 @@ where
     <U as Resource>::DynamicType: Default,
     Self: Send + Sync + Resource + Sized + HasSpec + HasStatus,
 {
-    /// Transform the current resource into a specific Kubernetes resource type.
-    fn resource(&self) -> U;
+    /// Transform the current resource into a specific Kubernetes resource type, using the
+    /// `MCPPool` the resource belongs to for the defaults it defines.
+    fn resource(&self, pool: &MCPPool) -> U;
 
     /// Generate the name of the specific resource in the Kubernetes cluster.
     fn resource_name(&self) -> String;
 
+    /// Returns the `Api` of the specific resource, scoped to the namespace of the owner.
+    fn resource_api(&self, client: &Client) -> Api<U> {
+        let namespace = self
+            .namespace()
+            .unwrap_or_else(|| client.default_namespace().to_owned());
+        Api::namespaced(client.clone(), &namespace)
+    }
+
     // Create the specific resource in the Kubernetes cluster.
-    fn patch_resource(&self, client: &Client) -> impl Future<Output = Result<U>> + Send {
+    fn patch_resource(
+        &self,
+        client: &Client,
+        pool: &MCPPool,
+    ) -> impl Future<Output = Result<U>> + Send {
         async {
-            Api::<U>::namespaced(client.clone(), client.default_namespace())
+            self.resource_api(client)
                 .patch(
                     &self.resource_name(),
                     &PatchParams::apply(NMCP_OPERATOR),
-                    &Patch::Apply(self.resource()),
+                    &Patch::Apply(self.resource(pool)),
                 )
                 .await
                 .map_err(Error::from)
@@ -43,7 +56,8 @@ where
     /// Create the specific resource in the Kubernetes cluster.
     fn delete_resource(&self, client: &Client) -> impl Future<Output = Result<()>> + Send {
         async {
-            match Api::<U>::namespaced(client.clone(), client.default_namespace())
+            match self
+                .resource_api(client)
                 .delete(&self.resource_name(), &Default::default())
                 .await
             {
@@ -57,7 +71,7 @@ where
     /// Get the specific resource from the Kubernetes cluster.
     fn get_resource(&self, client: &Client) -> impl Future<Output = Result<U>> + Send {
         async {
-            Api::<U>::namespaced(client.clone(), client.default_namespace())
+            self.resource_api(client)
                 .get(&self.resource_name())
                 .await
                 .map_err(Error::from)
@@ -67,11 +81,7 @@ where
     /// Check if the specific resource exists in the Kubernetes cluster.
     fn resource_exists(&self, client: &Client) -> impl Future<Output = Result<bool>> + Send {
         async {
-            let namespace = self.namespace().unwrap_or("default".to_owned());
-            match Api::<U>::namespaced(client.clone(), &namespace)
-                .get(&self.resource_name())
-                .await
-            {
+            match self.resource_api(client).get(&self.resource_name()).await {
                 Ok(_) => Ok(true),
                 Err(kube::Error::Api(e)) if e.code == 404 => Ok(false),
                 Err(e) => Err(Error::from(e)),
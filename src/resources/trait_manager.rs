@@ -27,6 +27,18 @@ where
     /// Create a new instance of the resource with the given name and spec.
     fn new(name: &str, spec: Self::Spec) -> Self;
 
+    /// Returns the namespace of the resource, falling back to the default namespace of the
+    /// client for resources that were created locally and never fetched from the cluster.
+    fn namespace_or_default(&self, client: &Client) -> String {
+        self.namespace()
+            .unwrap_or_else(|| client.default_namespace().to_owned())
+    }
+
+    /// Returns the `Api` scoped to the namespace of the resource.
+    fn api(&self, client: &Client) -> Api<Self> {
+        Api::namespaced(client.clone(), &self.namespace_or_default(client))
+    }
+
     /// Create the specific resource in the Kubernetes cluster.
     fn apply(&self, client: &Client) -> impl Future<Output = Result<Self>> + Send {
         async {
@@ -34,18 +46,27 @@ where
                 field_manager: Some(NMCP_OPERATOR.to_string()),
                 ..Default::default()
             };
-            Api::<Self>::namespaced(client.clone(), client.default_namespace())
+            self.api(client)
                 .create(&post_params, self)
                 .await
                 .map_err(Error::from)
         }
     }
 
-    /// Get the specific resource from the Kubernetes cluster.
-    #[tracing::instrument(name = "GetResource", skip(client))]
+    /// Get the specific resource from the default namespace of the client.
     fn get_by_name(client: &Client, name: &str) -> impl Future<Output = Result<Self>> + Send {
+        Self::get_by_name_in(client, client.default_namespace(), name)
+    }
+
+    /// Get the specific resource from the given namespace.
+    #[tracing::instrument(name = "GetResource", skip(client))]
+    fn get_by_name_in(
+        client: &Client,
+        namespace: &str,
+        name: &str,
+    ) -> impl Future<Output = Result<Self>> + Send {
         async move {
-            Api::<Self>::namespaced(client.clone(), client.default_namespace())
+            Api::<Self>::namespaced(client.clone(), namespace)
                 .get(name)
                 .await
                 .map_err(Error::from)
@@ -56,7 +77,8 @@ where
     #[tracing::instrument(name = "GetStatus", skip(client))]
     fn get_status(&self, client: &Client) -> impl Future<Output = Result<Self::Status>> + Send {
         async {
-            let statut = Api::<Self>::namespaced(client.clone(), client.default_namespace())
+            let statut = self
+                .api(client)
                 .get_status(&self.name_any())
                 .await
                 .map_err(Error::from)?
@@ -81,14 +103,23 @@ where
         }
     }
 
-    /// Search for resources based on a label selector.
+    /// Search for resources in the default namespace of the client based on a label selector.
     fn search(
         client: &Client,
         list_params: Option<ListParams>,
     ) -> impl Future<Output = Result<Vec<Self>>> + Send {
-        async {
+        Self::search_in(client, client.default_namespace(), list_params)
+    }
+
+    /// Search for resources in the given namespace based on a label selector.
+    fn search_in(
+        client: &Client,
+        namespace: &str,
+        list_params: Option<ListParams>,
+    ) -> impl Future<Output = Result<Vec<Self>>> + Send {
+        async move {
             let params = list_params.unwrap_or_default();
-            Api::<Self>::namespaced(client.clone(), client.default_namespace())
+            Api::<Self>::namespaced(client.clone(), namespace)
                 .list(&params)
                 .await
                 .map_err(Error::from)
@@ -106,7 +137,7 @@ where
             let patch = serde_json::json!({
                 "spec": spec
             });
-            Api::<Self>::namespaced(client.clone(), client.default_namespace())
+            self.api(client)
                 .patch(
                     &self.name_any(),
                     &PatchParams::apply(NMCP_OPERATOR),
@@ -125,7 +156,7 @@ where
     ) -> impl Future<Output = Result<Self>> + Send {
         async move {
             let patch = serde_json::json!({ "status": status });
-            Api::<Self>::namespaced(client.clone(), client.default_namespace())
+            self.api(client)
                 .patch_status(
                     &self.name_any(),
                     &PatchParams::apply(NMCP_OPERATOR),
@@ -139,7 +170,7 @@ where
     /// Refresh the specific resource from the Kubernetes cluster.
     fn refresh(&self, client: &Client) -> impl Future<Output = Result<Self>> + Send {
         async {
-            Api::<Self>::namespaced(client.clone(), client.default_namespace())
+            self.api(client)
                 .get_status(&self.name_any())
                 .await
                 .map_err(Error::from)
@@ -149,7 +180,8 @@ where
     /// Delete the specific resource from the Kubernetes cluster.
     fn delete(&self, client: &Client) -> impl Future<Output = Result<()>> + Send {
         async {
-            match Api::<Self>::namespaced(client.clone(), client.default_namespace())
+            match self
+                .api(client)
                 .delete(&self.name_any(), &Default::default())
                 .await
             {
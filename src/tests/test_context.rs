@@ -66,6 +66,8 @@ impl TestContext {
         let options = ControllerOptions {
             namespace: name.clone(),
             kubeconfig: self.kubeconfig.clone().into(),
+            requeue_interval: 5,
+            ..Default::default()
         };
 
         // --- Create the controller.
@@ -0,0 +1,47 @@
+use super::{Authenticator, Principal};
+use crate::Error;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use tracing::Instrument;
+
+/// Middleware authenticating the requests, rejecting them with a `401` if they are not. The
+/// `Principal` of the request is added to its extensions and recorded in its span.
+pub async fn authenticate(
+    State(authenticator): State<Authenticator>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let principal = match authenticator.authenticate(request.headers()).await {
+        Ok(principal) => principal,
+        Err(error) => return error.into_response(),
+    };
+    let span = tracing::info_span!(
+        "Auth",
+        principal = %principal,
+        method = ?principal.method,
+    );
+    let _ = request.extensions_mut().insert(principal);
+    next.run(request).instrument(span).await
+}
+
+/// Middleware rejecting with a `403` the authenticated requests whose principal may not use the
+/// manager API. Must be applied within the `authenticate` middleware.
+pub async fn authorize_manager(
+    State(authenticator): State<Authenticator>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_manager = request
+        .extensions()
+        .get::<Principal>()
+        .is_some_and(|principal| authenticator.is_manager(principal));
+    if !is_manager {
+        return Error::generic("Principal is not allowed to use the manager API")
+            .with_name("E_FORBIDDEN")
+            .with_status(StatusCode::FORBIDDEN)
+            .into_response();
+    }
+    next.run(request).await
+}
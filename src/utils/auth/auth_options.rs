@@ -0,0 +1,49 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Configuration of the authentication of the gateway and manager APIs. Requests are not
+/// authenticated unless at least one of the methods is configured.
+#[derive(Debug, Clone, Default, Parser)]
+pub struct AuthOptions {
+    /// Name of a `Secret` in the namespace of the controller holding the accepted API keys.
+    /// Each entry of the secret is an API key, named after the principal it authenticates.
+    #[arg(long)]
+    pub auth_api_keys_secret: Option<String>,
+
+    /// Path to a JWKS file holding the keys used to verify the JWT bearer tokens
+    #[arg(long, conflicts_with = "auth_jwt_issuer")]
+    pub auth_jwks_file: Option<PathBuf>,
+
+    /// Issuer of the JWT bearer tokens. The keys used to verify the tokens are discovered
+    /// from the `.well-known/openid-configuration` of the issuer, unless a JWKS file is provided.
+    #[arg(long)]
+    pub auth_jwt_issuer: Option<String>,
+
+    /// Expected audience of the JWT bearer tokens
+    #[arg(long)]
+    pub auth_jwt_audience: Option<String>,
+
+    /// Authenticate the bearer tokens of Kubernetes service accounts with a `TokenReview`
+    #[arg(long)]
+    pub auth_token_review: bool,
+
+    /// Names of the principals allowed to use the manager API. When neither principals nor
+    /// groups are listed, every authenticated principal is allowed, except the service
+    /// accounts authenticated with a `TokenReview`.
+    #[arg(long = "auth-manager-principal")]
+    pub auth_manager_principals: Vec<String>,
+
+    /// Groups whose members are allowed to use the manager API.
+    #[arg(long = "auth-manager-group")]
+    pub auth_manager_groups: Vec<String>,
+}
+
+impl AuthOptions {
+    /// Check if at least one authentication method is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.auth_api_keys_secret.is_some()
+            || self.auth_jwks_file.is_some()
+            || self.auth_jwt_issuer.is_some()
+            || self.auth_token_review
+    }
+}
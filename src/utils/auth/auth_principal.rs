@@ -0,0 +1,53 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// The method a `Principal` was authenticated with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum AuthMethod {
+    /// No authentication method is configured, every request is accepted.
+    Anonymous,
+
+    /// A static API key stored in a Kubernetes `Secret`.
+    ApiKey,
+
+    /// A JWT verified against the configured JWKS.
+    Jwt,
+
+    /// A Kubernetes service account token verified with a `TokenReview`.
+    TokenReview,
+}
+
+/// The identity a request was authenticated as. It is added to the extensions of the
+/// authenticated requests, so handlers can extract it with `Extension<Principal>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Principal {
+    /// The name of the principal, such as the name of the API key or the subject of the token.
+    pub name: String,
+
+    /// The groups the principal belongs to.
+    #[serde(default)]
+    pub groups: Vec<String>,
+
+    /// The method the principal was authenticated with.
+    pub method: AuthMethod,
+}
+
+impl Principal {
+    /// The principal of the requests when authentication is disabled.
+    pub fn anonymous() -> Self {
+        Self {
+            name: "anonymous".to_string(),
+            groups: vec![],
+            method: AuthMethod::Anonymous,
+        }
+    }
+}
+
+impl Display for Principal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
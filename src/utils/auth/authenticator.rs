@@ -0,0 +1,540 @@
+use super::{AuthMethod, AuthOptions, Principal};
+use crate::{Controller, Error, Result};
+use axum::http::{header, HeaderMap, StatusCode};
+use futures::StreamExt;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use k8s_openapi::api::authentication::v1::{TokenReview, TokenReviewSpec};
+use k8s_openapi::api::core::v1::Secret;
+use kube::api::PostParams;
+use kube::runtime::reflector::{self, ObjectRef, Store};
+use kube::runtime::{watcher, WatchStreamExt};
+use kube::{Api, Client};
+use moka::sync::Cache;
+use serde::Deserialize;
+use std::fmt::{Debug, Formatter};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use subtle::ConstantTimeEq;
+
+/// Header carrying the API key of a request, as an alternative to the `Authorization` header.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Duration for which an authenticated token is trusted without being verified again.
+const PRINCIPAL_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Duration for which a rejected token is rejected without being verified again, so that
+/// retrying an invalid token does not cost a `TokenReview` every time.
+const REJECTED_CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// Maximum number of rejected tokens remembered at once.
+const REJECTED_CACHE_CAPACITY: u64 = 10_000;
+
+/// Minimum duration between two refreshes of the keys of the JWT issuer, so that tokens
+/// signed by unknown keys cannot be used to flood the issuer with requests.
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Maximum duration of a request to the JWT issuer.
+const JWKS_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The claims of a JWT bearer token the principal is derived from.
+#[derive(Debug, Deserialize)]
+struct JwtClaims {
+    sub: String,
+    #[serde(default)]
+    groups: Vec<String>,
+}
+
+/// The subset of the `.well-known/openid-configuration` of an issuer used to discover its keys.
+#[derive(Debug, Deserialize)]
+struct OpenIdConfiguration {
+    jwks_uri: String,
+}
+
+/// Returns the credentials of a request, either from the `Authorization: Bearer` header or
+/// from the `X-API-Key` header.
+pub fn request_token(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let api_key = headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+    bearer
+        .or(api_key)
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// Returns the algorithms a key may verify signatures with. The algorithm declared by the key
+/// is the only one allowed, keys without one are limited to the algorithms of their type, so
+/// that the `alg` header of a token can never make a key verify another kind of signature.
+fn jwk_algorithms(jwk: &Jwk) -> Vec<Algorithm> {
+    if let Some(algorithm) = jwk.common.key_algorithm {
+        return Algorithm::from_str(&algorithm.to_string())
+            .into_iter()
+            .collect();
+    }
+    match &jwk.algorithm {
+        AlgorithmParameters::RSA(_) => vec![
+            Algorithm::RS256,
+            Algorithm::RS384,
+            Algorithm::RS512,
+            Algorithm::PS256,
+            Algorithm::PS384,
+            Algorithm::PS512,
+        ],
+        AlgorithmParameters::EllipticCurve(parameters) => match parameters.curve {
+            EllipticCurve::P256 => vec![Algorithm::ES256],
+            EllipticCurve::P384 => vec![Algorithm::ES384],
+            EllipticCurve::P521 | EllipticCurve::Ed25519 => vec![],
+        },
+        AlgorithmParameters::OctetKeyPair(_) => vec![Algorithm::EdDSA],
+        AlgorithmParameters::OctetKey(_) => {
+            vec![Algorithm::HS256, Algorithm::HS384, Algorithm::HS512]
+        }
+    }
+}
+
+/// Verify a JWT against a set of keys, and return the principal it was issued for. Returns
+/// `None` if the token is not a valid JWT signed by one of the keys, with an algorithm the
+/// key allows.
+pub fn validate_jwt(
+    jwks: &JwkSet,
+    token: &str,
+    issuer: Option<&str>,
+    audience: Option<&str>,
+) -> Option<Principal> {
+    let header = jsonwebtoken::decode_header(token).ok()?;
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid)?,
+        None => jwks.keys.first()?,
+    };
+    let algorithms = jwk_algorithms(jwk);
+    if !algorithms.contains(&header.alg) {
+        tracing::debug!("Rejected JWT signed with {:?}", header.alg);
+        return None;
+    }
+    let key = DecodingKey::from_jwk(jwk).ok()?;
+    let mut validation = Validation::new(header.alg);
+    validation.algorithms = algorithms;
+    if let Some(issuer) = issuer {
+        validation.set_issuer(&[issuer]);
+    }
+    match audience {
+        Some(audience) => validation.set_audience(&[audience]),
+        None => validation.validate_aud = false,
+    }
+    let claims = jsonwebtoken::decode::<JwtClaims>(token, &key, &validation)
+        .map_err(|error| tracing::debug!("Rejected JWT: {}", error))
+        .ok()?
+        .claims;
+    Some(Principal {
+        name: claims.sub,
+        groups: claims.groups,
+        method: AuthMethod::Jwt,
+    })
+}
+
+/// Find the API key of a `Secret` matching the token, and return the principal it is named
+/// after. The keys are compared in constant time, so the timing of the comparison does not
+/// reveal how much of a key the token matches.
+fn find_api_key(secret: &Secret, token: &str) -> Option<Principal> {
+    secret
+        .data
+        .iter()
+        .flatten()
+        .find(|(_, key)| bool::from(key.0.as_slice().ct_eq(token.as_bytes())))
+        .map(|(name, _)| Principal {
+            name: name.clone(),
+            groups: vec![],
+            method: AuthMethod::ApiKey,
+        })
+}
+
+/// Authenticates the requests of the gateway and manager APIs using the methods configured
+/// in the `AuthOptions`, in order: API keys, JWT, and finally `TokenReview`.
+#[derive(Clone)]
+pub struct Authenticator {
+    options: AuthOptions,
+    client: Client,
+    namespace: String,
+    jwks: Arc<RwLock<JwkSet>>,
+    jwks_refreshed_at: Arc<tokio::sync::Mutex<Option<Instant>>>,
+    api_keys: Option<Store<Secret>>,
+    principals: Cache<String, Principal>,
+    rejected: Cache<String, ()>,
+}
+
+impl Debug for Authenticator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Authenticator")
+            .field("options", &self.options)
+            .field("namespace", &self.namespace)
+            .field("principals", &self.principals.entry_count())
+            .finish()
+    }
+}
+
+impl Authenticator {
+    /// Create the authenticator, loading the keys used to verify the JWTs and watching the
+    /// `Secret` holding the API keys if configured. The keys of an issuer that cannot be
+    /// reached yet are fetched again on the first JWT to verify.
+    pub async fn new(options: &AuthOptions, controller: &Controller) -> Result<Self> {
+        let mut jwks_refreshed_at = None;
+        let jwks = match (&options.auth_jwks_file, &options.auth_jwt_issuer) {
+            (Some(path), _) => serde_json::from_str(&tokio::fs::read_to_string(path).await?)?,
+            (None, Some(issuer)) => match Self::discover_jwks(issuer).await {
+                Ok(jwks) => {
+                    jwks_refreshed_at = Some(Instant::now());
+                    jwks
+                }
+                Err(error) => {
+                    let _ = error.trace();
+                    JwkSet { keys: vec![] }
+                }
+            },
+            (None, None) => JwkSet { keys: vec![] },
+        };
+        let client = controller.get_client();
+        let namespace = controller.get_namespace();
+        let api_keys = options
+            .auth_api_keys_secret
+            .as_deref()
+            .map(|name| Self::watch_api_keys(&client, &namespace, name));
+        Ok(Self {
+            options: options.clone(),
+            client,
+            namespace,
+            jwks: Arc::new(RwLock::new(jwks)),
+            jwks_refreshed_at: Arc::new(tokio::sync::Mutex::new(jwks_refreshed_at)),
+            api_keys,
+            principals: Cache::builder().time_to_live(PRINCIPAL_CACHE_TTL).build(),
+            rejected: Cache::builder()
+                .max_capacity(REJECTED_CACHE_CAPACITY)
+                .time_to_live(REJECTED_CACHE_TTL)
+                .build(),
+        })
+    }
+
+    /// Watch the `Secret` holding the API keys, so that the keys are read from memory and
+    /// rotated without restarting. Errors of the watcher, such as a missing permission, are
+    /// only traced, the API keys are then simply not accepted.
+    fn watch_api_keys(client: &Client, namespace: &str, name: &str) -> Store<Secret> {
+        let (store, writer) = reflector::store();
+        let api = Api::<Secret>::namespaced(client.clone(), namespace);
+        let config = watcher::Config::default().fields(&format!("metadata.name={name}"));
+        let stream = reflector::reflector(writer, watcher(api, config))
+            .default_backoff()
+            .for_each(|event| async {
+                if let Err(error) = event {
+                    let _ = Error::from(error).trace();
+                }
+            });
+        drop(tokio::spawn(stream));
+        store
+    }
+
+    /// Check if the requests must be authenticated.
+    pub fn is_enabled(&self) -> bool {
+        self.options.is_enabled()
+    }
+
+    /// Fetch the keys of an issuer from the `jwks_uri` of its `.well-known/openid-configuration`.
+    async fn discover_jwks(issuer: &str) -> Result<JwkSet> {
+        let http = reqwest::Client::builder()
+            .timeout(JWKS_FETCH_TIMEOUT)
+            .build()?;
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            issuer.trim_end_matches('/')
+        );
+        let configuration: OpenIdConfiguration = http
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let jwks = http
+            .get(configuration.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(jwks)
+    }
+
+    /// Fetch the keys of the issuer again, unless they were already fetched within the last
+    /// `JWKS_REFRESH_INTERVAL`. Concurrent refreshes wait for the first one to complete. An
+    /// outage of the issuer is only traced, the tokens signed by unknown keys are then
+    /// rejected as unauthenticated.
+    async fn refresh_jwks(&self, issuer: &str) {
+        let mut refreshed_at = self.jwks_refreshed_at.lock().await;
+        if refreshed_at.is_some_and(|at| at.elapsed() < JWKS_REFRESH_INTERVAL) {
+            return;
+        }
+        *refreshed_at = Some(Instant::now());
+        match Self::discover_jwks(issuer).await {
+            Ok(jwks) => *self.jwks.write().unwrap_or_else(|error| error.into_inner()) = jwks,
+            Err(error) => {
+                let _ = error.trace();
+            }
+        }
+    }
+
+    /// Authenticate a request from its headers.
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Result<Principal> {
+        if !self.is_enabled() {
+            return Ok(Principal::anonymous());
+        }
+        let unauthorized = || {
+            Error::generic("Missing or invalid credentials")
+                .with_name("E_UNAUTHORIZED")
+                .with_status(StatusCode::UNAUTHORIZED)
+        };
+        let token = request_token(headers).ok_or_else(unauthorized)?;
+        if let Some(principal) = self.principals.get(token) {
+            return Ok(principal);
+        }
+        if self.rejected.contains_key(token) {
+            return Err(unauthorized());
+        }
+
+        // --- Try every configured method until one of them accepts the token.
+        let mut principal = self.authenticate_api_key(token).await?;
+        if principal.is_none() {
+            principal = self.authenticate_jwt(token).await?;
+        }
+        if principal.is_none() {
+            principal = self.authenticate_token_review(token).await?;
+        }
+        let Some(principal) = principal else {
+            self.rejected.insert(token.to_string(), ());
+            return Err(unauthorized());
+        };
+        self.principals.insert(token.to_string(), principal.clone());
+        Ok(principal)
+    }
+
+    /// Check if the principal of a request may use the manager API. Only the principals and
+    /// groups listed in the `AuthOptions` may do so, or every principal not authenticated with
+    /// a `TokenReview` if none are listed, since any service account of the cluster can be
+    /// reviewed successfully.
+    pub fn is_manager(&self, principal: &Principal) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+        let principals = &self.options.auth_manager_principals;
+        let groups = &self.options.auth_manager_groups;
+        if principals.is_empty() && groups.is_empty() {
+            return principal.method != AuthMethod::TokenReview;
+        }
+        principals.contains(&principal.name)
+            || principal.groups.iter().any(|group| groups.contains(group))
+    }
+
+    /// Authenticate a token against the API keys stored in the configured `Secret`. The token
+    /// is not accepted as an API key while the `Secret` is missing or cannot be read.
+    async fn authenticate_api_key(&self, token: &str) -> Result<Option<Principal>> {
+        let (Some(name), Some(api_keys)) = (&self.options.auth_api_keys_secret, &self.api_keys)
+        else {
+            return Ok(None);
+        };
+        let key = ObjectRef::new(name).within(&self.namespace);
+        let Some(secret) = api_keys.get(&key) else {
+            return Ok(None);
+        };
+        Ok(find_api_key(&secret, token))
+    }
+
+    /// Authenticate a JWT against the configured keys. When the keys are discovered from the
+    /// issuer, they are fetched again if the token is signed by an unknown key, at most once
+    /// per `JWKS_REFRESH_INTERVAL`.
+    async fn authenticate_jwt(&self, token: &str) -> Result<Option<Principal>> {
+        let issuer = self.options.auth_jwt_issuer.as_deref();
+        let audience = self.options.auth_jwt_audience.as_deref();
+        if self.options.auth_jwks_file.is_none() && issuer.is_none() {
+            return Ok(None);
+        }
+        let validate = || {
+            let jwks = self.jwks.read().unwrap_or_else(|error| error.into_inner());
+            validate_jwt(&jwks, token, issuer, audience)
+        };
+        if let Some(principal) = validate() {
+            return Ok(Some(principal));
+        }
+
+        // --- Refresh the keys of the issuer if the token was signed by an unknown key.
+        let kid = jsonwebtoken::decode_header(token)
+            .ok()
+            .and_then(|header| header.kid);
+        let is_unknown_key = {
+            let jwks = self.jwks.read().unwrap_or_else(|error| error.into_inner());
+            match kid {
+                Some(kid) => jwks.find(&kid).is_none(),
+                None => jwks.keys.is_empty(),
+            }
+        };
+        match (
+            issuer,
+            is_unknown_key && self.options.auth_jwks_file.is_none(),
+        ) {
+            (Some(issuer), true) => {
+                self.refresh_jwks(issuer).await;
+                Ok(validate())
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Authenticate a Kubernetes service account token with a `TokenReview`. Service account
+    /// tokens are JWTs, so other tokens are rejected without calling the API server. The
+    /// failures of the API server are reported as a `503`, unless it rejected the token itself.
+    async fn authenticate_token_review(&self, token: &str) -> Result<Option<Principal>> {
+        if !self.options.auth_token_review || jsonwebtoken::decode_header(token).is_err() {
+            return Ok(None);
+        }
+        let review = TokenReview {
+            spec: TokenReviewSpec {
+                token: Some(token.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let review = match Api::<TokenReview>::all(self.client.clone())
+            .create(&PostParams::default(), &review)
+            .await
+        {
+            Ok(review) => review,
+            Err(kube::Error::Api(error)) if matches!(error.code, 400 | 401 | 422) => {
+                tracing::debug!("TokenReview rejected the token: {}", error.message);
+                return Ok(None);
+            }
+            Err(error) => {
+                tracing::error!("Failed to review the token: {}", error);
+                return Err(Error::generic("Authentication is temporarily unavailable")
+                    .with_name("E_AUTH_UNAVAILABLE")
+                    .with_status(StatusCode::SERVICE_UNAVAILABLE));
+            }
+        };
+        let status = review.status.unwrap_or_default();
+        if status.authenticated != Some(true) {
+            return Ok(None);
+        }
+        let user = status.user.unwrap_or_default();
+        Ok(user.username.map(|name| Principal {
+            name,
+            groups: user.groups.unwrap_or_default(),
+            method: AuthMethod::TokenReview,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use jsonwebtoken::{EncodingKey, Header};
+
+    /// A JWKS holding the `secret` key for HS256 signatures.
+    fn jwks() -> JwkSet {
+        serde_json::from_value(serde_json::json!({
+            "keys": [{ "kty": "oct", "kid": "test", "alg": "HS256", "k": "c2VjcmV0" }]
+        }))
+        .unwrap()
+    }
+
+    fn jwt(secret: &[u8], issuer: &str) -> String {
+        let header = Header {
+            kid: Some("test".to_string()),
+            ..Header::new(jsonwebtoken::Algorithm::HS256)
+        };
+        let claims = serde_json::json!({
+            "sub": "alice",
+            "iss": issuer,
+            "groups": ["admins"],
+            "exp": chrono::Utc::now().timestamp() + 60,
+        });
+        jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(secret)).unwrap()
+    }
+
+    #[test]
+    fn test_request_token() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(request_token(&headers), None);
+        let _ = headers.insert(API_KEY_HEADER, HeaderValue::from_static("key"));
+        assert_eq!(request_token(&headers), Some("key"));
+        let _ = headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer token"),
+        );
+        assert_eq!(request_token(&headers), Some("token"));
+    }
+
+    #[test]
+    fn test_validate_jwt() {
+        let token = jwt(b"secret", "https://issuer");
+        let principal = validate_jwt(&jwks(), &token, Some("https://issuer"), None).unwrap();
+        assert_eq!(principal.name, "alice");
+        assert_eq!(principal.groups, vec!["admins".to_string()]);
+        assert_eq!(principal.method, AuthMethod::Jwt);
+    }
+
+    #[test]
+    fn test_validate_jwt_rejects_invalid_tokens() {
+        let token = jwt(b"other", "https://issuer");
+        assert_eq!(validate_jwt(&jwks(), &token, None, None), None);
+        let token = jwt(b"secret", "https://other");
+        assert_eq!(
+            validate_jwt(&jwks(), &token, Some("https://issuer"), None),
+            None
+        );
+        assert_eq!(validate_jwt(&jwks(), "not-a-jwt", None, None), None);
+    }
+
+    #[test]
+    fn test_validate_jwt_rejects_other_algorithms() {
+        let header = Header {
+            kid: Some("test".to_string()),
+            ..Header::new(Algorithm::HS512)
+        };
+        let claims =
+            serde_json::json!({ "sub": "alice", "exp": chrono::Utc::now().timestamp() + 60 });
+        let token = jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(b"secret"));
+        assert_eq!(validate_jwt(&jwks(), &token.unwrap(), None, None), None);
+    }
+
+    #[test]
+    fn test_find_api_key() {
+        let secret: Secret = serde_json::from_value(serde_json::json!({
+            "metadata": { "name": "api-keys" },
+            "data": { "ci": "a2V5", "bot": "b3RoZXI=" }
+        }))
+        .unwrap();
+        let principal = find_api_key(&secret, "key").unwrap();
+        assert_eq!(principal.name, "ci");
+        assert_eq!(principal.method, AuthMethod::ApiKey);
+        assert_eq!(find_api_key(&secret, "ke"), None);
+        assert_eq!(find_api_key(&secret, "keys"), None);
+    }
+
+    #[tokio::test]
+    async fn test_discover_jwks_over_tls() {
+        use tokio::io::AsyncReadExt;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("https://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream.read_u8().await.unwrap()
+        });
+
+        // --- The issuer is not a TLS server, so the discovery fails once the client
+        // --- sent its `ClientHello`, which starts with a handshake record (0x16).
+        assert!(Authenticator::discover_jwks(&issuer).await.is_err());
+        assert_eq!(server.await.unwrap(), 0x16);
+    }
+}
@@ -0,0 +1,9 @@
+mod auth_middleware;
+mod auth_options;
+mod auth_principal;
+mod authenticator;
+
+pub use auth_middleware::*;
+pub use auth_options::*;
+pub use auth_principal::*;
+pub use authenticator::*;
@@ -4,5 +4,32 @@ pub const DEFAULT_SSE_CHANNEL_CAPACITY: usize = 1024;
 // Default buffer size for pod streams
 pub const DEFAULT_POD_BUFFER_SIZE: usize = 1024 * 256; //  256 KiB
 
+/// Default maximum size of a single message read from the stdout of a process
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 1024 * 1024 * 4; // 4 MiB
+
 /// The container name for the Pod that runs the MCP server
 pub const MCP_SERVER_CONTAINER_NAME: &str = "server";
+
+/// The path of the SSE endpoint exposed by MCP servers using the `sse` transport
+pub const DEFAULT_SSE_ENDPOINT_PATH: &str = "/sse";
+
+/// Maximum time to wait for an upstream MCP server to accept a transport connection
+pub const DEFAULT_TRANSPORT_CONNECT_TIMEOUT: u64 = 30; // 30 seconds
+
+/// The path of the endpoint exposed by MCP servers using the `streamable-http` transport
+pub const DEFAULT_STREAMABLE_HTTP_ENDPOINT_PATH: &str = "/mcp";
+
+/// The header used by the Streamable HTTP transport to carry the session ID
+pub const MCP_SESSION_ID_HEADER: &str = "mcp-session-id";
+
+/// The label identifying the session an ephemeral `Pod` was created for
+pub const MCP_SESSION_LABEL: &str = "nmcp.nwrx.io/session";
+
+/// The annotation holding the last time the gateway reported the session of a `Pod` as open
+pub const MCP_SESSION_HEARTBEAT_ANNOTATION: &str = "nmcp.nwrx.io/heartbeat";
+
+/// Interval at which the gateway refreshes the heartbeat of the open sessions
+pub const DEFAULT_SESSION_HEARTBEAT_INTERVAL: u64 = 30; // 30 seconds
+
+/// Time without heartbeat after which the `Pod` of a session is considered orphaned
+pub const DEFAULT_SESSION_HEARTBEAT_TIMEOUT: u64 = 120; // 2 minutes
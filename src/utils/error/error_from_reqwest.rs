@@ -0,0 +1,29 @@
+use super::{Error, ErrorInner};
+use axum::http::StatusCode;
+
+impl From<reqwest::Error> for Error {
+    fn from(source: reqwest::Error) -> Self {
+        let message = source.to_string();
+        let status = source.status().map(|status| status.as_u16());
+        let name = if source.is_timeout() {
+            "E_HTTP_TIMEOUT"
+        } else if source.is_connect() {
+            "E_HTTP_CONNECT"
+        } else if source.is_status() {
+            "E_HTTP_STATUS"
+        } else if source.is_decode() {
+            "E_HTTP_DECODE"
+        } else {
+            "E_HTTP_REQUEST"
+        };
+
+        // --- Errors reaching an upstream MCP server are reported as a bad gateway,
+        // --- unless the upstream server responded with a status code of its own.
+        let source = ErrorInner::ReqwestError(source);
+        let error = Self::new(source).with_name(name).with_message(message);
+        match status {
+            Some(status) => error.with_status(status),
+            None => error.with_status(StatusCode::BAD_GATEWAY),
+        }
+    }
+}
@@ -25,6 +25,9 @@ pub enum ErrorInner {
     #[error("{0}")]
     KubeClientError(kube_client::Error),
 
+    #[error("{0}")]
+    ReqwestError(#[source] reqwest::Error),
+
     #[error("{0}")]
     AxumError(#[from] axum::Error),
 
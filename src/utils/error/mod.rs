@@ -6,6 +6,7 @@ mod error_from_kube;
 mod error_from_kube_runtime_controller;
 mod error_from_kube_runtime_finalizer;
 mod error_from_kube_runtime_watcher;
+mod error_from_reqwest;
 mod error_from_tokio;
 mod error_impl;
 mod error_inner;
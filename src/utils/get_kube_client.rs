@@ -101,6 +101,13 @@ impl From<Kubeconfig> for config::Kubeconfig {
 }
 
 pub async fn get_kube_client(kubeconfig: Kubeconfig) -> Result<Client> {
+    let config = get_kube_config(kubeconfig).await?;
+    let client = Client::try_from(config)?;
+    Ok(client)
+}
+
+/// Resolve the kube `Config` from the given `Kubeconfig`, without creating the client yet.
+pub async fn get_kube_config(kubeconfig: Kubeconfig) -> Result<Config> {
     let config = match &kubeconfig {
         Kubeconfig::Path(path) => {
             let kubeconfig = read_to_string(path).map_err(Error::from)?;
@@ -121,10 +128,7 @@ pub async fn get_kube_client(kubeconfig: Kubeconfig) -> Result<Client> {
         // --- If no kubeconfig path is provided, use the in-cluster config.
         Kubeconfig::InCluster => Config::incluster().map_err(ErrorInner::from)?,
     };
-
-    // --- Create a kube client from the kubeconfig.
-    let client = Client::try_from(config)?;
-    Ok(client)
+    Ok(config)
 }
 
 #[cfg(test)]
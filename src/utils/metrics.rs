@@ -0,0 +1,262 @@
+use crate::{Error, Result};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use prometheus::{
+    HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::fmt::{Debug, Formatter};
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+use tokio::net::TcpListener;
+
+/// The methods of the requests and notifications a client may send to a server, as defined by
+/// MCP. They are the only methods recorded as they are in the metrics.
+const MCP_CLIENT_METHODS: [&str; 17] = [
+    "initialize",
+    "ping",
+    "completion/complete",
+    "logging/setLevel",
+    "prompts/get",
+    "prompts/list",
+    "resources/list",
+    "resources/templates/list",
+    "resources/read",
+    "resources/subscribe",
+    "resources/unsubscribe",
+    "tools/call",
+    "tools/list",
+    "notifications/initialized",
+    "notifications/cancelled",
+    "notifications/progress",
+    "notifications/roots/list_changed",
+];
+
+/// Returns the `method` label of a JSON-RPC method. The methods that are not defined by MCP are
+/// all recorded as `other`, so that clients cannot create an unbounded number of time series.
+pub fn method_label(method: &str) -> &'static str {
+    MCP_CLIENT_METHODS
+        .into_iter()
+        .find(|known| *known == method)
+        .unwrap_or("other")
+}
+
+/// The metrics of the process, shared by the operator, the gateway and the manager.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// The Prometheus metrics exported on the `/metrics` endpoints. Each process only updates the
+/// metrics related to its own role, the others are exported without any sample.
+pub struct Metrics {
+    registry: Registry,
+
+    /// Number of servers in each phase, per pool.
+    pub servers: IntGaugeVec,
+
+    /// Number of active servers, per pool.
+    pub pool_active_servers: IntGaugeVec,
+
+    /// Maximum number of active servers, per pool.
+    pub pool_max_active_servers: IntGaugeVec,
+
+    /// Duration of the reconciliations, per kind of resource.
+    pub reconcile_duration: HistogramVec,
+
+    /// Number of failed reconciliations, per kind of resource.
+    pub reconcile_errors: IntCounterVec,
+
+    /// Number of sessions opened through the gateway, per server.
+    pub gateway_sessions: IntGaugeVec,
+
+    /// Number of JSON-RPC messages forwarded to the servers, per method and outcome.
+    pub jsonrpc_requests: IntCounterVec,
+
+    /// Time taken by the servers to answer the JSON-RPC requests, per method.
+    pub upstream_latency: HistogramVec,
+
+    /// Time taken by the servers to become ready after being requested, per pool.
+    pub cold_start: HistogramVec,
+
+    /// Number of transports kept in the cache of the gateway.
+    pub transport_cache_size: IntGauge,
+}
+
+impl Debug for Metrics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Metrics").finish_non_exhaustive()
+    }
+}
+
+/// Create a metric and register it in the registry. Metrics are only created once with static
+/// names, so failing to do so is a programming error.
+fn register<T>(registry: &Registry, metric: prometheus::Result<T>) -> T
+where
+    T: prometheus::core::Collector + Clone + 'static,
+{
+    let metric = metric.expect("Invalid metric definition");
+    registry
+        .register(Box::new(metric.clone()))
+        .expect("Metric registered twice");
+    metric
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("nmcp".to_string()), None).expect("Invalid metrics prefix");
+        let latency_buckets = vec![
+            0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+        ];
+        let cold_start_buckets = vec![1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0, 300.0];
+        Self {
+            servers: register(
+                &registry,
+                IntGaugeVec::new(
+                    Opts::new("servers", "Number of servers in each phase, per pool"),
+                    &["namespace", "pool", "phase"],
+                ),
+            ),
+            pool_active_servers: register(
+                &registry,
+                IntGaugeVec::new(
+                    Opts::new("pool_active_servers", "Number of active servers, per pool"),
+                    &["namespace", "pool"],
+                ),
+            ),
+            pool_max_active_servers: register(
+                &registry,
+                IntGaugeVec::new(
+                    Opts::new(
+                        "pool_max_active_servers",
+                        "Maximum number of active servers, per pool",
+                    ),
+                    &["namespace", "pool"],
+                ),
+            ),
+            reconcile_duration: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "reconcile_duration_seconds",
+                        "Duration of the reconciliations, per kind of resource",
+                    ),
+                    &["kind"],
+                ),
+            ),
+            reconcile_errors: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "reconcile_errors_total",
+                        "Number of failed reconciliations, per kind of resource",
+                    ),
+                    &["kind"],
+                ),
+            ),
+            gateway_sessions: register(
+                &registry,
+                IntGaugeVec::new(
+                    Opts::new(
+                        "gateway_sessions",
+                        "Number of sessions opened through the gateway, per server",
+                    ),
+                    &["namespace", "server"],
+                ),
+            ),
+            jsonrpc_requests: register(
+                &registry,
+                IntCounterVec::new(
+                    Opts::new(
+                        "jsonrpc_requests_total",
+                        "Number of JSON-RPC messages forwarded to the servers, per method and outcome",
+                    ),
+                    &["method", "outcome"],
+                ),
+            ),
+            upstream_latency: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "upstream_latency_seconds",
+                        "Time taken by the servers to answer the JSON-RPC requests, per method",
+                    )
+                    .buckets(latency_buckets),
+                    &["method"],
+                ),
+            ),
+            cold_start: register(
+                &registry,
+                HistogramVec::new(
+                    HistogramOpts::new(
+                        "cold_start_seconds",
+                        "Time taken by the servers to become ready after being requested, per pool",
+                    )
+                    .buckets(cold_start_buckets),
+                    &["namespace", "pool"],
+                ),
+            ),
+            transport_cache_size: register(
+                &registry,
+                IntGauge::new(
+                    "transport_cache_size",
+                    "Number of transports kept in the cache of the gateway",
+                ),
+            ),
+            registry,
+        }
+    }
+
+    /// Encode the metrics in the Prometheus text format.
+    pub fn encode(&self) -> Result<String> {
+        TextEncoder::new()
+            .encode_to_string(&self.registry.gather())
+            .map_err(|error| Error::generic(error.to_string()).with_name("E_METRICS_ENCODE"))
+    }
+}
+
+/// Handler for the `/metrics` endpoints.
+pub async fn render_metrics() -> Response {
+    match METRICS.encode() {
+        Ok(body) => ([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], body).into_response(),
+        Err(error) => {
+            let _ = error.trace();
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Serve the `/metrics` endpoint on its own address until the process stops.
+#[tracing::instrument(name = "MetricsServer", skip_all)]
+pub async fn serve_metrics(address: SocketAddr) -> Result<()> {
+    let router = Router::new().route("/metrics", get(render_metrics));
+    let listener = TcpListener::bind(&address).await.map_err(Error::from)?;
+    tracing::info!("Metrics server listening on http://{}", address);
+    axum::serve(listener, router).await.map_err(Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_encode() {
+        METRICS
+            .jsonrpc_requests
+            .with_label_values(&["tools/call", "success"])
+            .inc();
+        let body = METRICS.encode().unwrap();
+        assert!(body.contains("nmcp_jsonrpc_requests_total"));
+        assert!(body.contains("method=\"tools/call\""));
+    }
+
+    #[test]
+    fn test_method_label() {
+        assert_eq!(method_label("tools/call"), "tools/call");
+        assert_eq!(
+            method_label("notifications/cancelled"),
+            "notifications/cancelled"
+        );
+        assert_eq!(method_label("tools/call/0001"), "other");
+        assert_eq!(method_label(""), "other");
+    }
+}
@@ -1,14 +1,18 @@
+mod auth;
 mod constants;
 mod error;
 mod get_kube_client;
+mod metrics;
 mod serialize;
 mod system_status;
 mod tracing;
 mod transport;
 
+pub use auth::*;
 pub use constants::*;
 pub use error::*;
 pub use get_kube_client::*;
+pub use metrics::*;
 pub use serialize::*;
 pub use system_status::*;
 pub use tracing::*;
@@ -1,4 +1,9 @@
+use crate::{Error, Result};
 use clap::{Parser, ValueEnum};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
 use tracing::{level_filters::LevelFilter, Level};
 use tracing_subscriber::{
     filter,
@@ -43,10 +48,51 @@ pub struct TracingOptions {
     /// Show backtraces for errors
     #[arg(long, global = true)]
     pub show_backtrace: bool,
+
+    /// Export the traces to an OpenTelemetry collector at the given OTLP/gRPC endpoint
+    #[arg(long, global = true, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    pub otlp_endpoint: Option<String>,
+
+    /// The service name the traces are exported with
+    #[arg(long, global = true, env = "OTEL_SERVICE_NAME", default_value = "nmcp")]
+    pub otlp_service_name: String,
+}
+
+/// Keeps the OpenTelemetry exporter alive, and flushes the pending traces once dropped.
+#[derive(Debug)]
+pub struct TracingGuard {
+    provider: Option<SdkTracerProvider>,
+}
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            if let Err(error) = provider.shutdown() {
+                eprintln!("Failed to flush the traces: {error}");
+            }
+        }
+    }
 }
 
-/// Install tracing with the provided configuration options
-pub fn install_tracing(options: &TracingOptions) {
+/// Create the tracer provider exporting the spans to the OTLP endpoint.
+fn create_tracer_provider(options: &TracingOptions, endpoint: &str) -> Result<SdkTracerProvider> {
+    let exporter = SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .map_err(|error| Error::generic(error.to_string()).with_name("E_OTLP_EXPORTER"))?;
+    let resource = Resource::builder()
+        .with_service_name(options.otlp_service_name.clone())
+        .build();
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource)
+        .build())
+}
+
+/// Install tracing with the provided configuration options. The returned guard must be kept
+/// for as long as the process runs, so the traces are exported until then.
+pub fn install_tracing(options: &TracingOptions) -> Result<TracingGuard> {
     // Convert log level string to LevelFilter
     let level = match options.log_level.to_lowercase().as_str() {
         "off" => LevelFilter::OFF,
@@ -104,10 +150,23 @@ pub fn install_tracing(options: &TracingOptions) {
 
     let targets = filter::Targets::new().with_target("nmcp", Level::TRACE);
 
+    // Export the spans to the OpenTelemetry collector, if any.
+    let provider = match &options.otlp_endpoint {
+        Some(endpoint) => Some(create_tracer_provider(options, endpoint)?),
+        None => None,
+    };
+    let telemetry = provider.as_ref().map(|provider| {
+        let tracer = provider.tracer(options.otlp_service_name.clone());
+        tracing_opentelemetry::layer().with_tracer(tracer)
+    });
+
     // Initialize the tracing subscriber with the configured layers
     tracing_subscriber::registry()
         .with(formatter)
+        .with(telemetry)
         .with(targets)
         .with(filter)
         .init();
+
+    Ok(TracingGuard { provider })
 }
@@ -1,5 +1,7 @@
 mod formatter_detailed;
 mod install;
+mod propagation;
 
 pub use formatter_detailed::*;
 pub use install::*;
+pub use propagation::*;
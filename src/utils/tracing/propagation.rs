@@ -0,0 +1,129 @@
+use axum::extract::Request;
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::Response;
+use opentelemetry::propagation::{Extractor, Injector, TextMapPropagator};
+use opentelemetry::Context;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use rmcp::model::{ClientJsonRpcMessage, GetMeta, JsonRpcMessage, Meta};
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Reads the W3C trace context from the headers of an HTTP request.
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Writes the W3C trace context into the `_meta` of a JSON-RPC message.
+struct MetaInjector<'a>(&'a mut Meta);
+
+impl Injector for MetaInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        let _ = self.0 .0.insert(key.to_string(), value.into());
+    }
+}
+
+/// Extract the W3C trace context (`traceparent` and `tracestate`) from the headers of an
+/// HTTP request. Returns an empty context if the headers do not carry any.
+pub fn extract_trace_context(headers: &HeaderMap) -> Context {
+    TraceContextPropagator::new().extract(&HeaderExtractor(headers))
+}
+
+/// Inject the trace context of the current span into the `_meta` of a JSON-RPC request or
+/// notification, so the MCP server can continue the trace of the client. Other messages are
+/// left untouched.
+pub fn inject_trace_context(message: &mut ClientJsonRpcMessage) {
+    let meta = match message {
+        JsonRpcMessage::Request(request) => request.request.get_meta_mut(),
+        JsonRpcMessage::Notification(notification) => notification.notification.get_meta_mut(),
+        _ => return,
+    };
+    let context = tracing::Span::current().context();
+    TraceContextPropagator::new().inject_context(&context, &mut MetaInjector(meta));
+}
+
+/// Middleware continuing the trace of the client, if any, for the handling of the request.
+pub async fn propagate_trace_context(request: Request, next: Next) -> Response {
+    let span = tracing::info_span!(
+        "HTTP",
+        method = %request.method(),
+        path = %request.uri().path(),
+    );
+    span.set_parent(extract_trace_context(request.headers()));
+    next.run(request).instrument(span).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    #[test]
+    fn test_trace_context_is_propagated_to_meta() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("test"));
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        let message = tracing::subscriber::with_default(subscriber, || {
+            let mut headers = HeaderMap::new();
+            let traceparent = format!("00-{TRACE_ID}-00f067aa0ba902b7-01");
+            let _ = headers.insert("traceparent", HeaderValue::from_str(&traceparent).unwrap());
+
+            // --- Forward a message from within a span continuing the trace of the client.
+            let span = tracing::info_span!("request");
+            span.set_parent(extract_trace_context(&headers));
+            let _guard = span.enter();
+            let mut message: ClientJsonRpcMessage = serde_json::from_value(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "tools/call",
+                "params": { "name": "echo", "arguments": {} }
+            }))
+            .unwrap();
+            inject_trace_context(&mut message);
+            message
+        });
+
+        // --- The message carries the trace of the client.
+        let message = serde_json::to_value(&message).unwrap();
+        let traceparent = message
+            .pointer("/params/_meta/traceparent")
+            .and_then(|value| value.as_str())
+            .unwrap();
+        assert!(traceparent.starts_with(&format!("00-{TRACE_ID}-")));
+
+        // --- The span of the gateway is exported as part of the same trace.
+        let spans = exporter.get_finished_spans().unwrap();
+        let span = spans.first().unwrap();
+        assert_eq!(span.span_context.trace_id().to_string(), TRACE_ID);
+    }
+
+    #[test]
+    fn test_inject_trace_context_skips_responses() {
+        let mut message: ClientJsonRpcMessage = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": {}
+        }))
+        .unwrap();
+        inject_trace_context(&mut message);
+        let message = serde_json::to_value(&message).unwrap();
+        assert!(message.pointer("/result/_meta").is_none());
+    }
+}
@@ -1,23 +1,36 @@
-use crate::{Error, MCPServer, MCPServerTransport, Result};
-use kube::Client;
+use crate::{MCPServer, MCPServerTransport, Result};
+use k8s_openapi::api::core::v1;
+use kube::{Client, ResourceExt};
 use std::fmt::Debug;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
+mod transport_handshake;
 mod transport_peer;
+mod transport_router;
+mod transport_sse;
 mod transport_stdio;
+mod transport_streamable_http;
 
+pub use transport_handshake::*;
 pub use transport_peer::*;
+pub use transport_router::*;
+pub use transport_sse::*;
 pub use transport_stdio::*;
+pub use transport_streamable_http::*;
 
 pub enum TransportInner {
     AttachedProcess(TransportAttachedProcess),
+    Sse(TransportSse),
+    StreamableHttp(TransportStreamableHttp),
 }
 
 impl Debug for TransportInner {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::AttachedProcess(_) => write!(f, "TransportInner::AttachedProcess"),
+            Self::Sse(_) => write!(f, "TransportInner::Sse"),
+            Self::StreamableHttp(_) => write!(f, "TransportInner::StreamableHttp"),
         }
     }
 }
@@ -30,29 +43,67 @@ pub struct Transport {
 }
 
 impl Transport {
-    pub fn new(client: &Client, server: &MCPServer) -> Result<Self> {
-        match server.clone().spec.transport {
+    pub fn new(client: &Client, server: &MCPServer, max_message_size: usize) -> Result<Self> {
+        let transport = match server.clone().spec.transport {
             // --- Create a new transport that will proxy the pod's TTY to a BroadcastStream.
             // --- This will allow us to send and receive messages from the pod via SSE.
             MCPServerTransport::Stdio => {
-                let transport = TransportAttachedProcess::new(client, server);
-                let transport = TransportInner::AttachedProcess(transport);
-                let transport = Arc::new(RwLock::new(transport));
-                let transport = Self {
-                    inner: transport,
-                    created_at: Instant::now(),
-                    last_accessed: Arc::new(RwLock::new(Instant::now())),
-                };
-                Ok(transport)
+                let transport = TransportAttachedProcess::new(client, server)
+                    .with_max_message_size(max_message_size);
+                TransportInner::AttachedProcess(transport)
             }
 
-            // --- Create a new transport that will proxy the pod's HTTP stream to a BroadcastStream.
+            // --- Create a new transport that will proxy the server's SSE stream, exposed
+            // --- through its service, to a BroadcastStream.
             MCPServerTransport::Sse { .. } => {
-                Err(Error::generic("SSE transport is not supported yet"))
+                let transport = TransportSse::new(server);
+                TransportInner::Sse(transport)
             }
-            MCPServerTransport::StreamableHttp { .. } => Err(Error::generic(
-                "Streamable HTTP transport is not supported yet",
-            )),
+
+            // --- Create a new transport that will post messages to the server's MCP endpoint,
+            // --- exposed through its service, and fan the responses out to a BroadcastStream.
+            MCPServerTransport::StreamableHttp { .. } => {
+                let transport = TransportStreamableHttp::new(server);
+                TransportInner::StreamableHttp(transport)
+            }
+        };
+
+        Ok(Self::from_inner(transport))
+    }
+
+    /// Create a transport connected to the ephemeral pod of an isolated session of the server,
+    /// rather than to the shared pod of the server.
+    pub fn for_session(
+        client: &Client,
+        server: &MCPServer,
+        pod: &v1::Pod,
+        max_message_size: usize,
+    ) -> Result<Self> {
+        let transport = match server.clone().spec.transport {
+            MCPServerTransport::Stdio => {
+                let transport = TransportAttachedProcess::new(client, server)
+                    .with_pod(pod.name_any())
+                    .with_max_message_size(max_message_size);
+                TransportInner::AttachedProcess(transport)
+            }
+            MCPServerTransport::Sse { .. } => {
+                let transport = TransportSse::new(server).with_url(server.session_url(pod));
+                TransportInner::Sse(transport)
+            }
+            MCPServerTransport::StreamableHttp { .. } => {
+                let transport =
+                    TransportStreamableHttp::new(server).with_url(server.session_url(pod));
+                TransportInner::StreamableHttp(transport)
+            }
+        };
+        Ok(Self::from_inner(transport))
+    }
+
+    fn from_inner(transport: TransportInner) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(transport)),
+            created_at: Instant::now(),
+            last_accessed: Arc::new(RwLock::new(Instant::now())),
         }
     }
 
@@ -80,6 +131,8 @@ impl Transport {
         self.touch().await;
         match &mut *self.inner.write().await {
             TransportInner::AttachedProcess(transport) => transport.subscribe().await,
+            TransportInner::Sse(transport) => transport.subscribe().await,
+            TransportInner::StreamableHttp(transport) => transport.subscribe().await,
         }
     }
 
@@ -87,12 +140,26 @@ impl Transport {
         self.touch().await;
         match &*self.inner.read().await {
             TransportInner::AttachedProcess(transport) => transport.get_peer(&id).await,
+            TransportInner::Sse(transport) => transport.get_peer(&id).await,
+            TransportInner::StreamableHttp(transport) => transport.get_peer(&id).await,
+        }
+    }
+
+    /// Close the peer with the given ID and remove it from the transport, typically when
+    /// the client explicitly terminates its session.
+    pub async fn remove_peer(&self, id: String) -> Result<()> {
+        match &*self.inner.read().await {
+            TransportInner::AttachedProcess(transport) => transport.remove_peer(&id).await,
+            TransportInner::Sse(transport) => transport.remove_peer(&id).await,
+            TransportInner::StreamableHttp(transport) => transport.remove_peer(&id).await,
         }
     }
 
     pub async fn close(&mut self) -> Result<()> {
         match &mut *self.inner.write().await {
             TransportInner::AttachedProcess(transport) => transport.close().await,
+            TransportInner::Sse(transport) => transport.close().await,
+            TransportInner::StreamableHttp(transport) => transport.close().await,
         }
     }
 }
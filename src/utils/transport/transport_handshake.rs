@@ -0,0 +1,295 @@
+use super::TransportPeer;
+use crate::{Error, Result, DEFAULT_TRANSPORT_CONNECT_TIMEOUT};
+use rmcp::model::{
+    ClientJsonRpcMessage, ClientNotification, ClientRequest, JsonObject, JsonRpcMessage,
+    JsonRpcNotification, JsonRpcRequest, JsonRpcResponse, JsonRpcVersion2_0, NumberOrString,
+};
+use std::time::Duration;
+use tokio::sync::{broadcast, Mutex};
+
+/// The ID of the `initialize` requests sent by the gateway itself. It is a string so that the
+/// responses are never mistaken for the responses to the requests of the peers.
+const HANDSHAKE_REQUEST_ID: &str = "nmcp-initialize";
+
+/// The outcome of a message going through the handshake of a transport.
+#[derive(Debug)]
+pub enum TransportHandshakeOutcome {
+    /// The message is not part of the handshake and must be forwarded to the server.
+    Forward(ClientJsonRpcMessage),
+
+    /// The message was answered by the handshake.
+    Answered(JsonRpcMessage),
+
+    /// The message was consumed by the handshake and must not be forwarded.
+    Consumed,
+}
+
+/// The `initialize` request that was used to initialize the process, and its result.
+#[derive(Debug, Clone)]
+struct TransportHandshakeState {
+    request: ClientJsonRpcMessage,
+    result: JsonObject,
+}
+
+/// Performs the `initialize` handshake once for all the peers sharing a single process. The
+/// first `initialize` request is forwarded to the process and its result is cached, the ones
+/// of the following sessions are answered from the cache. The `notifications/initialized` of
+/// the clients are replaced by the one sent by the gateway once the process is initialized.
+///
+/// The cached result holds the `protocolVersion` the process agreed on with the first client,
+/// which is the only version the process speaks, so the following sessions are answered with
+/// it whatever version they requested, and disconnect if they do not support it.
+#[derive(Debug, Default)]
+pub struct TransportHandshake {
+    state: Mutex<Option<TransportHandshakeState>>,
+}
+
+/// Returns whether the message is an `initialize` request.
+fn is_initialize(message: &ClientJsonRpcMessage) -> bool {
+    matches!(
+        message,
+        JsonRpcMessage::Request(JsonRpcRequest {
+            request: ClientRequest::InitializeRequest(_),
+            ..
+        })
+    )
+}
+
+/// Returns whether the message is an `initialized` notification.
+fn is_initialized(message: &ClientJsonRpcMessage) -> bool {
+    matches!(
+        message,
+        JsonRpcMessage::Notification(JsonRpcNotification {
+            notification: ClientNotification::InitializedNotification(_),
+            ..
+        })
+    )
+}
+
+/// Returns the `notifications/initialized` notification sent once the process is initialized.
+fn initialized_notification() -> Result<ClientJsonRpcMessage> {
+    let message = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "notifications/initialized"
+    });
+    Ok(serde_json::from_value(message)?)
+}
+
+/// Returns the response to an `initialize` request from a cached result.
+fn initialize_response(id: NumberOrString, result: JsonObject) -> JsonRpcMessage {
+    JsonRpcMessage::Response(JsonRpcResponse {
+        jsonrpc: JsonRpcVersion2_0,
+        id,
+        result,
+    })
+}
+
+impl TransportHandshake {
+    /// Check if the process has been initialized.
+    pub async fn is_initialized(&self) -> bool {
+        self.state.lock().await.is_some()
+    }
+
+    /// Handle a message sent by a peer. The `initialize` requests are forwarded through the
+    /// peer only if the process is not initialized yet, and answered from the cache otherwise.
+    pub async fn intercept(
+        &self,
+        peer: &TransportPeer,
+        message: ClientJsonRpcMessage,
+    ) -> Result<TransportHandshakeOutcome> {
+        if is_initialized(&message) {
+            return Ok(TransportHandshakeOutcome::Consumed);
+        }
+        if !is_initialize(&message) {
+            return Ok(TransportHandshakeOutcome::Forward(message));
+        }
+        let Some((_, id)) = message.clone().into_request() else {
+            return Ok(TransportHandshakeOutcome::Forward(message));
+        };
+
+        // --- Hold the lock while the process is initialized, so that concurrent sessions
+        // --- wait for the first handshake to complete instead of starting their own. The
+        // --- exchange is bounded so that a process that never answers does not hold the
+        // --- lock forever, the lock is released as soon as it fails.
+        let mut state = self.state.lock().await;
+        if let Some(state) = state.as_ref() {
+            let response = initialize_response(id, state.result.clone());
+            return Ok(TransportHandshakeOutcome::Answered(response));
+        }
+        let timeout = Duration::from_secs(DEFAULT_TRANSPORT_CONNECT_TIMEOUT);
+        let response = tokio::time::timeout(timeout, peer.exchange(message.clone(), id))
+            .await
+            .map_err(|_| {
+                Error::generic("Server did not answer the initialize request in time")
+                    .with_name("E_SERVER_INITIALIZE")
+            })??;
+        if let JsonRpcMessage::Response(result) = &response {
+            let _ = peer
+                .send_message_to_server(initialized_notification()?)
+                .await?;
+            *state = Some(TransportHandshakeState {
+                request: message,
+                result: result.result.clone(),
+            });
+        }
+        Ok(TransportHandshakeOutcome::Answered(response))
+    }
+
+    /// Initialize a new process with the `initialize` request of the previous one, typically
+    /// after the pod of the server restarted, so the sessions of the peers remain usable.
+    /// Does nothing if the previous process was never initialized.
+    pub async fn reinitialize(
+        &self,
+        tx: &broadcast::Sender<ClientJsonRpcMessage>,
+        mut rx: broadcast::Receiver<JsonRpcMessage>,
+    ) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let Some(current) = state.as_mut() else {
+            return Ok(());
+        };
+        let id = NumberOrString::String(HANDSHAKE_REQUEST_ID.into());
+        let mut request = current.request.clone();
+        if let JsonRpcMessage::Request(request) = &mut request {
+            request.id = id.clone();
+        }
+        tracing::info!("Initializing the restarted server process");
+        let _ = tx.send(request)?;
+
+        // --- Wait for the process to answer the request, skipping every other message.
+        let timeout = Duration::from_secs(DEFAULT_TRANSPORT_CONNECT_TIMEOUT);
+        let result = tokio::time::timeout(timeout, async {
+            loop {
+                match rx.recv().await {
+                    Ok(JsonRpcMessage::Response(response)) if response.id == id => {
+                        return Ok(response.result);
+                    }
+                    Ok(JsonRpcMessage::Error(error)) if error.id == id => {
+                        return Err(Error::generic(error.error.message.to_string())
+                            .with_name("E_SERVER_INITIALIZE"));
+                    }
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(error) => return Err(Error::from(error)),
+                }
+            }
+        })
+        .await??;
+        let _ = tx.send(initialized_notification()?)?;
+        current.result = result;
+        Ok(())
+    }
+
+    /// Forget the handshake, so the next `initialize` request is forwarded to the process.
+    pub async fn reset(&self) {
+        *self.state.lock().await = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn message(value: serde_json::Value) -> ClientJsonRpcMessage {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_handshake_messages_are_detected() {
+        let initialize = message(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2025-03-26",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "1.0.0" }
+            }
+        }));
+        let initialized = message(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized"
+        }));
+        assert!(is_initialize(&initialize));
+        assert!(!is_initialized(&initialize));
+        assert!(is_initialized(&initialized));
+        assert!(is_initialized(&initialized_notification().unwrap()));
+    }
+
+    fn initialize(id: u32, capabilities: serde_json::Value) -> ClientJsonRpcMessage {
+        message(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2025-03-26",
+                "capabilities": capabilities,
+                "clientInfo": { "name": "test", "version": "1.0.0" }
+            }
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_initialize_is_answered_from_cache() {
+        let handshake = TransportHandshake::default();
+        let initialize = |id: u32| initialize(id, serde_json::json!({}));
+        let result = serde_json::json!({ "serverInfo": { "name": "server" } });
+        *handshake.state.lock().await = Some(TransportHandshakeState {
+            request: initialize(1),
+            result: result.as_object().cloned().unwrap(),
+        });
+
+        // --- The `initialize` request of a second session is answered with its own ID.
+        let peer = TransportPeer::new(Arc::default());
+        let outcome = handshake.intercept(&peer, initialize(7)).await.unwrap();
+        let TransportHandshakeOutcome::Answered(JsonRpcMessage::Response(response)) = outcome
+        else {
+            panic!("Expected the cached response");
+        };
+        assert_eq!(response.id, NumberOrString::Number(7));
+        assert_eq!(serde_json::Value::Object(response.result), result);
+    }
+
+    #[tokio::test]
+    async fn test_initialize_with_other_capabilities_is_answered_from_cache() {
+        let handshake = TransportHandshake::default();
+        let result = serde_json::json!({
+            "protocolVersion": "2025-03-26",
+            "serverInfo": { "name": "server" }
+        });
+        *handshake.state.lock().await = Some(TransportHandshakeState {
+            request: initialize(1, serde_json::json!({})),
+            result: result.as_object().cloned().unwrap(),
+        });
+
+        // --- A session declaring other capabilities and requesting another version is
+        // --- answered with the version the process agreed on.
+        let peer = TransportPeer::new(Arc::default());
+        let message = message(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2024-11-05",
+                "capabilities": { "sampling": {}, "roots": {} },
+                "clientInfo": { "name": "test", "version": "1.0.0" }
+            }
+        }));
+        let outcome = handshake.intercept(&peer, message).await.unwrap();
+        let TransportHandshakeOutcome::Answered(JsonRpcMessage::Response(response)) = outcome
+        else {
+            panic!("Expected the cached response");
+        };
+        assert_eq!(response.id, NumberOrString::Number(7));
+        assert_eq!(response.result["protocolVersion"], "2025-03-26");
+    }
+
+    #[tokio::test]
+    async fn test_reinitialize_without_handshake_is_noop() {
+        let handshake = TransportHandshake::default();
+        let (tx, mut rx) = broadcast::channel(1);
+        let (_, output_rx) = broadcast::channel(1);
+        handshake.reinitialize(&tx, output_rx).await.unwrap();
+        assert!(rx.try_recv().is_err());
+        assert!(!handshake.is_initialized().await);
+    }
+}
@@ -1,13 +1,17 @@
-use crate::{Error, Result, DEFAULT_SSE_CHANNEL_CAPACITY};
+use super::{TransportHandshake, TransportHandshakeOutcome, TransportRouter};
+use crate::{
+    inject_trace_context, method_label, Error, Result, DEFAULT_SSE_CHANNEL_CAPACITY, METRICS,
+};
 use axum::response::sse::Event;
 use axum::response::Sse;
-use futures::{FutureExt, Stream, StreamExt};
+use futures::{Stream, StreamExt};
 use rmcp::model::{
-    ClientJsonRpcMessage, ErrorCode, ErrorData, JsonRpcError, JsonRpcMessage, JsonRpcVersion2_0,
-    NumberOrString,
+    ClientJsonRpcMessage, ErrorCode, ErrorData, JsonRpcBatchRequestItem, JsonRpcBatchResponseItem,
+    JsonRpcError, JsonRpcMessage, JsonRpcVersion2_0, NumberOrString,
 };
 use std::borrow::Cow;
 use std::convert::Infallible;
+use std::future::Future;
 use std::sync::Arc;
 use tokio::sync::{broadcast, RwLock};
 use tokio::task::JoinHandle;
@@ -15,6 +19,64 @@ use tokio_stream::wrappers::BroadcastStream;
 use tracing::Instrument;
 use uuid::Uuid;
 
+/// Returns the `method` label of a JSON-RPC message, `batch` for the batches of requests, or
+/// `response` for the responses sent by the client.
+fn json_rpc_method(message: &ClientJsonRpcMessage) -> &'static str {
+    match message {
+        JsonRpcMessage::Request(request) => method_label(request.request.method()),
+        JsonRpcMessage::Notification(notification) => {
+            method_label(notification.notification.method())
+        }
+        JsonRpcMessage::BatchRequest(_) => "batch",
+        _ => "response",
+    }
+}
+
+/// The message of the error answering the requests whose result will never be received.
+const NO_RESULT_MESSAGE: &str = "Channel closed or no response received";
+
+/// Returns the IDs of the requests of a JSON-RPC message, including the ones of a batch.
+pub fn request_ids(message: &ClientJsonRpcMessage) -> Vec<NumberOrString> {
+    match message {
+        JsonRpcMessage::Request(request) => vec![request.id.clone()],
+        JsonRpcMessage::BatchRequest(items) => items
+            .iter()
+            .filter_map(|item| match item {
+                JsonRpcBatchRequestItem::Request(request) => Some(request.id.clone()),
+                JsonRpcBatchRequestItem::Notification(_) => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Returns an internal error answering the request with the given ID.
+pub fn internal_error(
+    request_id: NumberOrString,
+    message: impl Into<Cow<'static, str>>,
+) -> JsonRpcError {
+    JsonRpcError {
+        id: request_id,
+        jsonrpc: JsonRpcVersion2_0,
+        error: ErrorData {
+            code: ErrorCode(-32603),
+            message: message.into(),
+            data: None,
+        },
+    }
+}
+
+/// Calls a handler once dropped, such as along with the stream owning it.
+struct OnDrop<F: FnOnce()>(Option<F>);
+
+impl<F: FnOnce()> Drop for OnDrop<F> {
+    fn drop(&mut self) {
+        if let Some(handler) = self.0.take() {
+            handler();
+        }
+    }
+}
+
 #[derive(Debug)]
 struct TransportPeerInner {
     pub from_client_tx: broadcast::Sender<ClientJsonRpcMessage>,
@@ -23,18 +85,21 @@ struct TransportPeerInner {
     pub from_server_rx: broadcast::Receiver<JsonRpcMessage>,
     drop_tx: broadcast::Sender<()>,
     drop_rx: broadcast::Receiver<()>,
+    router: Arc<TransportRouter>,
+    handshake: Option<Arc<TransportHandshake>>,
+    is_closed: bool,
     task_attach_input: Option<JoinHandle<()>>,
     task_attach_output: Option<JoinHandle<()>>,
 }
 
 impl Default for TransportPeerInner {
     fn default() -> Self {
-        Self::new()
+        Self::new(Arc::default())
     }
 }
 
 impl TransportPeerInner {
-    pub fn new() -> Self {
+    pub fn new(router: Arc<TransportRouter>) -> Self {
         let (from_client_tx, from_client_rx) = broadcast::channel(DEFAULT_SSE_CHANNEL_CAPACITY);
         let (from_server_tx, from_server_rx) = broadcast::channel(DEFAULT_SSE_CHANNEL_CAPACITY);
         let (drop_tx, drop_rx) = broadcast::channel(1);
@@ -45,6 +110,9 @@ impl TransportPeerInner {
             from_client_rx,
             drop_tx,
             drop_rx,
+            router,
+            handshake: None,
+            is_closed: false,
             task_attach_input: None,
             task_attach_output: None,
         }
@@ -59,7 +127,7 @@ pub struct TransportPeer {
 
 impl Default for TransportPeer {
     fn default() -> Self {
-        Self::new()
+        Self::new(Arc::default())
     }
 }
 
@@ -75,12 +143,13 @@ impl Default for TransportPeer {
 ///
 /// Each peer has a unique ID and maintains internal state protected by an `Arc<RwLock>`.
 /// The peer can be attached to different I/O streams and supports graceful cleanup of
-/// resources when closed.
+/// resources when closed. The peers of a transport share a `TransportRouter`, so that each
+/// peer only receives the responses to its own requests.
 ///
 /// # Examples
 ///
 /// ```rust
-/// let peer = TransportPeer::new();
+/// let peer = TransportPeer::new(router.clone());
 ///
 /// // Attach to various streams
 /// peer.attach_input(stdin_sender).await?;
@@ -93,8 +162,8 @@ impl Default for TransportPeer {
 /// peer.close().await?;
 /// ```
 impl TransportPeer {
-    pub fn new() -> Self {
-        let inner = TransportPeerInner::new();
+    pub fn new(router: Arc<TransportRouter>) -> Self {
+        let inner = TransportPeerInner::new(router);
         let inner = RwLock::new(inner);
         let inner = Arc::new(inner);
         let id = Uuid::new_v4().to_string();
@@ -109,10 +178,17 @@ impl TransportPeer {
                 "Transport peer already has a task to bind to stdin",
             ));
         }
-        let mut rx = { self.inner.read().await.from_client_rx.resubscribe() };
+        let (mut rx, router) = {
+            let inner = self.inner.read().await;
+            (inner.from_client_rx.resubscribe(), inner.router.clone())
+        };
+        let id = self.id.clone();
         let task = tokio::spawn(async move {
             loop {
                 if let Ok(message) = rx.recv().await {
+                    let Some(message) = router.outbound(&id, message) else {
+                        continue;
+                    };
                     if let Err(error) = tx.send(message) {
                         let _ = Error::from(error).trace();
                     }
@@ -132,12 +208,19 @@ impl TransportPeer {
                 "Transport peer already has a task to bind to server",
             ));
         }
-        let tx = { self.inner.read().await.from_server_tx.clone() };
+        let (tx, router) = {
+            let inner = self.inner.read().await;
+            (inner.from_server_tx.clone(), inner.router.clone())
+        };
+        let id = self.id.clone();
         let task = tokio::spawn(
             async move {
                 loop {
                     match rx.recv().await {
                         Ok(message) => {
+                            let Some(message) = router.inbound(&id, message) else {
+                                continue;
+                            };
                             if let Err(error) = tx.send(message) {
                                 let _ = Error::from(error).trace();
                             }
@@ -156,6 +239,11 @@ impl TransportPeer {
         Ok(())
     }
 
+    /// Attach the handshake of a transport whose peers share a single initialized process.
+    pub async fn attach_handshake(&self, handshake: Arc<TransportHandshake>) {
+        self.inner.write().await.handshake = Some(handshake);
+    }
+
     /// Send a message to the the transport.
     pub async fn send_message_to_server(&self, message: ClientJsonRpcMessage) -> Result<usize> {
         self.inner
@@ -183,55 +271,242 @@ impl TransportPeer {
         }
     }
 
-    /// Receive a result from the transport with the given request ID.
+    /// Subscribe to the messages sent by the server to the peer, along with the signal sent
+    /// once the peer is closed. Returns `None` if the peer is already closed.
+    async fn subscribe_results(
+        &self,
+    ) -> Option<(broadcast::Receiver<JsonRpcMessage>, broadcast::Receiver<()>)> {
+        let inner = self.inner.read().await;
+        if inner.is_closed {
+            return None;
+        }
+        Some((
+            inner.from_server_rx.resubscribe(),
+            inner.drop_tx.subscribe(),
+        ))
+    }
+
+    /// Receive a result from the transport with the given request ID. Once the peer is closed,
+    /// the request is answered with an error since its result will never be received.
     pub async fn receive_result(&self, request_id: NumberOrString) -> JsonRpcMessage {
-        let mut rx = self.inner.read().await.from_server_rx.resubscribe();
+        let Some((mut rx, mut drop_rx)) = self.subscribe_results().await else {
+            return JsonRpcMessage::Error(internal_error(request_id, NO_RESULT_MESSAGE));
+        };
+        tokio::spawn(async move {
+            loop {
+                let message = tokio::select! {
+                    message = rx.recv() => message,
+                    _ = drop_rx.recv() => break,
+                };
+                let Ok(message) = message else {
+                    break;
+                };
+                let result_id = match &message {
+                    JsonRpcMessage::Response(response) => &response.id,
+                    JsonRpcMessage::Error(error) => &error.id,
+                    _ => continue,
+                };
+                if *result_id == request_id {
+                    return message;
+                }
+            }
+            JsonRpcMessage::Error(internal_error(request_id, NO_RESULT_MESSAGE))
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Receive the results from the transport of every request of a batch, whether the server
+    /// answers them one by one or as a batch, and return them as a single batch response.
+    pub async fn receive_batch_results(
+        &self,
+        mut request_ids: Vec<NumberOrString>,
+    ) -> JsonRpcMessage {
+        let subscription = self.subscribe_results().await;
         tokio::spawn(async move {
-            while let Ok(message) = rx.recv().await {
-                if let Some((_, result_id)) = message.clone().into_response() {
-                    if result_id == request_id {
-                        return message;
+            let mut results = Vec::with_capacity(request_ids.len());
+            if let Some((mut rx, mut drop_rx)) = subscription {
+                while !request_ids.is_empty() {
+                    let message = tokio::select! {
+                        message = rx.recv() => message,
+                        _ = drop_rx.recv() => break,
+                    };
+                    let items = match message {
+                        Ok(JsonRpcMessage::Response(response)) => {
+                            vec![JsonRpcBatchResponseItem::Response(response)]
+                        }
+                        Ok(JsonRpcMessage::Error(error)) => {
+                            vec![JsonRpcBatchResponseItem::Error(error)]
+                        }
+                        Ok(JsonRpcMessage::BatchResponse(items)) => items,
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    };
+                    for item in items {
+                        let result_id = match &item {
+                            JsonRpcBatchResponseItem::Response(response) => &response.id,
+                            JsonRpcBatchResponseItem::Error(error) => &error.id,
+                        };
+                        if let Some(index) = request_ids.iter().position(|id| id == result_id) {
+                            let _ = request_ids.swap_remove(index);
+                            results.push(item);
+                        }
                     }
                 }
             }
-            JsonRpcMessage::Error(JsonRpcError {
-                id: request_id,
-                jsonrpc: JsonRpcVersion2_0,
-                error: ErrorData {
-                    code: ErrorCode(-32603),
-                    message: Cow::Borrowed("Channel closed or no response received"),
-                    data: None,
-                },
-            })
+
+            // --- Answer the requests left unanswered once the peer or the channel closed
+            // --- with an error.
+            results.extend(request_ids.into_iter().map(|request_id| {
+                JsonRpcBatchResponseItem::Error(internal_error(request_id, NO_RESULT_MESSAGE))
+            }));
+            JsonRpcMessage::BatchResponse(results)
         })
         .await
         .unwrap()
     }
 
-    pub async fn send_request(
+    /// Send a request to the server and wait for its result. Note that we wait for the result
+    /// from the server before sending the request, ensuring that we dont miss broadcast
+    /// messages since we're `resubscribing`.
+    pub async fn exchange(
+        &self,
+        message: ClientJsonRpcMessage,
+        request_id: NumberOrString,
+    ) -> Result<JsonRpcMessage> {
+        let future = self.receive_result(request_id);
+        self.observe_exchange(message, future).await
+    }
+
+    /// Send a batch of requests to the server and wait for the results of all of them.
+    pub async fn exchange_batch(
         &self,
         message: ClientJsonRpcMessage,
+        request_ids: Vec<NumberOrString>,
+    ) -> Result<JsonRpcMessage> {
+        let future = self.receive_batch_results(request_ids);
+        self.observe_exchange(message, future).await
+    }
+
+    /// Send a message to the server and wait for the future receiving its results, recording
+    /// the latency of the exchange and its outcome.
+    async fn observe_exchange(
+        &self,
+        message: ClientJsonRpcMessage,
+        future: impl Future<Output = JsonRpcMessage>,
+    ) -> Result<JsonRpcMessage> {
+        let method = json_rpc_method(&message);
+        let timer = METRICS
+            .upstream_latency
+            .with_label_values(&[method])
+            .start_timer();
+        if let Err(error) = self.send_message_to_server(message).await {
+            let _ = timer.stop_and_discard();
+            METRICS
+                .jsonrpc_requests
+                .with_label_values(&[method, "failed"])
+                .inc();
+            return Err(error);
+        }
+
+        // --- Record the latency and whether the server answered with an error.
+        let result = future.await;
+        timer.observe_duration();
+        let outcome = match &result {
+            JsonRpcMessage::Error(_) => "error",
+            JsonRpcMessage::BatchResponse(items)
+                if items
+                    .iter()
+                    .any(|item| matches!(item, JsonRpcBatchResponseItem::Error(_))) =>
+            {
+                "error"
+            }
+            _ => "success",
+        };
+        METRICS
+            .jsonrpc_requests
+            .with_label_values(&[method, outcome])
+            .inc();
+        Ok(result)
+    }
+
+    pub async fn send_request(
+        &self,
+        mut message: ClientJsonRpcMessage,
     ) -> Result<Option<JsonRpcMessage>> {
-        match message.clone().into_request() {
-            // --- Message is a request, note that we wait for the result
-            // --- from the server before sending the request, ensuring
-            // --- that we dont miss broadcast messages since we're `resubscribing`
-            Some((_, request_id)) => {
-                let future = self.receive_result(request_id);
-                let _ = self.send_message_to_server(message).await?;
-                Ok(Some(future.await))
+        inject_trace_context(&mut message);
+
+        // --- Let the handshake of the transport answer the `initialize` requests of the
+        // --- sessions sharing a process that was already initialized.
+        let handshake = self.inner.read().await.handshake.clone();
+        let message = match handshake {
+            None => message,
+            Some(handshake) => match handshake.intercept(self, message).await? {
+                TransportHandshakeOutcome::Forward(message) => message,
+                TransportHandshakeOutcome::Answered(result) => return Ok(Some(result)),
+                TransportHandshakeOutcome::Consumed => return Ok(None),
+            },
+        };
+
+        let request_ids = request_ids(&message);
+        match message {
+            // --- Message is a request, wait for the result from the server.
+            JsonRpcMessage::Request(ref request) => {
+                let request_id = request.id.clone();
+                self.exchange(message, request_id).await.map(Some)
+            }
+
+            // --- Message is a batch holding requests, wait for the results of all of them
+            // --- and answer with a batch, as the client expects.
+            JsonRpcMessage::BatchRequest(_) if !request_ids.is_empty() => {
+                self.exchange_batch(message, request_ids).await.map(Some)
             }
 
-            // --- Message is not a request, skip early since we won't receive a response.
-            None => Ok(None),
+            // --- Message is a notification or a response, forward it to the server
+            // --- but return early since we won't receive a response.
+            _ => {
+                let method = json_rpc_method(&message);
+                let _ = self.send_message_to_server(message).await?;
+                METRICS
+                    .jsonrpc_requests
+                    .with_label_values(&[method, "accepted"])
+                    .inc();
+                Ok(None)
+            }
         }
     }
 
-    /// Return the SSE stream for the peer.
+    /// Return a stream of every message sent by the server to the peer. The stream
+    /// ends once the peer is closed from the server-side.
+    pub async fn messages(&self) -> impl Stream<Item = JsonRpcMessage> {
+        // --- Wrap the `drop` in a `BroadcastStream` so that we can append
+        // --- it to the stream and ensure that the stream is closed when
+        // --- the peer is dropped from the server-side.
+        let drop_rx = self.inner.read().await.drop_rx.resubscribe();
+        let drop_stream = BroadcastStream::new(drop_rx).into_future();
+
+        // --- Create a `BroadcastStream` from the peer's receiver. Lagging
+        // --- receivers skip the messages they missed instead of closing.
+        let rx = self.inner.read().await.from_server_rx.resubscribe();
+        BroadcastStream::new(rx)
+            .filter_map(|message| async move { message.ok() })
+            .take_until(drop_stream)
+    }
+
+    /// Return a stream of SSE events carrying every message sent by the server to the peer.
+    pub async fn events(&self) -> impl Stream<Item = core::result::Result<Event, Infallible>> {
+        self.messages().await.filter_map(|message| async move {
+            let data = serde_json::to_string(&message).ok()?;
+            Some(Ok(Event::default().event("message").data(data)))
+        })
+    }
+
+    /// Return the SSE stream for the peer. The `on_close` handler is called once the stream is
+    /// dropped, either because the peer was closed or because the client disconnected.
     pub async fn sse(
         self,
         endpoint: String,
-        on_close: impl FnOnce() -> JoinHandle<Result<()>> + Send + 'static,
+        on_close: impl FnOnce() + Send + 'static,
     ) -> Sse<impl Stream<Item = core::result::Result<Event, Infallible>>> {
         let endpoint = format!("{endpoint}?sessionId={}", self.id);
         tracing::debug!("Creating SSE stream with id {}", self.id);
@@ -243,32 +518,21 @@ impl TransportPeer {
             Event::default().event("endpoint").data(endpoint),
         ));
 
-        // --- Wrap the `drop` in a `BroadcastStream` so that we can append
-        // --- it to the SSE stream and ensure that the stream is closed when
-        // --- the peer is dropped from the server-side.
-        let drop_rx = self.inner.read().await.drop_rx.resubscribe();
-        let drop_stream = BroadcastStream::new(drop_rx).into_future().then(async |_| {
+        // --- Call the `on_close` handler once the stream is dropped. When the client
+        // --- disconnects, the stream is dropped by the server without being polled
+        // --- to its end, so the handler cannot simply be chained after the events.
+        let guard = OnDrop(Some(move || {
             tracing::info!("SSE stream for peer closed");
-            let _ = on_close().await;
-        });
-
-        // --- Create a `BroadcastStream` from the peer's receiver that will send
-        // --- every message as an SSE event. This will allow us to stream.
-        let rx = self.inner.read().await.from_server_rx.resubscribe();
-        let stream = BroadcastStream::new(rx)
-            .map(|message| {
-                Ok::<Event, Infallible>(
-                    Event::default()
-                        .event("message")
-                        .data(serde_json::to_string(&message.unwrap()).unwrap()),
-                )
-            })
-            .take_until(drop_stream);
+            on_close();
+        }));
 
-        // --- Chain the two streams together, so that the first stream sends the
+        // --- Chain the streams together, so that the first stream sends the
         // --- endpoint URL and the second stream sends the messages from the
-        // --- STDOUT of the pod.
-        let stream = endpoint.chain(stream);
+        // --- server until the peer is closed.
+        let stream = endpoint.chain(self.events().await).map(move |event| {
+            let _ = &guard;
+            event
+        });
         Sse::new(stream)
     }
 
@@ -282,7 +546,121 @@ impl TransportPeer {
         if let Some(task) = inner.task_attach_output.take() {
             task.abort();
         }
+        inner.router.release(&self.id);
+        inner.is_closed = true;
         let _ = inner.drop_tx.send(());
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn message(value: serde_json::Value) -> ClientJsonRpcMessage {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_json_rpc_method() {
+        let request = message(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": "echo" }
+        }));
+        let notification = message(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized"
+        }));
+        let response = message(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": {} }));
+        assert_eq!(json_rpc_method(&request), "tools/call");
+        assert_eq!(json_rpc_method(&notification), "notifications/initialized");
+        assert_eq!(json_rpc_method(&response), "response");
+    }
+
+    #[test]
+    fn test_request_ids() {
+        let batch = message(serde_json::json!([
+            { "jsonrpc": "2.0", "id": 1, "method": "ping" },
+            { "jsonrpc": "2.0", "method": "notifications/initialized" },
+            { "jsonrpc": "2.0", "id": "b", "method": "ping" }
+        ]));
+        let notification = message(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized"
+        }));
+        assert_eq!(
+            request_ids(&batch),
+            vec![
+                NumberOrString::Number(1),
+                NumberOrString::String("b".into())
+            ]
+        );
+        assert!(request_ids(&notification).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_receive_batch_results() {
+        let peer = TransportPeer::default();
+        let request_ids = vec![NumberOrString::Number(1), NumberOrString::Number(2)];
+        let future = peer.receive_batch_results(request_ids);
+        tokio::pin!(future);
+        assert!(futures::poll!(&mut future).is_pending());
+
+        // --- The server answers one request alone and the other in a batch, along with the
+        // --- result of a request that is not part of the batch.
+        let tx = peer.inner.read().await.from_server_tx.clone();
+        let response: JsonRpcMessage =
+            serde_json::from_value(serde_json::json!({ "jsonrpc": "2.0", "id": 2, "result": {} }))
+                .unwrap();
+        let batch: JsonRpcMessage = serde_json::from_value(serde_json::json!([
+            { "jsonrpc": "2.0", "id": 3, "result": {} },
+            { "jsonrpc": "2.0", "id": 1, "error": { "code": -32601, "message": "Not found" } }
+        ]))
+        .unwrap();
+        let _ = tx.send(response).unwrap();
+        let _ = tx.send(batch).unwrap();
+        let JsonRpcMessage::BatchResponse(items) = future.await else {
+            panic!("Expected a batch response");
+        };
+        assert_eq!(items.len(), 2);
+        assert!(
+            matches!(&items[0], JsonRpcBatchResponseItem::Response(r) if r.id == NumberOrString::Number(2))
+        );
+        assert!(
+            matches!(&items[1], JsonRpcBatchResponseItem::Error(e) if e.id == NumberOrString::Number(1))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pending_results_fail_once_closed() {
+        let peer = TransportPeer::default();
+        let future = peer.receive_result(NumberOrString::Number(1));
+        tokio::pin!(future);
+        assert!(futures::poll!(&mut future).is_pending());
+
+        // --- The pending request fails once the peer is closed, and so do the later ones.
+        peer.close().await.unwrap();
+        assert!(matches!(future.await, JsonRpcMessage::Error(_)));
+        let result = peer.receive_result(NumberOrString::Number(2)).await;
+        assert!(matches!(result, JsonRpcMessage::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn test_sse_calls_on_close_once_dropped() {
+        let peer = TransportPeer::default();
+        let is_released = Arc::new(AtomicBool::new(false));
+        let on_close = {
+            let is_released = is_released.clone();
+            move || is_released.store(true, Ordering::SeqCst)
+        };
+
+        // --- The client disconnects while the peer is still open.
+        let stream = peer.clone().sse("/message".to_string(), on_close).await;
+        assert!(!is_released.load(Ordering::SeqCst));
+        drop(stream);
+        assert!(is_released.load(Ordering::SeqCst));
+    }
+}
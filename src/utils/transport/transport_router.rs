@@ -0,0 +1,539 @@
+use rmcp::model::{
+    ClientJsonRpcMessage, ClientNotification, ClientRequest, ErrorData, GetMeta,
+    JsonRpcBatchRequestItem, JsonRpcBatchResponseItem, JsonRpcError, JsonRpcMessage,
+    JsonRpcNotification, JsonRpcRequest, JsonRpcVersion2_0, NumberOrString, ProgressToken,
+};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+/// The method of the notifications reporting the progress of a request.
+const PROGRESS_NOTIFICATION_METHOD: &str = "notifications/progress";
+
+/// A request of a peer forwarded to the server and waiting for a response.
+#[derive(Debug)]
+struct PendingRequest {
+    /// The peer that sent the request.
+    peer_id: String,
+
+    /// The ID of the request, as sent by the peer.
+    id: NumberOrString,
+
+    /// The progress token of the request, as sent by the peer.
+    progress_token: Option<ProgressToken>,
+}
+
+/// The state of a `TransportRouter`, guarded by a single lock so that the routing decisions
+/// are consistent across the peers.
+#[derive(Debug)]
+struct TransportRouterState {
+    /// The next ID allocated to a request forwarded to the server.
+    next_id: u32,
+
+    /// The requests of the peers waiting for a response, by their upstream ID.
+    pending: HashMap<u32, PendingRequest>,
+
+    /// The requests initiated by the server waiting for a response, with the peer they
+    /// were delivered to.
+    server_requests: HashMap<NumberOrString, String>,
+
+    /// The peer that sent the most recent request to the server.
+    last_peer: Option<String>,
+}
+
+/// Routes the JSON-RPC messages exchanged between the peers of a transport and the single
+/// upstream session they share with the server.
+///
+/// Clients pick their request IDs independently, so two sessions may both send `id: 1` to the
+/// same process. The router rewrites the ID of every request on its way to the server with an
+/// ID unique to the transport, and remembers which peer sent it under which original ID. The
+/// responses of the server are then only delivered to the peer that sent the request, with the
+/// original ID restored. Progress tokens are rewritten the same way, so the progress of a request
+/// is only reported to the peer that sent it, and cancellations are translated to the upstream
+/// ID of the request they cancel.
+///
+/// Requests initiated by the server, such as `sampling/createMessage` or `roots/list`, are
+/// delivered to the peer whose request is being handled by the server, and only the response
+/// of that peer is forwarded back to the server. Requests that cannot be attributed to a single
+/// peer are answered with an error instead of being delivered to the wrong one.
+#[derive(Debug)]
+pub struct TransportRouter {
+    state: Mutex<TransportRouterState>,
+}
+
+impl Default for TransportRouter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransportRouter {
+    pub fn new() -> Self {
+        let state = TransportRouterState {
+            // --- Start at 1 since the transports answer with `id: 0` the errors that are not
+            // --- related to any request, which must never be mistaken for a response.
+            next_id: 1,
+            pending: HashMap::new(),
+            server_requests: HashMap::new(),
+            last_peer: None,
+        };
+        Self {
+            state: Mutex::new(state),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, TransportRouterState> {
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Allocate an upstream ID for a request of the peer. The progress token of the request,
+    /// if any, is replaced by the same upstream ID. Once the IDs wrap around, the IDs of the
+    /// requests still pending are skipped, so a response is never delivered to the wrong peer.
+    fn rewrite(&self, peer_id: &str, request: &mut JsonRpcRequest<ClientRequest>) {
+        let mut state = self.state();
+        let mut upstream_id = state.next_id;
+        while state.pending.contains_key(&upstream_id) {
+            upstream_id = upstream_id.wrapping_add(1).max(1);
+        }
+        state.next_id = upstream_id.wrapping_add(1).max(1);
+        let progress_token = request.request.get_meta().get_progress_token();
+        if progress_token.is_some() {
+            let token = ProgressToken(NumberOrString::Number(upstream_id));
+            request.request.get_meta_mut().set_progress_token(token);
+        }
+        let pending = PendingRequest {
+            peer_id: peer_id.to_string(),
+            id: std::mem::replace(&mut request.id, NumberOrString::Number(upstream_id)),
+            progress_token,
+        };
+        let _ = state.pending.insert(upstream_id, pending);
+        state.last_peer = Some(peer_id.to_string());
+    }
+
+    /// Return the original ID of a response if it answers a request of the peer.
+    fn restore(&self, peer_id: &str, id: &NumberOrString) -> Option<NumberOrString> {
+        let NumberOrString::Number(upstream_id) = id else {
+            return None;
+        };
+        let mut state = self.state();
+        match state.pending.get(upstream_id) {
+            Some(pending) if pending.peer_id == peer_id => {
+                state.pending.remove(upstream_id).map(|pending| pending.id)
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the upstream ID of a pending request of the peer, from its original ID.
+    fn upstream_id(&self, peer_id: &str, id: &NumberOrString) -> Option<NumberOrString> {
+        self.state()
+            .pending
+            .iter()
+            .find(|(_, pending)| pending.peer_id == peer_id && pending.id == *id)
+            .map(|(upstream_id, _)| NumberOrString::Number(*upstream_id))
+    }
+
+    /// Return the original progress token of a pending request of the peer, from the token
+    /// it was forwarded to the server with.
+    fn restore_progress_token(
+        &self,
+        peer_id: &str,
+        token: &ProgressToken,
+    ) -> Option<ProgressToken> {
+        let NumberOrString::Number(upstream_id) = &token.0 else {
+            return None;
+        };
+        match self.state().pending.get(upstream_id) {
+            Some(pending) if pending.peer_id == peer_id => pending.progress_token.clone(),
+            _ => None,
+        }
+    }
+
+    /// Check if the response of a peer to a request of the server must be forwarded. Only the
+    /// peer the request was delivered to may answer it. Responses to unknown requests are
+    /// forwarded as they are.
+    fn accept(&self, peer_id: &str, id: &NumberOrString) -> bool {
+        let mut state = self.state();
+        match state.server_requests.get(id) {
+            Some(owner) if owner == peer_id => state.server_requests.remove(id).is_some(),
+            Some(_) => false,
+            None => true,
+        }
+    }
+
+    /// Translate the cancellation of a request of the peer to the upstream ID of the request.
+    /// Other notifications are forwarded as they are.
+    fn cancel(
+        &self,
+        peer_id: &str,
+        mut notification: JsonRpcNotification<ClientNotification>,
+    ) -> Option<JsonRpcNotification<ClientNotification>> {
+        if let ClientNotification::CancelledNotification(cancelled) = &mut notification.notification
+        {
+            cancelled.params.request_id =
+                self.upstream_id(peer_id, &cancelled.params.request_id)?;
+        }
+        Some(notification)
+    }
+
+    /// Assign a request initiated by the server to a peer, before it is broadcast to the peers
+    /// of the transport. If the transport knows which request of a peer the server is handling,
+    /// such as the request whose response stream carried it, the request is assigned to the
+    /// peer that sent it. Otherwise it is assigned to the only peer waiting for a response, or
+    /// else to the last peer that sent a request.
+    ///
+    /// When requests of several peers are waiting for a response, the request cannot be
+    /// attributed to any of them. It must then not be delivered, and the returned error must be
+    /// sent to the server instead.
+    pub fn assign(
+        &self,
+        message: &JsonRpcMessage,
+        origin: Option<&NumberOrString>,
+    ) -> Option<ClientJsonRpcMessage> {
+        let JsonRpcMessage::Request(request) = message else {
+            return None;
+        };
+        let mut state = self.state();
+        let origin = match origin {
+            Some(NumberOrString::Number(upstream_id)) => state.pending.get(upstream_id),
+            _ => None,
+        };
+        let owner = match origin {
+            Some(pending) => Some(pending.peer_id.clone()),
+            None => {
+                let mut peers = state.pending.values().map(|pending| &pending.peer_id);
+                match peers.next() {
+                    Some(peer_id) if peers.all(|other| other == peer_id) => Some(peer_id.clone()),
+                    Some(_) => {
+                        return Some(JsonRpcMessage::Error(JsonRpcError {
+                            jsonrpc: JsonRpcVersion2_0,
+                            id: request.id.clone(),
+                            error: ErrorData::internal_error(
+                                "Request cannot be attributed to a single client session",
+                                None,
+                            ),
+                        }))
+                    }
+                    None => state.last_peer.clone(),
+                }
+            }
+        };
+        if let Some(owner) = owner {
+            let _ = state.server_requests.insert(request.id.clone(), owner);
+        }
+        None
+    }
+
+    /// Prepare a message sent by a peer to be forwarded to the server, rewriting the IDs of the
+    /// requests it contains. Returns `None` if the message must not be forwarded, such as the
+    /// response to a request of the server that was delivered to another peer, or the
+    /// cancellation of a request that is not pending.
+    pub fn outbound(
+        &self,
+        peer_id: &str,
+        message: ClientJsonRpcMessage,
+    ) -> Option<ClientJsonRpcMessage> {
+        match message {
+            JsonRpcMessage::Request(mut request) => {
+                self.rewrite(peer_id, &mut request);
+                Some(JsonRpcMessage::Request(request))
+            }
+            JsonRpcMessage::Notification(notification) => self
+                .cancel(peer_id, notification)
+                .map(JsonRpcMessage::Notification),
+            JsonRpcMessage::BatchRequest(items) => {
+                let items: Vec<_> = items
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        JsonRpcBatchRequestItem::Request(mut request) => {
+                            self.rewrite(peer_id, &mut request);
+                            Some(JsonRpcBatchRequestItem::Request(request))
+                        }
+                        JsonRpcBatchRequestItem::Notification(notification) => self
+                            .cancel(peer_id, notification)
+                            .map(JsonRpcBatchRequestItem::Notification),
+                    })
+                    .collect();
+                (!items.is_empty()).then_some(JsonRpcMessage::BatchRequest(items))
+            }
+            JsonRpcMessage::Response(response) => self
+                .accept(peer_id, &response.id)
+                .then_some(JsonRpcMessage::Response(response)),
+            JsonRpcMessage::Error(error) => self
+                .accept(peer_id, &error.id)
+                .then_some(JsonRpcMessage::Error(error)),
+            JsonRpcMessage::BatchResponse(items) => {
+                let items: Vec<_> = items
+                    .into_iter()
+                    .filter(|item| match item {
+                        JsonRpcBatchResponseItem::Response(response) => {
+                            self.accept(peer_id, &response.id)
+                        }
+                        JsonRpcBatchResponseItem::Error(error) => self.accept(peer_id, &error.id),
+                    })
+                    .collect();
+                (!items.is_empty()).then_some(JsonRpcMessage::BatchResponse(items))
+            }
+        }
+    }
+
+    /// Filter a message sent by the server for a peer. Responses are only kept if they answer
+    /// a request of the peer, with their original ID restored, and so are the progress
+    /// notifications of its requests. Requests initiated by the server are only kept by the peer
+    /// they were assigned to, and other notifications by every peer.
+    pub fn inbound(&self, peer_id: &str, message: JsonRpcMessage) -> Option<JsonRpcMessage> {
+        match message {
+            JsonRpcMessage::Notification(mut notification)
+                if notification.notification.method == PROGRESS_NOTIFICATION_METHOD =>
+            {
+                let params = &mut notification.notification.params;
+                let token = params.get("progressToken").cloned()?;
+                let token = serde_json::from_value(token).ok()?;
+                let token = self.restore_progress_token(peer_id, &token)?;
+                let _ = params.insert(
+                    "progressToken".to_string(),
+                    serde_json::to_value(token).ok()?,
+                );
+                Some(JsonRpcMessage::Notification(notification))
+            }
+            JsonRpcMessage::Request(request) => {
+                let is_delivered = match self.state().server_requests.get(&request.id) {
+                    Some(owner) => owner == peer_id,
+                    None => true,
+                };
+                is_delivered.then_some(JsonRpcMessage::Request(request))
+            }
+            JsonRpcMessage::Response(mut response) => {
+                response.id = self.restore(peer_id, &response.id)?;
+                Some(JsonRpcMessage::Response(response))
+            }
+            JsonRpcMessage::Error(mut error) => {
+                error.id = self.restore(peer_id, &error.id)?;
+                Some(JsonRpcMessage::Error(error))
+            }
+            JsonRpcMessage::BatchResponse(items) => {
+                let items: Vec<_> = items
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        JsonRpcBatchResponseItem::Response(mut response) => {
+                            response.id = self.restore(peer_id, &response.id)?;
+                            Some(JsonRpcBatchResponseItem::Response(response))
+                        }
+                        JsonRpcBatchResponseItem::Error(mut error) => {
+                            error.id = self.restore(peer_id, &error.id)?;
+                            Some(JsonRpcBatchResponseItem::Error(error))
+                        }
+                    })
+                    .collect();
+                (!items.is_empty()).then_some(JsonRpcMessage::BatchResponse(items))
+            }
+            message => Some(message),
+        }
+    }
+
+    /// Forget the pending requests of a peer, typically once it is closed.
+    pub fn release(&self, peer_id: &str) {
+        let mut state = self.state();
+        state
+            .pending
+            .retain(|_, pending| pending.peer_id != peer_id);
+        state.server_requests.retain(|_, owner| owner != peer_id);
+        if state.last_peer.as_deref() == Some(peer_id) {
+            state.last_peer = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(id: u32) -> ClientJsonRpcMessage {
+        serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "ping"
+        }))
+        .unwrap()
+    }
+
+    fn server_request(id: &NumberOrString) -> JsonRpcMessage {
+        serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "sampling/createMessage",
+            "params": {}
+        }))
+        .unwrap()
+    }
+
+    fn response<T: serde::de::DeserializeOwned>(id: &NumberOrString) -> T {
+        serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {}
+        }))
+        .unwrap()
+    }
+
+    fn request_id(message: &ClientJsonRpcMessage) -> NumberOrString {
+        message.clone().into_request().unwrap().1
+    }
+
+    #[test]
+    fn test_responses_are_delivered_to_their_peer() {
+        let router = TransportRouter::new();
+        let a = router.outbound("a", request(1)).unwrap();
+        let b = router.outbound("b", request(1)).unwrap();
+        assert_ne!(request_id(&a), request_id(&b));
+
+        // --- The response to the request of `b` is only delivered to `b`, with its ID.
+        let message: JsonRpcMessage = response(&request_id(&b));
+        assert!(router.inbound("a", message.clone()).is_none());
+        let message = router.inbound("b", message).unwrap();
+        assert_eq!(
+            message.into_response().unwrap().1,
+            NumberOrString::Number(1)
+        );
+
+        // --- Responses are only delivered once.
+        assert!(router.inbound("b", response(&request_id(&b))).is_none());
+    }
+
+    #[test]
+    fn test_notifications_are_delivered_to_every_peer() {
+        let router = TransportRouter::new();
+        let message: JsonRpcMessage = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/tools/list_changed",
+            "params": {}
+        }))
+        .unwrap();
+        assert!(router.inbound("a", message.clone()).is_some());
+        assert!(router.inbound("b", message).is_some());
+    }
+
+    #[test]
+    fn test_server_requests_are_routed_to_the_requesting_peer() {
+        let router = TransportRouter::new();
+        let _ = router.outbound("a", request(1)).unwrap();
+        let b = router.outbound("b", request(1)).unwrap();
+
+        // --- The server asks for a sampling on the response stream of the request of `b`.
+        let id = NumberOrString::Number(42);
+        let message = server_request(&id);
+        assert!(router.assign(&message, Some(&request_id(&b))).is_none());
+        assert!(router.inbound("a", message.clone()).is_none());
+        assert!(router.inbound("b", message).is_some());
+
+        // --- Only the response of `b` is forwarded to the server.
+        assert!(router.outbound("a", response(&id)).is_none());
+        assert!(router.outbound("b", response(&id)).is_some());
+    }
+
+    #[test]
+    fn test_server_requests_are_routed_to_the_only_waiting_peer() {
+        let router = TransportRouter::new();
+        let _ = router.outbound("a", request(1)).unwrap();
+        let b = router.outbound("b", request(1)).unwrap();
+        let _ = router.inbound("b", response(&request_id(&b))).unwrap();
+
+        // --- Only `a` is waiting for a response, so the server is acting on its behalf.
+        let message = server_request(&NumberOrString::Number(42));
+        assert!(router.assign(&message, None).is_none());
+        assert!(router.inbound("a", message.clone()).is_some());
+        assert!(router.inbound("b", message).is_none());
+    }
+
+    #[test]
+    fn test_ambiguous_server_requests_are_rejected() {
+        let router = TransportRouter::new();
+        let _ = router.outbound("a", request(1)).unwrap();
+        let _ = router.outbound("b", request(1)).unwrap();
+
+        // --- Both peers are waiting for a response, so the server is answered with an error.
+        let id = NumberOrString::Number(42);
+        let Some(JsonRpcMessage::Error(error)) = router.assign(&server_request(&id), None) else {
+            panic!("Expected an error for the server");
+        };
+        assert_eq!(error.id, id);
+    }
+
+    #[test]
+    fn test_cancellations_target_the_upstream_request() {
+        let router = TransportRouter::new();
+        let _ = router.outbound("a", request(1)).unwrap();
+        let b = router.outbound("b", request(1)).unwrap();
+        let cancel = |id: u32| -> ClientJsonRpcMessage {
+            serde_json::from_value(serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "notifications/cancelled",
+                "params": { "requestId": id }
+            }))
+            .unwrap()
+        };
+
+        // --- The cancellation of `b` targets the upstream ID of its own request.
+        let message = router.outbound("b", cancel(1)).unwrap();
+        let message = serde_json::to_value(message).unwrap();
+        let request_id = serde_json::to_value(request_id(&b)).unwrap();
+        assert_eq!(message.pointer("/params/requestId"), Some(&request_id));
+
+        // --- Cancelling a request that is not pending is not forwarded.
+        assert!(router.outbound("b", cancel(2)).is_none());
+    }
+
+    #[test]
+    fn test_progress_is_delivered_to_the_requesting_peer() {
+        let router = TransportRouter::new();
+        let message: ClientJsonRpcMessage = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "tools/call",
+            "params": { "name": "fetch", "_meta": { "progressToken": "token" } }
+        }))
+        .unwrap();
+        let message = router.outbound("a", message).unwrap();
+        let message = serde_json::to_value(message).unwrap();
+        let token = message.pointer("/params/_meta/progressToken").cloned();
+        assert_ne!(token, Some(serde_json::json!("token")));
+
+        // --- The progress is only delivered to `a`, with its original token.
+        let progress: JsonRpcMessage = serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/progress",
+            "params": { "progressToken": token, "progress": 1 }
+        }))
+        .unwrap();
+        assert!(router.inbound("b", progress.clone()).is_none());
+        let progress = router.inbound("a", progress).unwrap();
+        let progress = serde_json::to_value(progress).unwrap();
+        assert_eq!(
+            progress.pointer("/params/progressToken"),
+            Some(&serde_json::json!("token"))
+        );
+    }
+
+    #[test]
+    fn test_pending_ids_are_skipped_after_wrapping() {
+        let router = TransportRouter::new();
+        let a = router.outbound("a", request(1)).unwrap();
+        assert_eq!(request_id(&a), NumberOrString::Number(1));
+        router.state().next_id = u32::MAX;
+
+        // --- The IDs wrap around to 1, which is still pending for `a`.
+        let b = router.outbound("b", request(1)).unwrap();
+        let c = router.outbound("c", request(1)).unwrap();
+        assert_eq!(request_id(&b), NumberOrString::Number(u32::MAX));
+        assert_eq!(request_id(&c), NumberOrString::Number(2));
+        assert!(router.inbound("c", response(&request_id(&a))).is_none());
+        assert!(router.inbound("a", response(&request_id(&a))).is_some());
+    }
+
+    #[test]
+    fn test_release_forgets_pending_requests() {
+        let router = TransportRouter::new();
+        let a = router.outbound("a", request(1)).unwrap();
+        router.release("a");
+        assert!(router.inbound("a", response(&request_id(&a))).is_none());
+    }
+}
@@ -0,0 +1,515 @@
+use super::{TransportHandshake, TransportPeer, TransportRouter};
+use crate::{Error, MCPServer, Result};
+use crate::{
+    DEFAULT_POD_BUFFER_SIZE, DEFAULT_SSE_ENDPOINT_PATH, DEFAULT_TRANSPORT_CONNECT_TIMEOUT,
+};
+use axum::http::StatusCode;
+use futures::StreamExt;
+use kube::ResourceExt;
+use reqwest::header::{ACCEPT, CACHE_CONTROL};
+use reqwest::Url;
+use rmcp::model;
+use sse_stream::SseStream;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch, RwLock};
+use tokio::task::JoinHandle;
+
+/// Returns the errors answering the requests of a message that could not be delivered to the
+/// server, with their IDs. Notifications and responses expect no answer, so `None` is returned
+/// for them and their failure is only logged.
+pub(super) fn delivery_error(
+    message: model::ClientJsonRpcMessage,
+    error: &Error,
+) -> Option<model::JsonRpcMessage> {
+    let reply = |id| model::JsonRpcError {
+        error: model::ErrorData::internal_error(error.to_string(), None),
+        id,
+        jsonrpc: model::JsonRpcVersion2_0,
+    };
+    match message {
+        model::JsonRpcMessage::Request(request) => {
+            Some(model::JsonRpcMessage::Error(reply(request.id)))
+        }
+        model::JsonRpcMessage::BatchRequest(items) => {
+            let items: Vec<_> = items
+                .into_iter()
+                .filter_map(|item| match item {
+                    model::JsonRpcBatchRequestItem::Request(request) => {
+                        Some(model::JsonRpcBatchResponseItem::Error(reply(request.id)))
+                    }
+                    model::JsonRpcBatchRequestItem::Notification(_) => None,
+                })
+                .collect();
+            (!items.is_empty()).then_some(model::JsonRpcMessage::BatchResponse(items))
+        }
+        _ => None,
+    }
+}
+
+/// Post a single client message to the message endpoint announced by the server.
+async fn post_message(
+    http: &reqwest::Client,
+    endpoint: Option<Url>,
+    message: &model::ClientJsonRpcMessage,
+) -> Result<()> {
+    let Some(endpoint) = endpoint else {
+        return Err(Error::generic("Server SSE session is not established"));
+    };
+    let _ = http
+        .post(endpoint)
+        .json(message)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// A transport for communicating with a server exposing the HTTP+SSE transport. The peers of
+/// the transport share the single session of the SSE stream, initialized once by the handshake
+/// of the transport, so that the `initialize` request of a client never reaches the session
+/// the other clients are using.
+pub struct TransportSse {
+    server: MCPServer,
+    /// The base URL of the server, its `Service` by default or the `Pod` of an isolated session.
+    url: Option<String>,
+    http: reqwest::Client,
+    peers: Arc<RwLock<HashMap<String, TransportPeer>>>,
+    router: Arc<TransportRouter>,
+    handshake: Arc<TransportHandshake>,
+
+    endpoint_rx: watch::Receiver<Option<Url>>,
+    endpoint_tx: watch::Sender<Option<Url>>,
+    input_rx: broadcast::Receiver<model::ClientJsonRpcMessage>,
+    input_tx: broadcast::Sender<model::ClientJsonRpcMessage>,
+    output_rx: broadcast::Receiver<model::JsonRpcMessage>,
+    output_tx: broadcast::Sender<model::JsonRpcMessage>,
+
+    task_attach_stream: Option<JoinHandle<Result<()>>>,
+    task_attach_input: Option<JoinHandle<Result<()>>>,
+}
+
+impl Debug for TransportSse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransportSse")
+            .field("server", &self.server)
+            .field("endpoint", &*self.endpoint_rx.borrow())
+            .field("task", &self.task_attach_stream)
+            .field(
+                "peers",
+                &self.peers.try_read().map(|peers| peers.len()).ok(),
+            )
+            .finish()
+    }
+}
+
+impl TransportSse {
+    pub fn new(server: &MCPServer) -> Self {
+        let (endpoint_tx, endpoint_rx) = watch::channel(None);
+        let (input_tx, input_rx) = broadcast::channel(DEFAULT_POD_BUFFER_SIZE);
+        let (output_tx, output_rx) = broadcast::channel(DEFAULT_POD_BUFFER_SIZE);
+        Self {
+            server: server.clone(),
+            url: server.service_url(),
+            http: reqwest::Client::new(),
+            peers: Arc::new(RwLock::new(HashMap::new())),
+            router: Arc::default(),
+            handshake: Arc::default(),
+            endpoint_tx,
+            endpoint_rx,
+            input_tx,
+            input_rx,
+            output_tx,
+            output_rx,
+            task_attach_stream: None,
+            task_attach_input: None,
+        }
+    }
+
+    /// Connect to the server at the given base URL rather than through its `Service`, such as
+    /// the ephemeral pod of an isolated session.
+    pub fn with_url(mut self, url: Option<String>) -> Self {
+        self.url = url;
+        self
+    }
+
+    /// Open the SSE stream exposed by the server.
+    #[tracing::instrument(name = "ConnectToServer", skip_all)]
+    async fn connect_to_server(&self) -> Result<reqwest::Response> {
+        let url = self.url.clone().ok_or_else(|| {
+            Error::generic("Server transport does not expose a port to connect to")
+        })?;
+
+        self.http
+            .get(format!("{url}{DEFAULT_SSE_ENDPOINT_PATH}"))
+            .header(ACCEPT, "text/event-stream")
+            .header(CACHE_CONTROL, "no-cache")
+            .send()
+            .await?
+            .error_for_status()
+            .map_err(Error::from)
+    }
+
+    /// Attach to the SSE stream of the server. The first `endpoint` event announces the URL
+    /// that messages must be posted to, every following `message` event is a JSON-RPC message.
+    async fn attach_stream(&mut self, response: reqwest::Response) -> JoinHandle<Result<()>> {
+        let tx = self.output_tx.clone();
+        let input_tx = self.input_tx.clone();
+        let router = self.router.clone();
+        let endpoint_tx = self.endpoint_tx.clone();
+        let base = response.url().clone();
+        tokio::spawn(async move {
+            let mut stream = Box::pin(SseStream::from_bytes_stream(response.bytes_stream()));
+            while let Some(event) = stream.next().await {
+                let event = event.map_err(|error| Error::generic(error.to_string()))?;
+                let data = event.data.unwrap_or_default();
+                match event.event.as_deref() {
+                    // --- The endpoint may either be an absolute URL or a path relative
+                    // --- to the SSE stream URL, `Url::join` handles both cases for us.
+                    Some("endpoint") => {
+                        let endpoint = base
+                            .join(data.trim())
+                            .map_err(|error| Error::generic(error.to_string()))?;
+                        tracing::debug!("Server announced message endpoint {}", endpoint);
+                        let _ = endpoint_tx.send(Some(endpoint));
+                    }
+                    Some("message") | None => {
+                        if data.trim().is_empty() {
+                            continue;
+                        }
+                        match serde_json::from_str::<model::JsonRpcMessage>(&data) {
+                            Ok(message) => {
+                                if let Some(reply) = router.assign(&message, None) {
+                                    let _ = input_tx.send(reply);
+                                    continue;
+                                }
+                                let _ = tx.send(message).map_err(Error::from)?;
+                            }
+                            Err(error) => {
+                                tracing::warn!("Ignoring malformed message from server: {}", error);
+                            }
+                        }
+                    }
+                    Some(event) => {
+                        tracing::debug!("Ignoring unknown SSE event '{}'", event);
+                    }
+                }
+            }
+
+            tracing::info!("Server SSE stream closed, stopping stream task");
+            let _ = endpoint_tx.send(None);
+            Ok(())
+        })
+    }
+
+    /// Attach to the input channel and post every message to the server's message endpoint.
+    /// Every message is posted from its own task, so that a slow request does not hold back
+    /// the messages of the other peers.
+    async fn attach_input(&mut self) -> JoinHandle<Result<()>> {
+        let tx = self.output_tx.clone();
+        let http = self.http.clone();
+        let endpoint_rx = self.endpoint_rx.clone();
+        let mut rx = self.input_rx.resubscribe();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(message) => {
+                        let tx = tx.clone();
+                        let http = http.clone();
+                        let endpoint = endpoint_rx.borrow().clone();
+                        let _task = tokio::spawn(async move {
+                            let result = post_message(&http, endpoint, &message).await;
+
+                            // --- If the message could not be delivered, answer its requests with an error
+                            // --- so that the client does not wait for a response that will never come.
+                            if let Err(error) = result {
+                                tracing::error!("Failed to post message to server: {}", error);
+                                if let Some(message) = delivery_error(message, &error) {
+                                    let _ = tx.send(message).map_err(Error::from);
+                                }
+                            }
+                        });
+                    }
+                    Err(error) => match error {
+                        broadcast::error::RecvError::Lagged(_) => {
+                            tracing::warn!("Input receiver lagged, some messages may be lost");
+                        }
+                        broadcast::error::RecvError::Closed => {
+                            tracing::info!("Input receiver closed, stopping input task");
+                            return Ok(());
+                        }
+                    },
+                }
+            }
+        })
+    }
+
+    #[tracing::instrument(name = "IsAttached", skip_all)]
+    async fn is_attached(&self) -> bool {
+        let is_stream_attached = self
+            .task_attach_stream
+            .as_ref()
+            .is_some_and(|task| !task.is_finished());
+        let is_input_attached = self
+            .task_attach_input
+            .as_ref()
+            .is_some_and(|task| !task.is_finished());
+        let is_endpoint_known = self.endpoint_rx.borrow().is_some();
+        is_stream_attached && is_input_attached && is_endpoint_known
+    }
+
+    #[tracing::instrument(name = "BindStreams", skip_all)]
+    async fn bind_streams(&mut self) -> Result<&mut Self> {
+        if self.is_attached().await {
+            return Ok(self);
+        }
+        if let Some(task) = self.task_attach_stream.take() {
+            task.abort();
+        }
+        if let Some(task) = self.task_attach_input.take() {
+            task.abort();
+        }
+        let _ = self.endpoint_tx.send(None);
+
+        // --- Open the SSE stream and attach the input channel to the transport.
+        let response = self.connect_to_server().await?;
+        self.task_attach_stream = Some(self.attach_stream(response).await);
+        self.task_attach_input = Some(self.attach_input().await);
+
+        // --- Wait for the server to announce the endpoint messages must be posted to,
+        // --- otherwise the first messages of the peers would have nowhere to go.
+        let mut endpoint_rx = self.endpoint_rx.clone();
+        let timeout = Duration::from_secs(DEFAULT_TRANSPORT_CONNECT_TIMEOUT);
+        let _ = tokio::time::timeout(timeout, endpoint_rx.wait_for(Option::is_some))
+            .await?
+            .map_err(|_| {
+                Error::generic("Server SSE stream closed before announcing its endpoint")
+            })?;
+
+        // --- The new stream is a new session of the server, initialize it with the request
+        // --- of the handshake so that the sessions of the peers remain usable.
+        if self.handshake.is_initialized().await {
+            let output_rx = self.output_rx.resubscribe();
+            if let Err(error) = self.handshake.reinitialize(&self.input_tx, output_rx).await {
+                let _ = error.trace();
+                self.handshake.reset().await;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Get a peer by ID.
+    #[tracing::instrument(name = "GetPeer", skip_all)]
+    pub async fn get_peer(&self, id: &String) -> Result<TransportPeer> {
+        match self.peers.read().await.get(id) {
+            Some(peer) => Ok(peer.clone()),
+            None => Err(Error::generic(format!("Session with ID {id} not found"))
+                .with_name("E_SESSION_NOT_FOUND")
+                .with_status(StatusCode::NOT_FOUND)),
+        }
+    }
+
+    /// Close the peer with the given ID and remove it from the transport.
+    #[tracing::instrument(name = "RemovePeer", skip_all)]
+    pub async fn remove_peer(&self, id: &String) -> Result<()> {
+        let peer = self.peers.write().await.remove(id);
+        match peer {
+            Some(peer) => peer.close().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Create a new peer connected to the SSE session of the server.
+    #[tracing::instrument(name = "Subscribe", skip_all, fields(name = self.server.name_any()))]
+    pub async fn subscribe(&mut self) -> Result<TransportPeer> {
+        let _ = self.bind_streams().await?;
+
+        // --- Create a new peer for the transport.
+        let peer = {
+            let peer = TransportPeer::new(self.router.clone());
+            let id = peer.id.clone();
+            let mut peers = self.peers.write().await;
+            let _ = peers.insert(id, peer.clone());
+            drop(peers);
+            peer
+        };
+
+        // --- Connect the input and output channels to the peer, and let the handshake of the
+        // --- transport answer its `initialize` request once the session is initialized.
+        peer.attach_input(self.input_tx.clone()).await?;
+        peer.attach_output(self.output_rx.resubscribe()).await?;
+        peer.attach_handshake(self.handshake.clone()).await;
+
+        Ok(peer)
+    }
+
+    /// Close the transport and all its peers.
+    #[tracing::instrument(name = "Close", skip_all)]
+    pub async fn close(&mut self) -> Result<()> {
+        if let Some(task) = self.task_attach_stream.take() {
+            task.abort();
+        }
+        if let Some(task) = self.task_attach_input.take() {
+            task.abort();
+        }
+        let _ = self.endpoint_tx.send(None);
+
+        // --- Close all peers. This will ensure that all underlying channels and
+        // --- streams are properly closed and cleaned up.
+        let mut peers = self.peers.write().await;
+        for peer in peers.values() {
+            peer.close().await?;
+        }
+        peers.clear();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MCPServerSpec;
+    use axum::extract::State;
+    use axum::response::sse::{Event, Sse};
+    use axum::Json;
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio_stream::wrappers::BroadcastStream;
+
+    /// The number of `initialize` requests received by the server, and the channel of the
+    /// messages it sends on its SSE stream.
+    type Upstream = (Arc<AtomicUsize>, broadcast::Sender<serde_json::Value>);
+
+    /// Open the SSE stream of the server, announcing the endpoint messages are posted to.
+    async fn upstream_sse(
+        State((_, tx)): State<Upstream>,
+    ) -> Sse<impl futures::Stream<Item = std::result::Result<Event, Infallible>>> {
+        let endpoint = Event::default().event("endpoint").data("/message");
+        let messages = BroadcastStream::new(tx.subscribe()).filter_map(|message| async move {
+            let message = message.ok()?;
+            Some(Ok(Event::default()
+                .event("message")
+                .data(message.to_string())))
+        });
+        Sse::new(futures::stream::once(async { Ok(endpoint) }).chain(messages))
+    }
+
+    /// Answer the requests posted by the gateway on the SSE stream. Calls of the `slow` tool
+    /// are only acknowledged after a long while, to simulate a long-running tool call.
+    async fn upstream_message(
+        State((initializes, tx)): State<Upstream>,
+        Json(message): Json<serde_json::Value>,
+    ) -> StatusCode {
+        let Some(id) = message.get("id").cloned() else {
+            return StatusCode::ACCEPTED;
+        };
+        let is_slow = message["params"]["name"] == "slow";
+        let result = match message["method"].as_str() {
+            Some("initialize") => {
+                let _ = initializes.fetch_add(1, Ordering::SeqCst);
+                serde_json::json!({
+                    "protocolVersion": "2024-11-05",
+                    "capabilities": {},
+                    "serverInfo": { "name": "upstream", "version": "1.0.0" }
+                })
+            }
+            Some("tools/call") if is_slow => {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                serde_json::json!({})
+            }
+            _ => serde_json::json!({}),
+        };
+        let _ = tx.send(serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+        StatusCode::ACCEPTED
+    }
+
+    fn message(value: serde_json::Value) -> model::ClientJsonRpcMessage {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_delivery_error_answers_requests_only() {
+        let error = Error::generic("Server SSE session is not established");
+        let request = message(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "ping",
+            "params": {}
+        }));
+        let Some(model::JsonRpcMessage::Error(reply)) = delivery_error(request, &error) else {
+            panic!("Expected an error for the request");
+        };
+        assert_eq!(reply.id, model::NumberOrString::Number(7));
+
+        // --- Notifications expect no answer, their failure is only logged.
+        let notification = message(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/cancelled",
+            "params": { "requestId": 7 }
+        }));
+        assert!(delivery_error(notification, &error).is_none());
+    }
+
+    fn initialize(id: u32) -> model::ClientJsonRpcMessage {
+        message(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "1.0.0" }
+            }
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_peers_share_the_upstream_session() {
+        let initializes = Arc::new(AtomicUsize::new(0));
+        let (tx, _) = broadcast::channel(16);
+        let router = axum::Router::new()
+            .route("/sse", axum::routing::get(upstream_sse))
+            .route("/message", axum::routing::post(upstream_message))
+            .with_state((initializes.clone(), tx));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let _server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let server = MCPServer::new("my-server", MCPServerSpec::default());
+        let mut transport = TransportSse::new(&server).with_url(Some(url));
+
+        // --- The clients initialize one after the other, only the first one reaches the server.
+        let a = transport.subscribe().await.unwrap();
+        let response = a.send_request(initialize(1)).await.unwrap().unwrap();
+        assert!(matches!(response, model::JsonRpcMessage::Response(_)));
+        let b = transport.subscribe().await.unwrap();
+        let response = b.send_request(initialize(1)).await.unwrap().unwrap();
+        assert!(matches!(response, model::JsonRpcMessage::Response(_)));
+        assert_eq!(initializes.load(Ordering::SeqCst), 1);
+
+        // --- A slow request of a client does not hold back the requests of the other one.
+        let slow = message(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": { "name": "slow" }
+        }));
+        let _slow = tokio::spawn(async move { a.send_request(slow).await });
+        let ping = message(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "ping",
+            "params": {}
+        }));
+        let response = tokio::time::timeout(Duration::from_secs(5), b.send_request(ping))
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(matches!(response, model::JsonRpcMessage::Response(_)));
+        transport.close().await.unwrap();
+    }
+}
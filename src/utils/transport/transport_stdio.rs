@@ -1,38 +1,190 @@
-use super::TransportPeer;
+use super::transport_sse::delivery_error;
+use super::{TransportHandshake, TransportPeer, TransportRouter};
+use crate::DEFAULT_TRANSPORT_CONNECT_TIMEOUT;
 use crate::{Error, MCPServer, Result, MCP_SERVER_CONTAINER_NAME};
-use crate::{IntoResource, DEFAULT_POD_BUFFER_SIZE};
+use crate::{IntoResource, DEFAULT_MAX_MESSAGE_SIZE, DEFAULT_POD_BUFFER_SIZE};
+use axum::http::StatusCode;
+use futures::StreamExt;
 use k8s_openapi::api::core::v1;
 use kube::api::{AttachParams, AttachedProcess};
-use kube::{Api, Client, ResourceExt};
+use kube::{Client, ResourceExt};
 use rmcp::model;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWriteExt};
 use tokio::sync::{broadcast, RwLock};
 use tokio::task::JoinHandle;
+use tokio_util::codec::{FramedRead, LinesCodec, LinesCodecError};
+
+/// Returns a `notifications/message` logging notification carrying a line printed by the
+/// process that is not a JSON-RPC message, such as a misplaced log line on its stdout or any
+/// line on its stderr. The `logger` is the name of the stream the line was printed on.
+fn log_notification(logger: &str, line: &str) -> model::JsonRpcMessage {
+    let notification = model::Notification {
+        method: "notifications/message".to_string(),
+        params: serde_json::Map::from_iter([
+            ("level".to_string(), serde_json::Value::from("info")),
+            ("logger".to_string(), serde_json::Value::from(logger)),
+            ("data".to_string(), serde_json::Value::from(line)),
+        ]),
+        extensions: model::Extensions::new(),
+    };
+    model::JsonRpcMessage::Notification(model::JsonRpcNotification {
+        jsonrpc: model::JsonRpcVersion2_0,
+        notification,
+    })
+}
+
+/// Handle the lines printed by a process on one of its outputs until it is closed. The output
+/// is split into newline-delimited lines, buffering partial lines across reads. Blank lines are
+/// skipped, and lines exceeding the `max_message_size` are discarded.
+async fn forward_lines<T>(
+    output: T,
+    max_message_size: usize,
+    mut handle: impl FnMut(String) -> Result<()>,
+) -> Result<()>
+where
+    T: AsyncRead + Unpin,
+{
+    let codec = LinesCodec::new_with_max_length(max_message_size);
+    let mut lines = FramedRead::with_capacity(output, codec, DEFAULT_POD_BUFFER_SIZE);
+    let mut is_recovering = false;
+    loop {
+        match lines.next().await {
+            Some(Ok(line)) => {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                handle(line)?;
+            }
+
+            // --- The codec skips the rest of an oversized or malformed line and
+            // --- resumes with the next one, so the transport is kept alive.
+            Some(Err(LinesCodecError::MaxLineLengthExceeded)) => {
+                tracing::warn!(
+                    "Discarding a line larger than {} bytes from the process output",
+                    max_message_size
+                );
+                is_recovering = true;
+            }
+            Some(Err(LinesCodecError::Io(error)))
+                if error.kind() == std::io::ErrorKind::InvalidData =>
+            {
+                tracing::warn!("Discarding a line that is not valid UTF-8: {}", error);
+                is_recovering = true;
+            }
+            Some(Err(LinesCodecError::Io(error))) => {
+                let error = Error::from(error).trace();
+                return Err(error);
+            }
+
+            // --- The stream yields `None` once after a decoding error before
+            // --- resuming, which must not be mistaken for the end of the output.
+            None if is_recovering => is_recovering = false,
+            None => {
+                tracing::info!("Process output stream closed, stopping output task");
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Forward the messages printed by a process on its stdout until it is closed. Lines that are
+/// not JSON-RPC messages are forwarded as logging notifications. Requests of the process that
+/// cannot be attributed to a single peer are answered with an error on its stdin.
+async fn forward_stdout<T>(
+    stdout: T,
+    max_message_size: usize,
+    router: &TransportRouter,
+    stdin: &broadcast::Sender<model::ClientJsonRpcMessage>,
+    tx: &broadcast::Sender<model::JsonRpcMessage>,
+) -> Result<()>
+where
+    T: AsyncRead + Unpin,
+{
+    forward_lines(stdout, max_message_size, |line| {
+        let message = match serde_json::from_str::<model::JsonRpcMessage>(&line) {
+            Ok(message) => message,
+            Err(error) => {
+                tracing::debug!("Process printed a non JSON-RPC line: {}", error);
+                log_notification("stdout", &line)
+            }
+        };
+        if let Some(reply) = router.assign(&message, None) {
+            let _ = stdin.send(reply);
+            return Ok(());
+        }
+        let _ = tx.send(message).map_err(Error::from)?;
+        Ok(())
+    })
+    .await
+}
+
+/// Forward the lines printed by a process on its stderr as logging notifications until it is
+/// closed.
+async fn forward_stderr<T>(
+    stderr: T,
+    max_message_size: usize,
+    tx: &broadcast::Sender<model::JsonRpcMessage>,
+) -> Result<()>
+where
+    T: AsyncRead + Unpin,
+{
+    forward_lines(stderr, max_message_size, |line| {
+        let _ = tx
+            .send(log_notification("stderr", &line))
+            .map_err(Error::from)?;
+        Ok(())
+    })
+    .await
+}
 
 /// A transport for communicating with a process via stdin/stdout
 pub struct TransportAttachedProcess {
     client: Client,
     server: MCPServer,
+    /// The name of the pod to attach to, the pod of the server or the one of an isolated session.
+    pod: String,
+    /// The maximum size of a single message read from the stdout of the process.
+    max_message_size: usize,
     peers: Arc<RwLock<HashMap<String, TransportPeer>>>,
+    router: Arc<TransportRouter>,
+    handshake: Arc<TransportHandshake>,
 
     stdin_rx: broadcast::Receiver<model::ClientJsonRpcMessage>,
     stdin_tx: broadcast::Sender<model::ClientJsonRpcMessage>,
     stdout_rx: broadcast::Receiver<model::JsonRpcMessage>,
     stdout_tx: broadcast::Sender<model::JsonRpcMessage>,
 
+    task_attach: Option<JoinHandle<Result<()>>>,
     task_attach_stdin: Option<JoinHandle<Result<()>>>,
     task_attach_stdout: Option<JoinHandle<Result<()>>>,
     task_attach_stderr: Option<JoinHandle<Result<()>>>,
 }
 
+impl Drop for TransportAttachedProcess {
+    fn drop(&mut self) {
+        for task in [
+            &self.task_attach,
+            &self.task_attach_stdin,
+            &self.task_attach_stdout,
+            &self.task_attach_stderr,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            task.abort();
+        }
+    }
+}
+
 impl Debug for TransportAttachedProcess {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("TransportAttachedProcess")
             .field("server", &self.server)
-            .field("task", &self.task_attach_stdout)
+            .field("task", &self.task_attach)
             .field("peers", &self.peers.blocking_read().len())
             .finish()
     }
@@ -45,11 +197,52 @@ impl TransportAttachedProcess {
         Self {
             client: client.clone(),
             server: server.clone(),
+            pod: <MCPServer as IntoResource<v1::Pod>>::resource_name(server),
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
             peers: Arc::new(RwLock::new(HashMap::new())),
+            router: Arc::default(),
+            handshake: Arc::default(),
             stdin_tx,
             stdin_rx,
             stdout_tx,
             stdout_rx,
+            task_attach: None,
+            task_attach_stdin: None,
+            task_attach_stdout: None,
+            task_attach_stderr: None,
+        }
+    }
+
+    /// Attach to the process of the given pod rather than the pod of the server, such as the
+    /// ephemeral pod of an isolated session.
+    pub fn with_pod(mut self, pod: String) -> Self {
+        self.pod = pod;
+        self
+    }
+
+    /// Set the maximum size of a single message read from the stdout of the process. Larger
+    /// messages are discarded rather than buffered indefinitely.
+    pub fn with_max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    /// Returns a handle on the same process, sharing the channels, peers and handshake of the
+    /// transport, used by the task keeping the process attached.
+    fn handle(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            server: self.server.clone(),
+            pod: self.pod.clone(),
+            max_message_size: self.max_message_size,
+            peers: self.peers.clone(),
+            router: self.router.clone(),
+            handshake: self.handshake.clone(),
+            stdin_rx: self.stdin_rx.resubscribe(),
+            stdin_tx: self.stdin_tx.clone(),
+            stdout_rx: self.stdout_rx.resubscribe(),
+            stdout_tx: self.stdout_tx.clone(),
+            task_attach: None,
             task_attach_stdin: None,
             task_attach_stdout: None,
             task_attach_stderr: None,
@@ -57,92 +250,27 @@ impl TransportAttachedProcess {
     }
 
     /// Attach to the process stdout.
-    async fn attach_stdout<T>(&mut self, mut stdout: T) -> JoinHandle<Result<()>>
+    async fn attach_stdout<T>(&mut self, stdout: T) -> JoinHandle<Result<()>>
     where
-        T: AsyncReadExt + Send + Unpin + 'static,
+        T: AsyncRead + Send + Unpin + 'static,
     {
+        let stdin = self.stdin_tx.clone();
         let tx = self.stdout_tx.clone();
-        tokio::spawn(async move {
-            let mut buffer = vec![0u8; DEFAULT_POD_BUFFER_SIZE];
-            loop {
-                match stdout.read(&mut buffer).await {
-                    Ok(0) => {
-                        tracing::info!("Process stdout stream closed, stopping stdout task");
-                        return Ok(());
-                    }
-                    Ok(size) => {
-                        let data = buffer.get(..size).expect("Failed to get data from buffer");
-                        let data = String::from_utf8_lossy(data).to_string();
-                        for line in data.lines() {
-                            if !line.trim().is_empty() {
-                                let message: model::JsonRpcMessage = serde_json::from_str(line)?;
-                                let _ = tx.send(message).map_err(Error::from)?;
-                            }
-                        }
-                    }
-                    Err(error) => {
-                        let error = Error::from(error).trace();
-                        return Err(error);
-                    }
-                }
-            }
-        })
+        let router = self.router.clone();
+        let max_message_size = self.max_message_size;
+        tokio::spawn(
+            async move { forward_stdout(stdout, max_message_size, &router, &stdin, &tx).await },
+        )
     }
 
     /// Attach to the process stderr.
-    async fn attach_stderr<T>(&mut self, mut stderr: T) -> JoinHandle<Result<()>>
+    async fn attach_stderr<T>(&mut self, stderr: T) -> JoinHandle<Result<()>>
     where
-        T: AsyncReadExt + Send + Unpin + 'static,
+        T: AsyncRead + Send + Unpin + 'static,
     {
         let tx = self.stdout_tx.clone();
-        tokio::spawn(async move {
-            let mut buffer = vec![0u8; DEFAULT_POD_BUFFER_SIZE];
-            loop {
-                match stderr.read(&mut buffer).await {
-                    Ok(0) => {}
-                    Ok(size) => {
-                        let data = buffer.get(..size).expect("Failed to get data from buffer");
-                        let data = String::from_utf8_lossy(data).to_string();
-                        for line in data.lines() {
-                            if !line.trim().is_empty() {
-                                // let error = ErrorData {
-                                //     message: line.to_string().into(),
-                                //     code: ErrorCode::INTERNAL_ERROR,
-                                //     data: None,
-                                // };
-                                // let error = JsonRpcError {
-                                //     error,
-                                //     id: NumberOrString::Number(0),
-                                //     jsonrpc: JsonRpcVersion2_0,
-                                // };
-                                // let message = JsonRpcMessage::Error(error);
-                                // let _ = tx.send(message).map_err(Error::from)?;
-
-                                let notification = model::Notification {
-                                    method: "notifications/message".to_string(),
-                                    params: serde_json::Map::from_iter(vec![(
-                                        "message".to_string(),
-                                        serde_json::Value::String(line.to_string()),
-                                    )]),
-                                    extensions: model::Extensions::new(),
-                                };
-                                let notification = model::JsonRpcNotification {
-                                    jsonrpc: model::JsonRpcVersion2_0,
-                                    notification,
-                                };
-                                let message: model::JsonRpcMessage<_, _, model::Notification> =
-                                    model::JsonRpcMessage::Notification(notification);
-                                let _ = tx.send(message).map_err(Error::from)?;
-                            }
-                        }
-                    }
-                    Err(error) => {
-                        let error = Error::from(error).trace();
-                        return Err(error);
-                    }
-                }
-            }
-        })
+        let max_message_size = self.max_message_size;
+        tokio::spawn(async move { forward_stderr(stderr, max_message_size, &tx).await })
     }
 
     /// Attach to the process stdin.
@@ -173,15 +301,10 @@ impl TransportAttachedProcess {
                                 } else {
                                     tracing::error!("Failed to write to stdin: {}", error);
                                 }
-                                let message = model::JsonRpcMessage::Error(model::JsonRpcError {
-                                    error: model::ErrorData::internal_error(
-                                        error.to_string(),
-                                        None,
-                                    ),
-                                    id: model::NumberOrString::Number(0),
-                                    jsonrpc: model::JsonRpcVersion2_0,
-                                });
-                                let _ = tx.send(message).map_err(Error::from);
+                                let error = Error::from(error);
+                                if let Some(message) = delivery_error(message, &error) {
+                                    let _ = tx.send(message).map_err(Error::from);
+                                }
                             }
                         }
                     }
@@ -201,9 +324,9 @@ impl TransportAttachedProcess {
 
     #[tracing::instrument(name = "AttachToProcess", skip_all)]
     async fn attach_to_process(&self) -> Result<AttachedProcess> {
-        Api::<v1::Pod>::namespaced(self.client.clone(), self.client.default_namespace())
+        <MCPServer as IntoResource<v1::Pod>>::resource_api(&self.server, &self.client)
             .attach(
-                &<MCPServer as IntoResource<v1::Pod>>::resource_name(&self.server),
+                &self.pod,
                 &AttachParams::default()
                     .tty(false)
                     .stdin(true)
@@ -220,27 +343,24 @@ impl TransportAttachedProcess {
 
     #[tracing::instrument(name = "IsAttached", skip_all)]
     async fn is_attached(&self) -> bool {
-        let is_stdout_attached = self.task_attach_stdout.is_some()
-            && !self.task_attach_stdout.as_ref().unwrap().is_finished();
-        let is_stdin_attached = self.task_attach_stdin.is_some()
-            && !self.task_attach_stdin.as_ref().unwrap().is_finished();
-        let is_stderr_attached = self.task_attach_stderr.is_some()
-            && !self.task_attach_stderr.as_ref().unwrap().is_finished();
-        is_stdout_attached && is_stdin_attached && is_stderr_attached
+        self.task_attach
+            .as_ref()
+            .is_some_and(|task| !task.is_finished())
     }
 
-    #[tracing::instrument(name = "BindStreams", skip_all)]
-    async fn bind_streams(&mut self) -> Result<&mut Self> {
-        if self.is_attached().await {
-            return Ok(self);
-        }
-        if let Some(task) = self.task_attach_stdout.take() {
-            task.abort();
-        }
-        if let Some(task) = self.task_attach_stdin.take() {
-            task.abort();
-        }
-        if let Some(task) = self.task_attach_stderr.take() {
+    /// Attach to the process and forward its stdin, stdout and stderr. If the previous process
+    /// was initialized, the pod was restarted and the new process is initialized again before
+    /// the sessions of the peers can use it.
+    #[tracing::instrument(name = "Attach", skip_all)]
+    async fn attach(&mut self) -> Result<()> {
+        for task in [
+            self.task_attach_stdin.take(),
+            self.task_attach_stdout.take(),
+            self.task_attach_stderr.take(),
+        ]
+        .into_iter()
+        .flatten()
+        {
             task.abort();
         }
 
@@ -250,11 +370,73 @@ impl TransportAttachedProcess {
         let stderr = process.stderr().unwrap();
 
         // --- Attach the stdout and stdin to the transport.
-        self.task_attach_stdin = Some(self.attach_stdout(stdout).await);
-        self.task_attach_stdout = Some(self.attach_stdin(stdin).await);
+        self.task_attach_stdin = Some(self.attach_stdin(stdin).await);
+        self.task_attach_stdout = Some(self.attach_stdout(stdout).await);
         self.task_attach_stderr = Some(self.attach_stderr(stderr).await);
 
-        // --- Spawn the task to read from stdin.
+        if self.handshake.is_initialized().await {
+            let stdout_rx = self.stdout_rx.resubscribe();
+            if let Err(error) = self.handshake.reinitialize(&self.stdin_tx, stdout_rx).await {
+                let _ = error.trace();
+                self.handshake.reset().await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Keep the process attached. Once its stdout ends, such as when the container restarted,
+    /// the process is attached again until the connect timeout elapses, after which the peers
+    /// are closed, failing their pending requests, and the task ends so the next subscription
+    /// attaches it from scratch.
+    async fn supervise(mut self) -> Result<()> {
+        loop {
+            if let Some(task) = self.task_attach_stdout.take() {
+                match task.await {
+                    Ok(Ok(())) => tracing::warn!("Process stdout ended, attaching again"),
+                    Ok(Err(error)) => tracing::warn!("Process stdout failed: {}", error),
+                    Err(error) => tracing::warn!("Process stdout task failed: {}", error),
+                }
+            }
+
+            // --- Retry until the container is running again, the pod may take a while to
+            // --- restart it and attaching fails in the meantime.
+            let timeout = Duration::from_secs(DEFAULT_TRANSPORT_CONNECT_TIMEOUT);
+            let result = tokio::time::timeout(timeout, async {
+                loop {
+                    match self.attach().await {
+                        Ok(()) => break,
+                        Err(error) => tracing::debug!("Failed to attach again: {}", error),
+                    }
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            })
+            .await;
+
+            // --- Give up on the process, the peers would otherwise wait forever for the
+            // --- results of the requests it will never answer.
+            if let Err(error) = result {
+                tracing::warn!("Process could not be attached again, closing its peers");
+                self.close_peers().await?;
+                return Err(error.into());
+            }
+        }
+    }
+
+    #[tracing::instrument(name = "BindStreams", skip_all)]
+    async fn bind_streams(&mut self) -> Result<&mut Self> {
+        if self.is_attached().await {
+            return Ok(self);
+        }
+        if let Some(task) = self.task_attach.take() {
+            task.abort();
+        }
+
+        // --- Attach to the process upfront so that failures are reported to the caller,
+        // --- then hand the process over to the task keeping it attached.
+        let mut process = self.handle();
+        process.attach().await?;
+        self.task_attach = Some(tokio::spawn(process.supervise()));
+
         Ok(self)
     }
 
@@ -263,7 +445,19 @@ impl TransportAttachedProcess {
     pub async fn get_peer(&self, id: &String) -> Result<TransportPeer> {
         match self.peers.read().await.get(id) {
             Some(peer) => Ok(peer.clone()),
-            None => Err(Error::generic(format!("Session with ID {id} not found"))),
+            None => Err(Error::generic(format!("Session with ID {id} not found"))
+                .with_name("E_SESSION_NOT_FOUND")
+                .with_status(StatusCode::NOT_FOUND)),
+        }
+    }
+
+    /// Close the peer with the given ID and remove it from the transport.
+    #[tracing::instrument(name = "RemovePeer", skip_all)]
+    pub async fn remove_peer(&self, id: &String) -> Result<()> {
+        let peer = self.peers.write().await.remove(id);
+        match peer {
+            Some(peer) => peer.close().await,
+            None => Ok(()),
         }
     }
 
@@ -274,7 +468,7 @@ impl TransportAttachedProcess {
 
         // --- Create a new peer for the transport.
         let peer = {
-            let peer = TransportPeer::new();
+            let peer = TransportPeer::new(self.router.clone());
             let id = peer.id.clone();
             let mut peers = self.peers.write().await;
             let _ = peers.insert(id, peer.clone());
@@ -283,6 +477,7 @@ impl TransportAttachedProcess {
         };
 
         // --- Connect the stdin and stdout channels to the peer.
+        peer.attach_handshake(self.handshake.clone()).await;
         peer.attach_input(self.stdin_tx.clone()).await?;
         peer.attach_output(self.stdout_rx.resubscribe()).await?;
 
@@ -292,24 +487,116 @@ impl TransportAttachedProcess {
     /// Close the transport and all its peers.
     #[tracing::instrument(name = "Close", skip_all)]
     pub async fn close(&mut self) -> Result<()> {
-        if let Some(task) = self.task_attach_stdout.take() {
-            task.abort();
-        }
-        if let Some(task) = self.task_attach_stdin.take() {
-            task.abort();
-        }
-        if let Some(task) = self.task_attach_stderr.take() {
+        if let Some(task) = self.task_attach.take() {
             task.abort();
         }
 
-        // --- Close all peers. This will ensure that all underlying channels and
-        // --- streams are properly closed and cleaned up.
+        self.close_peers().await
+    }
+
+    /// Close all peers and remove them from the transport. This will ensure that all
+    /// underlying channels and streams are properly closed and cleaned up, and that the
+    /// pending requests of the peers fail.
+    async fn close_peers(&self) -> Result<()> {
         let mut peers = self.peers.write().await;
         for peer in peers.values() {
             peer.close().await?;
         }
         peers.clear();
-
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn forward<T>(output: T, max_message_size: usize) -> Vec<model::JsonRpcMessage>
+    where
+        T: AsyncRead + Unpin,
+    {
+        let router = TransportRouter::new();
+        let (stdin, _) = broadcast::channel(16);
+        let (tx, mut rx) = broadcast::channel(16);
+        forward_stdout(output, max_message_size, &router, &stdin, &tx)
+            .await
+            .unwrap();
+        let mut messages = vec![];
+        while let Ok(message) = rx.try_recv() {
+            messages.push(message);
+        }
+        messages
+    }
+
+    #[tokio::test]
+    async fn test_forward_stdout_buffers_partial_lines() {
+        let (mut writer, reader) = tokio::io::duplex(64);
+        let output = b"{\"jsonrpc\":\"2.0\",\"method\":\"ping\",\"id\":1,\"params\":{}}\n\n{\"jsonrpc\":\"2.0\",\"method\":\"ping\",\"id\":2,\"params\":{}}\n";
+        let _writer = tokio::spawn(async move {
+            for chunk in output.chunks(7) {
+                writer.write_all(chunk).await.unwrap();
+                tokio::task::yield_now().await;
+            }
+        });
+        let messages = forward(reader, DEFAULT_MAX_MESSAGE_SIZE).await;
+        assert_eq!(messages.len(), 2);
+        assert!(messages
+            .iter()
+            .all(|message| matches!(message, model::JsonRpcMessage::Request(_))));
+    }
+
+    #[tokio::test]
+    async fn test_forward_stdout_turns_logs_into_notifications() {
+        let output =
+            b"Server started on stdio\n{\"jsonrpc\":\"2.0\",\"method\":\"ping\",\"id\":1,\"params\":{}}\n";
+        let messages = forward(output.as_slice(), DEFAULT_MAX_MESSAGE_SIZE).await;
+        assert_eq!(messages.len(), 2);
+        let Some(model::JsonRpcMessage::Notification(notification)) = messages.first() else {
+            panic!("Expected a logging notification");
+        };
+        assert_eq!(notification.notification.method, "notifications/message");
+        assert_eq!(
+            notification.notification.params.get("data"),
+            Some(&serde_json::Value::from("Server started on stdio"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forward_stderr_ends_with_the_output() {
+        let (tx, mut rx) = broadcast::channel(16);
+        let output = b"Warning: deprecated option\n\npartial";
+        forward_stderr(output.as_slice(), DEFAULT_MAX_MESSAGE_SIZE, &tx)
+            .await
+            .unwrap();
+        let mut messages = vec![];
+        while let Ok(message) = rx.try_recv() {
+            messages.push(message);
+        }
+        assert_eq!(messages.len(), 2);
+        let Some(model::JsonRpcMessage::Notification(notification)) = messages.first() else {
+            panic!("Expected a logging notification");
+        };
+        let params = &notification.notification.params;
+        assert_eq!(notification.notification.method, "notifications/message");
+        assert_eq!(params.get("level"), Some(&serde_json::Value::from("info")));
+        assert_eq!(
+            params.get("logger"),
+            Some(&serde_json::Value::from("stderr"))
+        );
+        assert_eq!(
+            params.get("data"),
+            Some(&serde_json::Value::from("Warning: deprecated option"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forward_stdout_discards_oversized_messages() {
+        let output = b"{\"jsonrpc\":\"2.0\",\"method\":\"notifications/progress\",\"params\":{\"data\":\"xxxxxxxxxxxxxxxx\"}}\n{\"jsonrpc\":\"2.0\",\"method\":\"ping\",\"id\":1,\"params\":{}}\n";
+        let messages = forward(output.as_slice(), 64).await;
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(
+            messages.first(),
+            Some(model::JsonRpcMessage::Request(_))
+        ));
+    }
+}
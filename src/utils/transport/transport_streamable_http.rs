@@ -0,0 +1,609 @@
+use super::transport_sse::delivery_error;
+use super::{TransportHandshake, TransportPeer, TransportRouter};
+use crate::{Error, MCPServer, Result};
+use crate::{
+    DEFAULT_POD_BUFFER_SIZE, DEFAULT_STREAMABLE_HTTP_ENDPOINT_PATH, MCP_SESSION_ID_HEADER,
+};
+use axum::http::StatusCode;
+use futures::StreamExt;
+use kube::ResourceExt;
+use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use rmcp::model;
+use sse_stream::SseStream;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch, RwLock};
+use tokio::task::JoinHandle;
+
+/// A transport for communicating with a server exposing the Streamable HTTP transport. The peers
+/// of the transport share a single session with the server, initialized once by the handshake
+/// of the transport, so that the `initialize` request of a client never replaces the session
+/// the other clients are using.
+pub struct TransportStreamableHttp {
+    server: MCPServer,
+    /// The base URL of the server, its `Service` by default or the `Pod` of an isolated session.
+    url: Option<String>,
+    http: reqwest::Client,
+    peers: Arc<RwLock<HashMap<String, TransportPeer>>>,
+    router: Arc<TransportRouter>,
+    handshake: Arc<TransportHandshake>,
+
+    session_rx: watch::Receiver<Option<String>>,
+    session_tx: watch::Sender<Option<String>>,
+    input_rx: broadcast::Receiver<model::ClientJsonRpcMessage>,
+    input_tx: broadcast::Sender<model::ClientJsonRpcMessage>,
+    output_rx: broadcast::Receiver<model::JsonRpcMessage>,
+    output_tx: broadcast::Sender<model::JsonRpcMessage>,
+
+    task_attach_stream: Option<JoinHandle<Result<()>>>,
+    task_attach_input: Option<JoinHandle<Result<()>>>,
+}
+
+impl Debug for TransportStreamableHttp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransportStreamableHttp")
+            .field("server", &self.server)
+            .field("session", &*self.session_rx.borrow())
+            .field("task", &self.task_attach_input)
+            .field(
+                "peers",
+                &self.peers.try_read().map(|peers| peers.len()).ok(),
+            )
+            .finish()
+    }
+}
+
+/// The output channel of the transport, along with the router the requests initiated by the
+/// server are assigned with before being broadcast to the peers, and the input channel the
+/// requests that cannot be assigned are answered on.
+type Output = (
+    Arc<TransportRouter>,
+    broadcast::Sender<model::JsonRpcMessage>,
+    broadcast::Sender<model::ClientJsonRpcMessage>,
+);
+
+/// Parse a JSON body, which is either a single message or a batch of messages, and
+/// forward every message it contains to the output channel. The `origin` is the upstream ID
+/// of the request the body answers, if any, the requests of the server it contains are
+/// assigned to the peer that sent it.
+fn forward_body(
+    body: &str,
+    (router, tx, input_tx): &Output,
+    origin: Option<&model::NumberOrString>,
+) -> Result<()> {
+    if body.trim().is_empty() {
+        return Ok(());
+    }
+    let messages = match serde_json::from_str(body)? {
+        serde_json::Value::Array(messages) => messages,
+        message => vec![message],
+    };
+    for message in messages {
+        match serde_json::from_value::<model::JsonRpcMessage>(message) {
+            Ok(message) => {
+                if let Some(reply) = router.assign(&message, origin) {
+                    let _ = input_tx.send(reply);
+                    continue;
+                }
+                let _ = tx.send(message).map_err(Error::from)?;
+            }
+            Err(error) => {
+                tracing::warn!("Ignoring malformed message from server: {}", error);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read an SSE response body until it ends and forward every message it contains.
+async fn forward_event_stream(
+    response: reqwest::Response,
+    output: &Output,
+    origin: Option<&model::NumberOrString>,
+) -> Result<()> {
+    let mut stream = Box::pin(SseStream::from_bytes_stream(response.bytes_stream()));
+    while let Some(event) = stream.next().await {
+        let event = event.map_err(|error| Error::generic(error.to_string()))?;
+        if !matches!(event.event.as_deref(), Some("message") | None) {
+            continue;
+        }
+        if let Some(data) = event.data {
+            forward_body(&data, output, origin)?;
+        }
+    }
+    Ok(())
+}
+
+/// Open the optional standalone SSE stream the server uses to push requests and notifications
+/// that are not related to a client request, and forward its messages until it ends.
+async fn open_event_stream(
+    http: &reqwest::Client,
+    url: &str,
+    session_id: &str,
+    output: &Output,
+) -> Result<()> {
+    let response = http
+        .get(url)
+        .header(ACCEPT, "text/event-stream")
+        .header(MCP_SESSION_ID_HEADER, session_id)
+        .send()
+        .await?;
+
+    // --- The GET stream is optional, servers that do not offer it answer with a 405.
+    if response.status() == StatusCode::METHOD_NOT_ALLOWED {
+        tracing::debug!("Server does not offer a standalone SSE stream");
+        return Ok(());
+    }
+
+    // --- The messages of the standalone stream are not related to any request.
+    let response = response.error_for_status()?;
+    forward_event_stream(response, output, None).await
+}
+
+/// Initialize a new session with the server once it dropped the previous one, using the
+/// `initialize` request of the handshake, so that the sessions of the peers remain usable.
+fn reinitialize(handshake: &Arc<TransportHandshake>, (_, tx, input_tx): &Output) {
+    let handshake = handshake.clone();
+    let input_tx = input_tx.clone();
+    let rx = tx.subscribe();
+    let _task = tokio::spawn(async move {
+        if let Err(error) = handshake.reinitialize(&input_tx, rx).await {
+            let _ = error.trace();
+            handshake.reset().await;
+        }
+    });
+}
+
+/// POST a single client message to the server and forward the messages of the response.
+async fn send_message(
+    http: &reqwest::Client,
+    url: &str,
+    session_tx: &watch::Sender<Option<String>>,
+    handshake: &Arc<TransportHandshake>,
+    message: &model::ClientJsonRpcMessage,
+    output: &Output,
+) -> Result<()> {
+    let is_initialize = matches!(
+        message,
+        model::JsonRpcMessage::Request(model::JsonRpcRequest {
+            request: model::ClientRequest::InitializeRequest(_),
+            ..
+        })
+    );
+
+    // --- An `initialize` request starts a new session on the server, every other
+    // --- message must be sent within the session assigned by the server.
+    let session_id = session_tx.borrow().clone();
+    let mut request = http
+        .post(url)
+        .header(ACCEPT, "application/json, text/event-stream")
+        .json(message);
+    if !is_initialize {
+        if let Some(session_id) = &session_id {
+            request = request.header(MCP_SESSION_ID_HEADER, session_id);
+        }
+    }
+    let response = request.send().await?;
+
+    // --- The server answers with a 404 once it has dropped our session. Only the first
+    // --- message rejected within that session forgets it and initializes a new one.
+    if response.status() == StatusCode::NOT_FOUND && !is_initialize && session_id.is_some() {
+        let is_dropped = session_tx.send_if_modified(|current| {
+            if *current != session_id {
+                return false;
+            }
+            *current = None;
+            true
+        });
+        if is_dropped {
+            reinitialize(handshake, output);
+        }
+    }
+    let response = response.error_for_status()?;
+
+    // --- Keep track of the session assigned by the server, if any.
+    let session_id = response
+        .headers()
+        .get(MCP_SESSION_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(ToOwned::to_owned);
+    if let Some(session_id) = session_id {
+        let _ = session_tx.send_if_modified(|current| {
+            if current.as_deref() == Some(session_id.as_str()) {
+                return false;
+            }
+            *current = Some(session_id);
+            true
+        });
+    }
+
+    // --- Notifications and responses are acknowledged with a 202 and an empty body.
+    if response.status() == StatusCode::ACCEPTED {
+        return Ok(());
+    }
+
+    // --- The messages of the response are related to the request that was posted, so the
+    // --- requests of the server it carries are attributed to the peer that sent it.
+    let origin = match message {
+        model::JsonRpcMessage::Request(request) => Some(request.id.clone()),
+        model::JsonRpcMessage::BatchRequest(items) => items.iter().find_map(|item| match item {
+            model::JsonRpcBatchRequestItem::Request(request) => Some(request.id.clone()),
+            model::JsonRpcBatchRequestItem::Notification(_) => None,
+        }),
+        _ => None,
+    };
+
+    // --- Requests are answered either with a JSON body or with an SSE stream that
+    // --- may carry server requests and notifications before the actual response.
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_owned();
+    if content_type.starts_with("text/event-stream") {
+        let output = output.clone();
+        let _stream = tokio::spawn(async move {
+            if let Err(error) = forward_event_stream(response, &output, origin.as_ref()).await {
+                let _ = error.trace();
+            }
+        });
+        Ok(())
+    } else {
+        let body = response.text().await?;
+        forward_body(&body, output, origin.as_ref())
+    }
+}
+
+impl TransportStreamableHttp {
+    pub fn new(server: &MCPServer) -> Self {
+        let (session_tx, session_rx) = watch::channel(None);
+        let (input_tx, input_rx) = broadcast::channel(DEFAULT_POD_BUFFER_SIZE);
+        let (output_tx, output_rx) = broadcast::channel(DEFAULT_POD_BUFFER_SIZE);
+        Self {
+            server: server.clone(),
+            url: server.service_url(),
+            http: reqwest::Client::new(),
+            peers: Arc::new(RwLock::new(HashMap::new())),
+            router: Arc::default(),
+            handshake: Arc::default(),
+            session_tx,
+            session_rx,
+            input_tx,
+            input_rx,
+            output_tx,
+            output_rx,
+            task_attach_stream: None,
+            task_attach_input: None,
+        }
+    }
+
+    /// Connect to the server at the given base URL rather than through its `Service`, such as
+    /// the ephemeral pod of an isolated session.
+    pub fn with_url(mut self, url: Option<String>) -> Self {
+        self.url = url;
+        self
+    }
+
+    /// Returns the URL of the MCP endpoint exposed by the server.
+    fn endpoint(&self) -> Result<String> {
+        let url = self.url.clone().ok_or_else(|| {
+            Error::generic("Server transport does not expose a port to connect to")
+        })?;
+        Ok(format!("{url}{DEFAULT_STREAMABLE_HTTP_ENDPOINT_PATH}"))
+    }
+
+    /// Attach to the input channel and post every message to the server. Every message is
+    /// posted from its own task, so that a slow request does not hold back the messages of the
+    /// other peers, such as the cancellation of that very request.
+    async fn attach_input(&mut self, url: String) -> JoinHandle<Result<()>> {
+        let output = (
+            self.router.clone(),
+            self.output_tx.clone(),
+            self.input_tx.clone(),
+        );
+        let http = self.http.clone();
+        let session_tx = self.session_tx.clone();
+        let handshake = self.handshake.clone();
+        let mut rx = self.input_rx.resubscribe();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(message) => {
+                        let http = http.clone();
+                        let url = url.clone();
+                        let session_tx = session_tx.clone();
+                        let handshake = handshake.clone();
+                        let output = output.clone();
+                        let _task = tokio::spawn(async move {
+                            let result = send_message(
+                                &http,
+                                &url,
+                                &session_tx,
+                                &handshake,
+                                &message,
+                                &output,
+                            )
+                            .await;
+
+                            // --- If the message could not be delivered, answer its requests with an error
+                            // --- so that the client does not wait for a response that will never come.
+                            if let Err(error) = result {
+                                tracing::error!("Failed to post message to server: {}", error);
+                                if let Some(message) = delivery_error(message, &error) {
+                                    let _ = output.1.send(message).map_err(Error::from);
+                                }
+                            }
+                        });
+                    }
+                    Err(error) => match error {
+                        broadcast::error::RecvError::Lagged(_) => {
+                            tracing::warn!("Input receiver lagged, some messages may be lost");
+                        }
+                        broadcast::error::RecvError::Closed => {
+                            tracing::info!("Input receiver closed, stopping input task");
+                            return Ok(());
+                        }
+                    },
+                }
+            }
+        })
+    }
+
+    /// Attach to the standalone SSE stream of the server. The stream is (re)opened every time
+    /// the server assigns a new session, and closed when the session is dropped.
+    async fn attach_stream(&mut self, url: String) -> JoinHandle<Result<()>> {
+        let output = (
+            self.router.clone(),
+            self.output_tx.clone(),
+            self.input_tx.clone(),
+        );
+        let http = self.http.clone();
+        let mut session_rx = self.session_rx.clone();
+        tokio::spawn(async move {
+            loop {
+                let session_id = session_rx.borrow_and_update().clone();
+                if let Some(session_id) = session_id {
+                    tokio::select! {
+                        result = open_event_stream(&http, &url, &session_id, &output) => {
+                            if let Err(error) = result {
+                                let _ = error.trace();
+                            }
+                        }
+                        _ = session_rx.changed() => continue,
+                    }
+                }
+                if session_rx.changed().await.is_err() {
+                    tracing::info!("Session channel closed, stopping stream task");
+                    return Ok(());
+                }
+            }
+        })
+    }
+
+    #[tracing::instrument(name = "IsAttached", skip_all)]
+    async fn is_attached(&self) -> bool {
+        let is_stream_attached = self
+            .task_attach_stream
+            .as_ref()
+            .is_some_and(|task| !task.is_finished());
+        let is_input_attached = self
+            .task_attach_input
+            .as_ref()
+            .is_some_and(|task| !task.is_finished());
+        is_stream_attached && is_input_attached
+    }
+
+    #[tracing::instrument(name = "BindStreams", skip_all)]
+    async fn bind_streams(&mut self) -> Result<&mut Self> {
+        if self.is_attached().await {
+            return Ok(self);
+        }
+        if let Some(task) = self.task_attach_stream.take() {
+            task.abort();
+        }
+        if let Some(task) = self.task_attach_input.take() {
+            task.abort();
+        }
+
+        // --- Unlike the SSE transport there is no long-lived connection to open upfront,
+        // --- the session is established by the first `initialize` request of a client.
+        let url = self.endpoint()?;
+        self.task_attach_input = Some(self.attach_input(url.clone()).await);
+        self.task_attach_stream = Some(self.attach_stream(url).await);
+
+        Ok(self)
+    }
+
+    /// Get a peer by ID.
+    #[tracing::instrument(name = "GetPeer", skip_all)]
+    pub async fn get_peer(&self, id: &String) -> Result<TransportPeer> {
+        match self.peers.read().await.get(id) {
+            Some(peer) => Ok(peer.clone()),
+            None => Err(Error::generic(format!("Session with ID {id} not found"))
+                .with_name("E_SESSION_NOT_FOUND")
+                .with_status(StatusCode::NOT_FOUND)),
+        }
+    }
+
+    /// Close the peer with the given ID and remove it from the transport.
+    #[tracing::instrument(name = "RemovePeer", skip_all)]
+    pub async fn remove_peer(&self, id: &String) -> Result<()> {
+        let peer = self.peers.write().await.remove(id);
+        match peer {
+            Some(peer) => peer.close().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Create a new peer connected to the server.
+    #[tracing::instrument(name = "Subscribe", skip_all, fields(name = self.server.name_any()))]
+    pub async fn subscribe(&mut self) -> Result<TransportPeer> {
+        let _ = self.bind_streams().await?;
+
+        // --- Create a new peer for the transport.
+        let peer = {
+            let peer = TransportPeer::new(self.router.clone());
+            let id = peer.id.clone();
+            let mut peers = self.peers.write().await;
+            let _ = peers.insert(id, peer.clone());
+            drop(peers);
+            peer
+        };
+
+        // --- Connect the input and output channels to the peer, and let the handshake of the
+        // --- transport answer its `initialize` request once the session is initialized.
+        peer.attach_input(self.input_tx.clone()).await?;
+        peer.attach_output(self.output_rx.resubscribe()).await?;
+        peer.attach_handshake(self.handshake.clone()).await;
+
+        Ok(peer)
+    }
+
+    /// Close the transport and all its peers.
+    #[tracing::instrument(name = "Close", skip_all)]
+    pub async fn close(&mut self) -> Result<()> {
+        if let Some(task) = self.task_attach_stream.take() {
+            task.abort();
+        }
+        if let Some(task) = self.task_attach_input.take() {
+            task.abort();
+        }
+
+        // --- Explicitly terminate the session on the server. Servers are allowed to
+        // --- refuse this with a 405, in which case the session simply expires.
+        self.handshake.reset().await;
+        if let Some(session_id) = self.session_tx.send_replace(None) {
+            let url = self.endpoint()?;
+            let result = self
+                .http
+                .delete(url)
+                .header(MCP_SESSION_ID_HEADER, session_id)
+                .send()
+                .await;
+            if let Err(error) = result {
+                tracing::warn!("Failed to terminate server session: {}", error);
+            }
+        }
+
+        // --- Close all peers. This will ensure that all underlying channels and
+        // --- streams are properly closed and cleaned up.
+        let mut peers = self.peers.write().await;
+        for peer in peers.values() {
+            peer.close().await?;
+        }
+        peers.clear();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MCPServerSpec;
+    use axum::extract::State;
+    use axum::http::HeaderMap;
+    use axum::response::{IntoResponse, Response};
+    use axum::Json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A server assigning a new session to every `initialize` request, and rejecting the
+    /// messages sent within any other session than the first one.
+    async fn upstream(
+        State(sessions): State<Arc<AtomicUsize>>,
+        headers: HeaderMap,
+        Json(message): Json<serde_json::Value>,
+    ) -> Response {
+        let id = message.get("id").cloned();
+        if message["method"] == "initialize" {
+            let session = sessions.fetch_add(1, Ordering::SeqCst) + 1;
+            let result = serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": {
+                    "protocolVersion": "2025-03-26",
+                    "capabilities": {},
+                    "serverInfo": { "name": "upstream", "version": "1.0.0" }
+                }
+            });
+            let session = [(MCP_SESSION_ID_HEADER, format!("session-{session}"))];
+            return (session, Json(result)).into_response();
+        }
+        let session = headers.get(MCP_SESSION_ID_HEADER);
+        if session.and_then(|value| value.to_str().ok()) != Some("session-1") {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        match id {
+            Some(id) => Json(serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": {} }))
+                .into_response(),
+            None => StatusCode::ACCEPTED.into_response(),
+        }
+    }
+
+    fn message(value: serde_json::Value) -> model::ClientJsonRpcMessage {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn initialize(id: u32) -> model::ClientJsonRpcMessage {
+        message(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "initialize",
+            "params": {
+                "protocolVersion": "2025-03-26",
+                "capabilities": {},
+                "clientInfo": { "name": "test", "version": "1.0.0" }
+            }
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_peers_share_the_upstream_session() {
+        let sessions = Arc::new(AtomicUsize::new(0));
+        let router = axum::Router::new()
+            .route("/mcp", axum::routing::post(upstream))
+            .with_state(sessions.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let _server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let server = MCPServer::new("my-server", MCPServerSpec::default());
+        let mut transport = TransportStreamableHttp::new(&server).with_url(Some(url));
+        let initialized = message(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/initialized"
+        }));
+
+        // --- The clients initialize one after the other, only the first one reaches the server.
+        let a = transport.subscribe().await.unwrap();
+        let response = a.send_request(initialize(1)).await.unwrap().unwrap();
+        assert_eq!(
+            response.into_response().unwrap().1,
+            model::NumberOrString::Number(1)
+        );
+        assert!(a.send_request(initialized.clone()).await.unwrap().is_none());
+        let b = transport.subscribe().await.unwrap();
+        let response = b.send_request(initialize(1)).await.unwrap().unwrap();
+        assert_eq!(
+            response.into_response().unwrap().1,
+            model::NumberOrString::Number(1)
+        );
+        assert!(b.send_request(initialized).await.unwrap().is_none());
+        assert_eq!(sessions.load(Ordering::SeqCst), 1);
+
+        // --- Both clients keep using the session of the first one.
+        for (peer, id) in [(&a, 2), (&b, 2)] {
+            let ping = message(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "ping",
+                "params": {}
+            }));
+            let response = peer.send_request(ping).await.unwrap().unwrap();
+            assert!(matches!(response, model::JsonRpcMessage::Response(_)));
+        }
+        transport.close().await.unwrap();
+    }
+}